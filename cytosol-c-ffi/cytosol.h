@@ -17,6 +17,10 @@ typedef enum cyt_value_type {
         CYT_VALUE_TYPE_INTEGER,
         CYT_VALUE_TYPE_STRING,
         CYT_VALUE_TYPE_RECORD,
+        CYT_VALUE_TYPE_VARIANT,
+        CYT_VALUE_TYPE_FLOAT,
+        CYT_VALUE_TYPE_LIST,
+        CYT_VALUE_TYPE_NONE,
 } cyt_value_type;
 
 typedef struct cyt_cell_env cyt_cell_env;
@@ -107,6 +111,16 @@ void cyt_driver_runner_run(struct cyt_driver_runner *r,
 
 struct cyt_value_buffer *cyt_value_buffer_new(size_t size);
 
+/**
+ * Create a value buffer for the fields of the record with id `record_id`.
+ *
+ * Fields with a default value are already set to it and all other fields are
+ * `none`. The fields that are not optional have to be set, otherwise
+ * `cyt_cellenv_add_record` rejects the buffer.
+ */
+struct cyt_value_buffer *cyt_value_buffer_new_record_fields(const struct cyt_program *prog,
+                                                            struct cyt_record_id record_id);
+
 size_t cyt_value_buffer_get_size(const struct cyt_value_buffer *buf);
 
 void cyt_value_buffer_set_bool(struct cyt_value_buffer *buf,
@@ -117,6 +131,15 @@ void cyt_value_buffer_set_int(struct cyt_value_buffer *buf,
                               size_t idx,
                               ptrdiff_t i);
 
+void cyt_value_buffer_set_float(struct cyt_value_buffer *buf,
+                                size_t idx,
+                                double f);
+
+/**
+ * Set the value at `idx` to `none`, the value of unset optional fields.
+ */
+void cyt_value_buffer_set_none(struct cyt_value_buffer *buf, size_t idx);
+
 /**
  * # Safety
  * `s` must be a valid pointer to a UTF-8 and NUL-terminated string.
@@ -133,6 +156,27 @@ void cyt_value_buffer_set_record(struct cyt_value_buffer *buf,
                                  size_t idx,
                                  struct cyt_value_buffer *fields);
 
+/**
+ * Set the value at `idx` to the enum variant with index `variant`.
+ *
+ * # Safety
+ * `fields` will be consumed, do **not** call the destructor on the value buffer
+ */
+void cyt_value_buffer_set_variant(struct cyt_value_buffer *buf,
+                                  size_t idx,
+                                  size_t variant,
+                                  struct cyt_value_buffer *fields);
+
+/**
+ * Set the value at `idx` to a list containing the values of `elems`.
+ *
+ * # Safety
+ * `elems` will be consumed, do **not** call the destructor on the value buffer
+ */
+void cyt_value_buffer_set_list(struct cyt_value_buffer *buf,
+                               size_t idx,
+                               struct cyt_value_buffer *elems);
+
 void cyt_value_buffer_destroy(struct cyt_value_buffer *buf);
 
 /**
@@ -161,6 +205,15 @@ bool cyt_value_buffer_get_int(const struct cyt_value_buffer *buf,
                               size_t idx,
                               ptrdiff_t *out_i);
 
+/**
+ * Get the float value in `buf` at `idx` by writing it in `out_f`.
+ *
+ * If the value is not a float then `false` is returned, `true` otherwise.
+ */
+bool cyt_value_buffer_get_float(const struct cyt_value_buffer *buf,
+                                size_t idx,
+                                double *out_f);
+
 /**
  * Get the string value in `buf` at `idx` by writing a pointer to `out_ptr`
  * and the length to `out_len`.
@@ -187,17 +240,48 @@ bool cyt_value_buffer_get_record_fields(const struct cyt_value_buffer *buf,
                                         size_t idx,
                                         struct cyt_value_buffer **out_value);
 
+/**
+ * Get the elements of the list value in `buf` at `idx` as a new value buffer.
+ *
+ * The value buffer in `out_elems` will be owned, so the `destroy` function
+ * needs to be called.
+ *
+ * If the value at `idx` is not a list or if `idx` is out of bounds then
+ * `false` is returned, `true` otherwise.
+ */
+bool cyt_value_buffer_get_list(const struct cyt_value_buffer *buf,
+                               size_t idx,
+                               struct cyt_value_buffer **out_elems);
+
+/**
+ * Get the variant index and the field value buffer of the enum value in
+ * `buf` at `idx`.
+ *
+ * The value buffer in `out_fields` will be owned, so the `destroy` function
+ * needs to be called.
+ *
+ * If the value at `idx` is not an enum value or if `idx` is out of bounds
+ * then `false` is returned, `true` otherwise.
+ */
+bool cyt_value_buffer_get_variant(const struct cyt_value_buffer *buf,
+                                  size_t idx,
+                                  size_t *out_variant,
+                                  struct cyt_value_buffer **out_fields);
+
 struct cyt_cell_env *cyt_cellenv_new(void);
 
 void cyt_cellenv_destroy(struct cyt_cell_env *cell_env);
 
 /**
  * Add a record with id `record_id` to the environment `quantity` times.
+ * Returns `false` and adds nothing if the values in `fields` don't fit the
+ * fields of the record.
  *
  * The ownership of `fields` will be transferred, so **do not** call the
  * destroy function on this value buffer.
  */
-void cyt_cellenv_add_record(struct cyt_cell_env *cell_env,
+bool cyt_cellenv_add_record(struct cyt_cell_env *cell_env,
+                            const struct cyt_program *prog,
                             size_t quantity,
                             struct cyt_record_id record_id,
                             struct cyt_value_buffer *fields);
@@ -219,6 +303,44 @@ void cyt_exec_state_set_extern_function(struct cyt_exec_state *exec_state,
                                         void (*f)(void*, const struct cyt_value_buffer*),
                                         void *data);
 
+/**
+ * Override the value of the `param` item called `name` with the value in
+ * `buf` at index `idx`. Returns `false` and changes nothing if the index is
+ * out of bounds, `prog` has no such parameter or the value does not fit its
+ * type.
+ *
+ * # Safety
+ * `name` must be a valid pointer to a UTF-8 and NUL-terminated string.
+ */
+bool cyt_exec_state_set_param(struct cyt_exec_state *exec_state,
+                              const struct cyt_program *prog,
+                              const char *name,
+                              const struct cyt_value_buffer *buf,
+                              size_t idx);
+
+/**
+ * Restart the random number generator of the execution state from `seed`.
+ * Runs with the same seed make the same random choices.
+ */
+void cyt_exec_state_set_seed(struct cyt_exec_state *exec_state, uint64_t seed);
+
+/**
+ * The number of iterations that ran so far.
+ */
+size_t cyt_exec_state_iteration(const struct cyt_exec_state *exec_state);
+
+/**
+ * Set the simulated time that `@time` evaluates to.
+ */
+void cyt_exec_state_set_time(struct cyt_exec_state *exec_state, double time);
+
+/**
+ * Set how much the simulated time advances after every iteration. The
+ * default is `1.0`.
+ */
+void cyt_exec_state_set_time_step(struct cyt_exec_state *exec_state,
+                                  double time_step);
+
 #ifdef __cplusplus
 } // extern "C"
 #endif // __cplusplus
@@ -183,6 +183,10 @@ pub enum ValueType {
     Integer,
     String,
     Record,
+    Variant,
+    Float,
+    List,
+    None,
 }
 
 pub struct ValueBuffer(Vec<Value>);
@@ -192,6 +196,23 @@ pub extern "C" fn cyt_value_buffer_new(size: usize) -> Box<ValueBuffer> {
     Box::new(ValueBuffer(vec![Value::Integer(0); size]))
 }
 
+/// Create a value buffer for the fields of the record with id `record_id`.
+///
+/// Fields with a default value are already set to it and all other fields are
+/// `none`. The fields that are not optional have to be set, otherwise
+/// `cyt_cellenv_add_record` rejects the buffer.
+#[no_mangle]
+pub extern "C" fn cyt_value_buffer_new_record_fields(
+    prog: &Program,
+    record_id: RecordId,
+) -> Box<ValueBuffer> {
+    let id = record_id.to_id();
+    let fields = (0..prog.0[id].fields.len())
+        .map(|idx| cytosol::runtime::default_field_value(&prog.0, id, idx).unwrap_or(Value::None))
+        .collect();
+    Box::new(ValueBuffer(fields))
+}
+
 #[no_mangle]
 pub extern "C" fn cyt_value_buffer_get_size(buf: &ValueBuffer) -> usize {
     buf.0.len()
@@ -211,6 +232,21 @@ pub extern "C" fn cyt_value_buffer_set_int(buf: &mut ValueBuffer, idx: usize, i:
     }
 }
 
+#[no_mangle]
+pub extern "C" fn cyt_value_buffer_set_float(buf: &mut ValueBuffer, idx: usize, f: f64) {
+    if let Some(val) = buf.0.get_mut(idx) {
+        *val = Value::Float(f);
+    }
+}
+
+/// Set the value at `idx` to `none`, the value of unset optional fields.
+#[no_mangle]
+pub extern "C" fn cyt_value_buffer_set_none(buf: &mut ValueBuffer, idx: usize) {
+    if let Some(val) = buf.0.get_mut(idx) {
+        *val = Value::None;
+    }
+}
+
 /// # Safety
 /// `s` must be a valid pointer to a UTF-8 and NUL-terminated string.
 #[no_mangle]
@@ -238,6 +274,37 @@ pub extern "C" fn cyt_value_buffer_set_record(
     }
 }
 
+/// Set the value at `idx` to the enum variant with index `variant`.
+///
+/// # Safety
+/// `fields` will be consumed, do **not** call the destructor on the value buffer
+#[no_mangle]
+pub extern "C" fn cyt_value_buffer_set_variant(
+    buf: &mut ValueBuffer,
+    idx: usize,
+    variant: usize,
+    fields: Box<ValueBuffer>,
+) {
+    if let Some(val) = buf.0.get_mut(idx) {
+        *val = Value::Variant(variant, fields.0);
+    }
+}
+
+/// Set the value at `idx` to a list containing the values of `elems`.
+///
+/// # Safety
+/// `elems` will be consumed, do **not** call the destructor on the value buffer
+#[no_mangle]
+pub extern "C" fn cyt_value_buffer_set_list(
+    buf: &mut ValueBuffer,
+    idx: usize,
+    elems: Box<ValueBuffer>,
+) {
+    if let Some(val) = buf.0.get_mut(idx) {
+        *val = Value::List(elems.0);
+    }
+}
+
 #[no_mangle]
 pub extern "C" fn cyt_value_buffer_destroy(buf: Box<ValueBuffer>) {
     drop(buf);
@@ -251,8 +318,12 @@ pub extern "C" fn cyt_value_get_type(buf: &ValueBuffer, idx: usize) -> ValueType
     match buf.0.get(idx) {
         Some(Value::Bool(_)) => ValueType::Bool,
         Some(Value::Integer(_)) => ValueType::Integer,
+        Some(Value::Float(_)) => ValueType::Float,
         Some(Value::String(_)) => ValueType::String,
         Some(Value::Record(_)) => ValueType::Record,
+        Some(Value::Variant(..)) => ValueType::Variant,
+        Some(Value::List(_)) => ValueType::List,
+        Some(Value::None) => ValueType::None,
         None => ValueType::Integer,
     }
 }
@@ -293,6 +364,24 @@ pub extern "C" fn cyt_value_buffer_get_int(
     }
 }
 
+/// Get the float value in `buf` at `idx` by writing it in `out_f`.
+///
+/// If the value is not a float then `false` is returned, `true` otherwise.
+#[no_mangle]
+pub extern "C" fn cyt_value_buffer_get_float(
+    buf: &ValueBuffer,
+    idx: usize,
+    out_f: &mut f64,
+) -> bool {
+    match buf.0.get(idx) {
+        Some(Value::Float(v)) => {
+            *out_f = *v;
+            true
+        }
+        _ => false,
+    }
+}
+
 /// Get the string value in `buf` at `idx` by writing a pointer to `out_ptr`
 /// and the length to `out_len`.
 ///
@@ -338,6 +427,53 @@ pub extern "C" fn cyt_value_buffer_get_record_fields(
     }
 }
 
+/// Get the elements of the list value in `buf` at `idx` as a new value buffer.
+///
+/// The value buffer in `out_elems` will be owned, so the `destroy` function
+/// needs to be called.
+///
+/// If the value at `idx` is not a list or if `idx` is out of bounds then
+/// `false` is returned, `true` otherwise.
+#[no_mangle]
+pub extern "C" fn cyt_value_buffer_get_list(
+    buf: &ValueBuffer,
+    idx: usize,
+    out_elems: &mut *mut ValueBuffer,
+) -> bool {
+    match buf.0.get(idx) {
+        Some(Value::List(elems)) => {
+            *out_elems = Box::into_raw(Box::new(ValueBuffer(elems.clone())));
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Get the variant index and the field value buffer of the enum value in
+/// `buf` at `idx`.
+///
+/// The value buffer in `out_fields` will be owned, so the `destroy` function
+/// needs to be called.
+///
+/// If the value at `idx` is not an enum value or if `idx` is out of bounds
+/// then `false` is returned, `true` otherwise.
+#[no_mangle]
+pub extern "C" fn cyt_value_buffer_get_variant(
+    buf: &ValueBuffer,
+    idx: usize,
+    out_variant: &mut usize,
+    out_fields: &mut *mut ValueBuffer,
+) -> bool {
+    match buf.0.get(idx) {
+        Some(Value::Variant(variant, fields)) => {
+            *out_variant = *variant;
+            *out_fields = Box::into_raw(Box::new(ValueBuffer(fields.clone())));
+            true
+        }
+        _ => false,
+    }
+}
+
 //
 // Cell environment
 //
@@ -355,17 +491,26 @@ pub extern "C" fn cyt_cellenv_destroy(cell_env: Box<CellEnv>) {
 }
 
 /// Add a record with id `record_id` to the environment `quantity` times.
+/// Returns `false` and adds nothing if the values in `fields` don't fit the
+/// fields of the record.
 ///
 /// The ownership of `fields` will be transferred, so **do not** call the
 /// destroy function on this value buffer.
 #[no_mangle]
 pub extern "C" fn cyt_cellenv_add_record(
     cell_env: &mut CellEnv,
+    prog: &Program,
     quantity: usize,
     record_id: RecordId,
     fields: Box<ValueBuffer>,
-) {
-    cell_env.0.add_record(quantity, record_id.to_id(), fields.0);
+) -> bool {
+    let id = record_id.to_id();
+    if !cytosol::runtime::fields_have_types(&prog.0, id, &fields.0) {
+        return false;
+    }
+
+    cell_env.0.add_record(quantity, id, fields.0);
+    true
 }
 
 #[no_mangle]
@@ -412,3 +557,55 @@ pub unsafe extern "C" fn cyt_exec_state_set_extern_function(
         f(data, &buf as *const _);
     });
 }
+
+/// Override the value of the `param` item called `name` with the value in
+/// `buf` at index `idx`. Returns `false` and changes nothing if the index is
+/// out of bounds, `prog` has no such parameter or the value does not fit its
+/// type.
+///
+/// # Safety
+/// `name` must be a valid pointer to a UTF-8 and NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn cyt_exec_state_set_param(
+    exec_state: &mut ExecutionState,
+    prog: &Program,
+    name: *const std::os::raw::c_char,
+    buf: &ValueBuffer,
+    idx: usize,
+) -> bool {
+    let name = CStr::from_ptr(name);
+
+    if let Some(val) = buf.0.get(idx) {
+        let ctx = exec_state.0.program_context();
+        ctx.set_param(&prog.0, &name.to_string_lossy(), val.clone())
+            .is_ok()
+    } else {
+        false
+    }
+}
+
+/// Restart the random number generator of the execution state from `seed`.
+/// Runs with the same seed make the same random choices.
+#[no_mangle]
+pub extern "C" fn cyt_exec_state_set_seed(exec_state: &mut ExecutionState, seed: u64) {
+    exec_state.0.program_context().set_seed(seed);
+}
+
+/// The number of iterations that ran so far.
+#[no_mangle]
+pub extern "C" fn cyt_exec_state_iteration(exec_state: &ExecutionState) -> usize {
+    exec_state.0.iteration()
+}
+
+/// Set the simulated time that `@time` evaluates to.
+#[no_mangle]
+pub extern "C" fn cyt_exec_state_set_time(exec_state: &mut ExecutionState, time: f64) {
+    exec_state.0.set_time(time);
+}
+
+/// Set how much the simulated time advances after every iteration. The
+/// default is `1.0`.
+#[no_mangle]
+pub extern "C" fn cyt_exec_state_set_time_step(exec_state: &mut ExecutionState, time_step: f64) {
+    exec_state.0.set_time_step(time_step);
+}
@@ -1,8 +1,12 @@
 use std::collections::BTreeMap;
 
-use ahash::AHashMap as HashMap;
+use ahash::{AHashMap as HashMap, AHashSet as HashSet};
 
-use crate::{types::*, Program};
+use crate::{
+    templates::{substitute_gene, substitute_rule, TypeArgs},
+    types::*,
+    Program,
+};
 
 use ast::{HasFC, Identifier, InfixOperator, PrefixOperator, FC};
 use cytosol_syntax as ast;
@@ -30,6 +34,67 @@ pub enum Error {
     #[error("Recursive record definitions")]
     RecursiveRecordDefinitions { defs: Vec<FC> },
 
+    #[error("Template {} unknown", .name.1)]
+    UnknownTemplate { name: Identifier },
+
+    #[error("Template {} has duplicated parameter {}", .template_name.1, .param.1)]
+    DuplicateTemplateParameter {
+        template_name: Identifier,
+        param: Identifier,
+        first_occurance: FC,
+    },
+
+    #[error("Template {} takes {} type arguments but {} were given", .template_name.1, .expected, .found)]
+    TemplateArgumentCount {
+        template_name: Identifier,
+        instantiation: FC,
+        expected: usize,
+        found: usize,
+    },
+
+    #[error("Template argument {} is not a record", .arg.1)]
+    TemplateArgumentNotRecord { arg: Identifier },
+
+    #[error("{} in instantiation of template {}", .error, .template_name.1)]
+    InTemplateInstantiation {
+        template_name: Identifier,
+        instantiation: FC,
+        error: Box<Error>,
+    },
+
+    #[error("Record family {} unknown", .name.1)]
+    UnknownRecordFamily { name: Identifier },
+
+    #[error("Record {} is not compatible with field {} of family {}", .record_name.1, .field_name.1, .family_name.1)]
+    IncompatibleFamilyField {
+        record_name: Identifier,
+        family_name: Identifier,
+        field_name: Identifier,
+        expected: TypeId,
+        /// The field of the record with the same name and its type
+        found: Option<(Identifier, TypeId)>,
+    },
+
+    #[error("Record {} can't be in family {}, which is in a family itself", .record_name.1, .family_name.1)]
+    NestedRecordFamily {
+        record_name: Identifier,
+        family_name: Identifier,
+    },
+
+    #[error("Enum {} has duplicated variant {}", .enum_name.1, .variant.1)]
+    DuplicateEnumVariant {
+        enum_name: Identifier,
+        variant: Identifier,
+        first_occurance: FC,
+    },
+
+    #[error("Variant {} has duplicated field {}", .variant.1, .field_name.1)]
+    DuplicateVariantField {
+        variant: Identifier,
+        field_name: Identifier,
+        first_occurance: FC,
+    },
+
     #[error("Name {} rebound", .name.1)]
     NameRebound {
         item_fc: FC,
@@ -49,6 +114,9 @@ pub enum Error {
     #[error("Type {} can't be used as an execution factor", .name.1)]
     InvalidFactorType { name: Identifier },
 
+    #[error("Type {} can't be used as a product", .name.1)]
+    InvalidProductType { name: Identifier },
+
     #[error("Binding number of 0 is not supported, use `when [{}] = 0` instead", .type_name.1)]
     ZeroBind {
         item: FC,
@@ -103,6 +171,12 @@ pub enum Error {
         field: Identifier,
     },
 
+    #[error("Parameter {} can't be used in a field pattern", .name.1)]
+    ParamInPattern {
+        name: Identifier,
+        param_name: Identifier,
+    },
+
     #[error("Undefined variable {}", .name.1)]
     UndefinedVariable {
         name: Identifier,
@@ -148,20 +222,130 @@ pub enum Error {
         parameter: Identifier,
     },
 
+    #[error("Function {} does not exist", .name.1)]
+    UnknownFunction { name: Identifier },
+
+    #[error("Builtin function {} can't be used with `..`", .name.1)]
+    BuiltinCallWithBase { name: Identifier, base_fc: FC },
+
+    #[error("Type {} is not an enum", .name.1)]
+    NotAnEnum { name: Identifier },
+
+    #[error("Enum {} has no variant {}", .enum_name.1, .variant.1)]
+    UnknownEnumVariant {
+        enum_name: Identifier,
+        variant: Identifier,
+    },
+
+    #[error("Variant {} is missing field {}", .variant.1, .missing_field.1)]
+    VariantMissingField {
+        variant: Identifier,
+        use_fc: FC,
+        missing_field: Identifier,
+    },
+
+    #[error("Duplicated field {} on variant {}", .duplicate_field.1, .variant.1)]
+    VariantDuplicateField {
+        variant: Identifier,
+        duplicate_field: Identifier,
+        original_field: Identifier,
+    },
+
+    #[error("Unknown field {} on variant {}", .field.1, .variant.1)]
+    VariantUnknownField {
+        variant: Identifier,
+        field: Identifier,
+    },
+
+    #[error("Match on non-enum type")]
+    MatchOnNonEnum { expr: ExpressionId, type_id: TypeId },
+
+    #[error("Match is not exhaustive")]
+    NonExhaustiveMatch { fc: FC, missing: Vec<Identifier> },
+
+    #[error("Match arm is unreachable")]
+    UnreachableMatchArm { fc: FC },
+
+    #[error("Missing parameter `{}` in call to `{}`", .missing_param, .func_name.1)]
+    BuiltinMissingParameter {
+        func_name: Identifier,
+        call_fc: FC,
+        missing_param: &'static str,
+    },
+
+    #[error("Unknown parameter `{}` in call to `{}`", .parameter.1, .func_name.1)]
+    BuiltinUnknownParameter {
+        func_name: Identifier,
+        parameter: Identifier,
+    },
+
+    #[error("Type mismatch in call to `{}`", .func_name.1)]
+    TypeMismatchBuiltinCall {
+        func_name: Identifier,
+        param_name: &'static str,
+        expr: ExpressionId,
+        expected_types: Vec<TypeId>,
+    },
+
+    #[error("Parameter `{}` of `{}` must be a list", .param_name, .func_name.1)]
+    BuiltinExpectedList {
+        func_name: Identifier,
+        param_name: &'static str,
+        expr: ExpressionId,
+    },
+
+    #[error("`is` test on a value that is not optional")]
+    OptionTestOnNonOptional { expr: ExpressionId, type_id: TypeId },
+
+    #[error("Empty list without an element type")]
+    UntypedEmptyList { fc: FC },
+
     #[error("Concentration of non-record type `{}`", .type_name.1)]
     ConcentrationOfNonRecordType { fc: FC, type_name: Identifier },
 
     #[error("When clause must be of type bool")]
     WhenClauseMustBeOfTypeBool { expr: ExpressionId, type_id: TypeId },
+
+    #[error("Recursive constant definitions")]
+    RecursiveConstDefinitions { defs: Vec<FC> },
+
+    #[error("Expression can't be evaluated at compile time")]
+    NonConstantExpression { fc: FC },
+
+    #[error("Instance field used outside of a concentration filter")]
+    InstanceFieldOutsideFilter { fc: FC },
+
+    #[error("Aggregate of field {} that is not a number", .field_name.1)]
+    AggregateOfNonNumber {
+        field_name: Identifier,
+        type_id: TypeId,
+    },
+
+    #[error("Named binding `{}` of a reversible rule can't be reversed", .name.1)]
+    IrreversibleNamedBinding { rule_fc: FC, name: Identifier },
+
+    #[error("Product with a base value of a reversible rule can't be reversed")]
+    IrreversibleProductBase { rule_fc: FC, base_fc: FC },
+
+    #[error("`gene on` needs a single factor without a quantity")]
+    InvalidGeneTrigger { gene_fc: FC, on_fc: FC },
+
+    #[error("Computed quantity of a reversible rule can't be reversed")]
+    IrreversibleQuantity { rule_fc: FC, quantity_fc: FC },
+
+    #[error("Field binding `{}` of a reversible rule can't be reversed", .name.1)]
+    IrreversibleFieldBinding { rule_fc: FC, name: Identifier },
 }
 
 pub fn files_to_hir(prog: &mut Program, files: &[ast::File]) -> Result<(), Vec<Error>> {
     let mut t = Translator {
         prog,
         errors: Default::default(),
+        narrowed: Default::default(),
+        filter_instance: None,
     };
 
-    let _ = t.translate_files(files);
+    t.translate_files(files);
 
     if t.errors.is_empty() {
         Ok(())
@@ -175,6 +359,21 @@ type VariableMap<'a> = HashMap<&'a str, (Identifier, TypeId)>;
 struct Translator<'a> {
     prog: &'a mut Program,
     errors: Vec<Error>,
+    /// Paths like `p.partner` that are known to not be `none` in the
+    /// expressions that are currently translated.
+    narrowed: Vec<String>,
+    /// The record and its type whose instances are checked by the
+    /// concentration filter that is currently translated.
+    filter_instance: Option<(RecordId, TypeId)>,
+}
+
+/// The rules and genes of a template with the type arguments of one
+/// instantiation filled in.
+struct Expansion<'a> {
+    template: &'a ast::Template,
+    instantiation: &'a ast::Instantiation,
+    rules: Vec<ast::Rule>,
+    genes: Vec<ast::Gene>,
 }
 
 impl Translator<'_> {
@@ -183,19 +382,36 @@ impl Translator<'_> {
     }
 
     fn translate_files(&mut self, files: &[ast::File]) {
+        // enums and records can refer to each other, so the enum types are
+        // created first and their fields are filled in after the records.
+        self.setup_enums(files);
+
         self.setup_records(files);
 
-        self.setup_rules(files);
+        self.setup_enum_variants(files);
+
+        self.setup_values(files);
+
+        // defaults can use constants and enum variants, so they are filled in
+        // once those exist.
+        self.setup_field_defaults(files);
+
+        // instantiated templates are translated along with the rules and genes
+        // that are written out
+        let expansions = self.expand_templates(files);
+
+        self.setup_rules(files, &expansions);
 
         self.setup_externs(files);
 
-        self.setup_genes(files);
+        self.setup_genes(files, &expansions);
     }
 
     fn setup_records(&mut self, files: &[ast::File]) {
         // sort by dependency
-        // Because there are currently no optional types, so recursive
-        // types are not allowed.
+        // The types of the fields have to be set up before the record, so
+        // recursive types are not allowed, not even through optional or list
+        // fields.
         let mut name_to_edge = HashMap::new();
         let mut name_to_fc = HashMap::new();
 
@@ -227,7 +443,7 @@ impl Translator<'_> {
                 let edges = record
                     .fields
                     .iter()
-                    .filter_map(|(_, ast::Type::Named(n))| name_to_edge.get(&n.1))
+                    .filter_map(|(_, ty)| name_to_edge.get(&type_base_name(ty).1))
                     .map(|other| (*self_id, *other));
 
                 g.extend_with_edges(edges);
@@ -257,7 +473,7 @@ impl Translator<'_> {
             if record
                 .fields
                 .iter()
-                .any(|(_, ast::Type::Named(n))| n.1 == record.name.1)
+                .any(|(_, ty)| type_base_name(ty).1 == record.name.1)
             {
                 self.add_error(Error::RecursiveRecordDefinitions {
                     defs: vec![record.name.0],
@@ -271,6 +487,12 @@ impl Translator<'_> {
                 name: record.name.clone(),
                 field_names: vec![],
                 fields: vec![],
+                defaults: vec![],
+                singleton: record.singleton.is_some(),
+                family: None,
+                family_fields: vec![],
+                members: vec![],
+                inspected: false,
             };
 
             for (name, ty) in &record.fields {
@@ -282,13 +504,10 @@ impl Translator<'_> {
                     });
                 }
 
-                let ast::Type::Named(n) = ty;
-
-                if let Some(id) = self.prog.type_by_name(&n.1) {
+                if let Some(id) = self.resolve_type(ty) {
                     hir_record.fields.push(id);
                     hir_record.field_names.push(name.clone());
-                } else {
-                    self.add_error(Error::UnknownType { name: n.clone() });
+                    hir_record.defaults.push(None);
                 }
             }
 
@@ -310,642 +529,2718 @@ impl Translator<'_> {
                 });
             }
         }
+
+        self.setup_record_families(files);
     }
 
-    // NOTE: records must be setup first! Otherwise they can't be used as products or
-    // reactant types
-    fn setup_rules(&mut self, files: &[ast::File]) {
-        // after they have all been added their binds are filled
-        for file in files {
-            'rules: for rule in &file.rules {
-                let mut binds = vec![];
-                let mut bound_vars = VariableMap::new();
-
-                for reactant in &rule.reactants {
-                    let bind_attr = match &reactant.attr {
-                        Some(ast::BindingAttribute::Quantity(fc, n)) => {
-                            if *n == 0 {
-                                self.errors.push(Error::ZeroBind {
-                                    bind_number: *fc,
-                                    item: rule.fc,
-                                    type_name: reactant.name.clone(),
-                                });
-                                continue 'rules;
-                            } else {
-                                Bind::Quantity(*n)
-                            }
-                        }
-                        Some(ast::BindingAttribute::Name(name)) => {
-                            let ty = if let Some(ty) = self.prog.type_by_name(&reactant.name.1) {
-                                ty
-                            } else {
-                                self.add_error(Error::UnknownType {
-                                    name: reactant.name.clone(),
-                                });
-                                continue;
-                            };
-                            if let Some((prev, _)) = bound_vars.insert(&name.1, (name.clone(), ty))
-                            {
-                                self.add_error(Error::NameRebound {
-                                    item_fc: rule.fc,
-                                    name: name.clone(),
-                                    orig_name: prev,
-                                });
-                                continue;
-                            }
+    /// Links the records with an `is Family` to their family, which has to be
+    /// a record without a family itself. Every member needs all fields of the
+    /// family with the same types.
+    fn setup_record_families(&mut self, files: &[ast::File]) {
+        let with_family = files
+            .iter()
+            .flat_map(|file| &file.records)
+            .filter(|record| record.family.is_some())
+            .map(|record| record.name.1.as_str())
+            .collect::<HashSet<_>>();
+
+        for record in files.iter().flat_map(|file| &file.records) {
+            let family_name = match &record.family {
+                Some(name) => name,
+                None => continue,
+            };
 
-                            Bind::Named(name.clone())
-                        }
-                        None => Bind::Quantity(1),
-                    };
+            // redefined or recursive records were not added
+            let record_id = match self.prog.record_by_name(&record.name.1) {
+                Some(id) if self.prog[id].name.0 == record.name.0 => id,
+                _ => continue,
+            };
 
-                    if let Some(record_id) = self.prog.record_by_name(&reactant.name.1) {
-                        binds.push((bind_attr, record_id))
-                    } else {
-                        self.add_error(Error::InvalidReactantType {
-                            name: reactant.name.clone(),
-                        });
-                    }
+            let family_id = match self.prog.record_by_name(&family_name.1) {
+                Some(id) => id,
+                None => {
+                    self.add_error(Error::UnknownRecordFamily {
+                        name: family_name.clone(),
+                    });
+                    continue;
                 }
+            };
 
-                let when = if let Some(expr) = &rule.when {
-                    // bound vars are not available, maybe that should be done?
-                    match self.translate_expr(&VariableMap::new(), expr) {
-                        Some(expr) => {
-                            let ty_id = self
-                                .prog
-                                .expr_type(expr)
-                                .expect("after translate_expr() a type should be assigned to expr");
+            if with_family.contains(family_name.1.as_str()) {
+                self.add_error(Error::NestedRecordFamily {
+                    record_name: record.name.clone(),
+                    family_name: family_name.clone(),
+                });
+                continue;
+            }
 
-                            if ty_id != self.prog.type_bool_id {
-                                self.errors.push(Error::WhenClauseMustBeOfTypeBool {
-                                    expr,
-                                    type_id: ty_id,
-                                });
-                                continue;
-                            }
-                            Some(expr)
-                        }
-                        None => {
-                            // check next next gene
-                            continue;
-                        }
-                    }
-                } else {
-                    None
-                };
+            let family = &self.prog[family_id];
+            let member = &self.prog[record_id];
+            let mut family_fields = Vec::with_capacity(family.fields.len());
+            let mut errors = vec![];
 
-                let products = rule
-                    .products
-                    .iter()
-                    .flat_map(|p| self.translate_product(&bound_vars, p))
-                    .collect();
+            for (field_name, ty) in family.field_names.iter().zip(&family.fields) {
+                match member.field_names.iter().position(|n| n.1 == field_name.1) {
+                    Some(idx) if member.fields[idx] == *ty => family_fields.push(idx),
+                    found => errors.push(Error::IncompatibleFamilyField {
+                        record_name: record.name.clone(),
+                        family_name: family_name.clone(),
+                        field_name: field_name.clone(),
+                        expected: *ty,
+                        found: found
+                            .map(|idx| (member.field_names[idx].clone(), member.fields[idx])),
+                    }),
+                }
+            }
 
-                let _ = self.prog.add_rule(
-                    rule.fc,
-                    Rule {
-                        binds,
-                        products,
-                        when,
-                    },
-                );
+            if !errors.is_empty() {
+                self.errors.extend(errors);
+                continue;
             }
+
+            let member = &mut self.prog.records[record_id];
+            member.family = Some(family_id);
+            member.family_fields = family_fields;
+            self.prog.records[family_id].members.push(record_id);
         }
     }
 
-    fn setup_externs(&mut self, files: &[ast::File]) {
-        let mut fields = BTreeMap::<&str, Identifier>::new();
+    fn setup_field_defaults(&mut self, files: &[ast::File]) {
+        for record in files.iter().flat_map(|file| &file.records) {
+            // redefined or recursive records were not added
+            let record_id = match self.prog.record_by_name(&record.name.1) {
+                Some(id) if self.prog[id].name.0 == record.name.0 => id,
+                _ => continue,
+            };
 
-        for file in files {
-            for ext in &file.externs {
-                fields.clear();
+            for ((name, _), default) in record.fields.iter().zip(&record.defaults) {
+                let default = match default {
+                    Some(expr) => expr,
+                    None => continue,
+                };
 
-                let mut hir_ext = Extern {
-                    name: ext.name.clone(),
-                    parameters: vec![],
-                    parameter_names: vec![],
+                // fields with an unknown type were left out
+                let hir_record = &self.prog[record_id];
+                let idx = match hir_record.field_names.iter().position(|n| n.0 == name.0) {
+                    Some(idx) => idx,
+                    None => continue,
                 };
+                let field_ty = hir_record.fields[idx];
 
-                for (name, ty) in &ext.parameters {
-                    let ast::Type::Named(type_name) = ty;
+                let value = self.translate_field_default(field_ty, default);
+                self.prog.records[record_id].defaults[idx] = value;
+            }
+        }
+    }
 
-                    let ty_id = if let Some(id) = self.prog.type_by_name(&type_name.1) {
-                        id
-                    } else {
-                        self.add_error(Error::UnknownType {
-                            name: type_name.clone(),
-                        });
-                        continue;
-                    };
+    /// Translates the default value of a record field, it has to be a constant
+    /// that fits the type of the field.
+    fn translate_field_default(
+        &mut self,
+        field_ty: TypeId,
+        expr: &ast::Expression,
+    ) -> Option<ExpressionId> {
+        let value = self.translate_const_expr(&VariableMap::new(), expr)?;
+
+        let value_ty = self.prog.expr_type(value).unwrap();
+        if !self.prog.type_is_assignable(field_ty, value_ty) {
+            self.add_error(Error::TypeMismatch {
+                fc: expr.fc(),
+                expected: field_ty,
+                found: value_ty,
+            });
+            return None;
+        }
 
-                    if let Some(prev) = fields.insert(name.1.as_str(), name.clone()) {
-                        self.add_error(Error::ExternDuplicateParameterName {
-                            ext_name: ext.name.clone(),
-                            duplicate_param: name.clone(),
-                            original_param: prev,
-                        });
-                        continue;
-                    }
+        Some(value)
+    }
 
-                    hir_ext.parameter_names.push(name.clone());
-                    hir_ext.parameters.push(ty_id);
+    fn setup_enums(&mut self, files: &[ast::File]) {
+        for enum_def in files.iter().flat_map(|f| &f.enums) {
+            let mut variants = Vec::<Variant>::new();
+
+            for variant in &enum_def.variants {
+                if let Some(prev) = variants.iter().find(|v| v.name.1 == variant.name.1) {
+                    self.add_error(Error::DuplicateEnumVariant {
+                        enum_name: enum_def.name.clone(),
+                        variant: variant.name.clone(),
+                        first_occurance: prev.name.0,
+                    });
+                    continue;
                 }
 
-                if self.prog.add_extern(ext.fc, hir_ext).is_none() {
-                    let redef_id = self.prog.extern_by_name(&ext.name.1).unwrap();
-                    let redef_ext = &self.prog[redef_id];
+                variants.push(Variant {
+                    name: variant.name.clone(),
+                    field_names: vec![],
+                    fields: vec![],
+                });
+            }
 
-                    let err = Error::RedefinedItem {
-                        orig_name: redef_ext.name.clone(),
-                        redef_name: ext.name.clone(),
-                    };
-                    self.add_error(err);
+            let hir_enum = Enum {
+                name: enum_def.name.clone(),
+                variants,
+            };
+
+            if let Some(id) = self.prog.add_enum(enum_def.fc, hir_enum) {
+                self.prog.add_type(Type::Enum(id));
+            } else {
+                let type_id = self.prog.type_by_name(&enum_def.name.1).unwrap();
+                let prev_name = if let Some(n) = self.prog.type_ident(type_id) {
+                    n.clone()
+                } else {
+                    self.add_error(Error::RedefinedBuiltinType {
+                        redef_name: enum_def.name.clone(),
+                    });
                     continue;
-                }
+                };
+                self.add_error(Error::RedefinedItem {
+                    orig_name: prev_name,
+                    redef_name: enum_def.name.clone(),
+                });
             }
         }
     }
 
-    fn setup_genes(&mut self, files: &[ast::File]) {
-        for file in files {
-            'genes: for gene in &file.genes {
-                let mut binds = vec![];
-                let mut bound_vars = VariableMap::new();
-
-                for factor in &gene.factors {
-                    let bind_attr = match &factor.attr {
-                        Some(ast::BindingAttribute::Quantity(fc, n)) => {
-                            if *n == 0 {
-                                self.errors.push(Error::ZeroBind {
-                                    bind_number: *fc,
-                                    item: gene.fc,
-                                    type_name: factor.name.clone(),
-                                });
-                                continue 'genes;
-                            } else {
-                                Bind::Quantity(*n)
-                            }
-                        }
-                        Some(ast::BindingAttribute::Name(name)) => {
-                            let ty = if let Some(ty) = self.prog.type_by_name(&factor.name.1) {
-                                ty
-                            } else {
-                                self.add_error(Error::UnknownType {
-                                    name: factor.name.clone(),
-                                });
-                                continue;
-                            };
-                            if let Some((prev, _)) = bound_vars.insert(&name.1, (name.clone(), ty))
-                            {
-                                self.add_error(Error::NameRebound {
-                                    item_fc: gene.fc,
-                                    name: name.clone(),
-                                    orig_name: prev,
-                                });
-                                continue;
-                            }
+    fn setup_enum_variants(&mut self, files: &[ast::File]) {
+        for enum_def in files.iter().flat_map(|f| &f.enums) {
+            let id = match self.prog.enum_by_name(&enum_def.name.1) {
+                // skip enums that were redefinitions
+                Some(id) if self.prog.enum_fc(id) == Some(enum_def.fc) => id,
+                _ => continue,
+            };
 
-                            Bind::Named(name.clone())
-                        }
-                        None => Bind::Quantity(1),
-                    };
+            for variant in &enum_def.variants {
+                let idx = match self.prog[id].variant_by_name(&variant.name.1) {
+                    Some(idx) => idx,
+                    None => continue,
+                };
 
-                    if let Some(record_id) = self.prog.record_by_name(&factor.name.1) {
-                        binds.push((bind_attr, record_id))
-                    } else {
-                        self.add_error(Error::InvalidFactorType {
-                            name: factor.name.clone(),
+                let mut field_names = Vec::<Identifier>::new();
+                let mut fields = vec![];
+
+                for (name, ty) in &variant.fields {
+                    if let Some(prev) = field_names.iter().find(|f| f.1 == name.1) {
+                        self.add_error(Error::DuplicateVariantField {
+                            variant: variant.name.clone(),
+                            field_name: name.clone(),
+                            first_occurance: prev.0,
                         });
+                        continue;
                     }
-                }
-
-                let when = if let Some(expr) = &gene.when {
-                    // bound vars are not moved, so they can be accessed without problems
-                    match self.translate_expr(&bound_vars, expr) {
-                        Some(expr) => {
-                            let ty_id = self
-                                .prog
-                                .expr_type(expr)
-                                .expect("after translate_expr() a type should be assigned to expr");
 
-                            if ty_id != self.prog.type_bool_id {
-                                self.errors.push(Error::WhenClauseMustBeOfTypeBool {
-                                    expr,
-                                    type_id: ty_id,
-                                });
-                                continue;
-                            }
-                            Some(expr)
-                        }
-                        None => {
-                            // check next next gene
-                            continue;
-                        }
+                    if let Some(ty) = self.resolve_type(ty) {
+                        fields.push(ty);
+                        field_names.push(name.clone());
                     }
-                } else {
-                    None
-                };
-
-                let body = gene
-                    .body
-                    .iter()
-                    .filter_map(|s| self.translate_gene_statement(&bound_vars, s))
-                    .collect();
-
-                let hir_gene = Gene { binds, body, when };
+                }
 
-                self.prog.add_gene(gene.fc(), hir_gene);
+                let hir_variant = &mut self.prog.enums[id].variants[idx];
+                hir_variant.field_names = field_names;
+                hir_variant.fields = fields;
             }
         }
     }
 
-    fn translate_product(&mut self, vars: &VariableMap, product: &ast::Product) -> Option<Product> {
-        let type_id = if let Some(id) = self.prog.type_by_name(&product.name.1) {
-            id
-        } else {
-            self.add_error(Error::UnknownType {
-                name: product.name.clone(),
-            });
-            return None;
-        };
-        let ty = self.prog.typ(type_id).unwrap();
-        match ty {
-            Type::Bool | Type::Int | Type::String => {
-                self.add_error(Error::UsingBuiltinTypeAsProduct {
-                    product_name: product.name.clone(),
-                });
-                None
-            }
-            Type::Record(id) => {
-                use std::collections::btree_map::Entry;
+    /// Sets up `const` and `param` items.
+    ///
+    /// Constants can refer to other constants, so they are processed in
+    /// dependency order, just like records.
+    fn setup_values(&mut self, files: &[ast::File]) {
+        enum Item<'a> {
+            Const(&'a ast::Const),
+            Param(&'a ast::Param),
+        }
 
-                let mut errs = vec![];
+        let mut name_to_node = HashMap::new();
+        let mut name_to_ident = HashMap::new();
 
-                let id = *id;
+        let mut g = petgraph::Graph::<Item, ()>::new();
 
-                let record = self.prog[id].clone();
+        let consts = files.iter().flat_map(|f| &f.consts).map(Item::Const);
+        let params = files.iter().flat_map(|f| &f.params).map(Item::Param);
 
-                // already ordered/sorted by field name
-                let mut args = vec![];
-                let mut call_fields = BTreeMap::new();
+        for item in consts.chain(params) {
+            let name = match &item {
+                Item::Const(c) => &c.name,
+                Item::Param(p) => &p.name,
+            };
 
-                for (ident, expr) in &product.fields {
-                    match call_fields.entry(ident.1.as_str()) {
-                        Entry::Vacant(e) => {
-                            e.insert((ident, expr));
-                        }
-                        Entry::Occupied(e) => {
-                            let (orig_ident, _) = e.get();
-                            errs.push(Error::ProductDuplicateRecordField {
-                                record_name: record.name.clone(),
-                                duplicate_field: ident.clone(),
-                                original_field: (*orig_ident).clone(),
-                            });
-                            continue;
-                        }
-                    }
-                }
+            if let Some(prev) = name_to_ident.insert(&name.1, name) {
+                self.add_error(Error::RedefinedItem {
+                    orig_name: prev.clone(),
+                    redef_name: name.clone(),
+                });
+                continue;
+            }
 
-                for (field_name, field_ty) in record.field_names.iter().zip(&record.fields) {
-                    // find the field inside the product call
+            let node = g.add_node(item);
+            name_to_node.insert(name.1.as_str(), node);
+        }
 
-                    if let Some((_, expr)) = call_fields.remove(&field_name.1.as_str()) {
-                        if let Some(expr_id) = self.translate_expr(vars, expr) {
-                            let ty = self.prog.expr_type(expr_id).unwrap();
-                            if field_ty != &ty {
-                                errs.push(Error::TypeMismatch {
-                                    fc: expr.fc(),
-                                    expected: *field_ty,
-                                    found: ty,
-                                });
-                                continue;
-                            }
-                            args.push(expr_id);
-                        } else {
-                            continue;
-                        }
-                    } else {
-                        errs.push(Error::ProductMissingRecordField {
-                            record_name: record.name.clone(),
-                            product_fc: product.fc,
-                            missing_field: field_name.clone(),
-                        });
-                    }
-                }
+        let mut edges = vec![];
+        for node in g.node_indices() {
+            let expr = match &g[node] {
+                Item::Const(c) => &c.value,
+                Item::Param(p) => &p.default,
+            };
 
-                for (_, (ident, _)) in call_fields {
-                    errs.push(Error::ProductUnknownRecordField {
-                        record_name: record.name.clone(),
-                        field: ident.clone(),
-                    });
-                }
+            let mut names = vec![];
+            collect_variable_names(expr, &mut names);
 
-                self.errors.extend(errs);
+            edges.extend(
+                names
+                    .into_iter()
+                    .filter_map(|n| name_to_node.get(n.1.as_str()))
+                    .map(|other| (node, *other)),
+            );
+        }
+        for (a, b) in edges {
+            g.add_edge(a, b, ());
+        }
 
-                Some(Product {
-                    quantity: product.quantity.map(|(_, n)| n).unwrap_or(1),
-                    record: id,
-                    arguments: args,
-                })
+        for group in petgraph::algo::tarjan_scc(&g) {
+            let is_self_recursive = group.len() == 1 && g.contains_edge(group[0], group[0]);
+
+            if group.len() > 1 || is_self_recursive {
+                let defs = group
+                    .into_iter()
+                    .map(|i| match &g[i] {
+                        Item::Const(c) => c.name.0,
+                        Item::Param(p) => p.name.0,
+                    })
+                    .collect();
+                self.add_error(Error::RecursiveConstDefinitions { defs });
+                continue;
+            }
+
+            let (fc, name, ty, expr) = match &g[group[0]] {
+                Item::Const(c) => (c.fc, &c.name, &c.ty, &c.value),
+                Item::Param(p) => (p.fc, &p.name, &p.ty, &p.default),
+            };
+
+            let ty = if let Some(id) = self.resolve_type(ty) {
+                id
+            } else {
+                continue;
+            };
+
+            let value = match self.translate_const_expr(&VariableMap::new(), expr) {
+                Some(id) => id,
+                None => continue,
+            };
+
+            let value_ty = self.prog.expr_type(value).unwrap();
+            if !self.prog.type_is_assignable(ty, value_ty) {
+                self.add_error(Error::TypeMismatch {
+                    fc: expr.fc(),
+                    expected: ty,
+                    found: value_ty,
+                });
+                continue;
             }
+
+            let added = match &g[group[0]] {
+                Item::Const(_) => {
+                    let c = Const {
+                        name: name.clone(),
+                        ty,
+                        value,
+                    };
+                    self.prog.add_const(fc, c).is_some()
+                }
+                Item::Param(_) => {
+                    let p = Param {
+                        name: name.clone(),
+                        ty,
+                        default: value,
+                    };
+                    self.prog.add_param(fc, p).is_some()
+                }
+            };
+            debug_assert!(added, "redefinitions are reported before translation");
         }
     }
 
-    fn translate_gene_statement(
-        &mut self,
-        vars: &VariableMap,
-        stmt: &ast::GeneStatement,
-    ) -> Option<GeneStatementId> {
-        let fc = stmt.fc();
-        match stmt {
-            ast::GeneStatement::Call {
-                fc: _,
-                name,
-                arguments,
-            } => {
-                use std::collections::btree_map::Entry;
-
-                let ext_id = if let Some(id) = self.prog.extern_by_name(&name.1) {
-                    id
-                } else {
-                    self.add_error(Error::UnknownExtern { name: name.clone() });
-                    return None;
-                };
+    // NOTE: records must be setup first! Otherwise they can't be used as products or
+    // reactant types
+    fn setup_rules(&mut self, files: &[ast::File], expansions: &[Expansion]) {
+        for file in files {
+            for rule in &file.rules {
+                self.translate_rule_item(rule);
+            }
+        }
 
-                let ext = self.prog[ext_id].clone();
+        for expansion in expansions {
+            let first_error = self.errors.len();
+            for rule in &expansion.rules {
+                self.translate_rule_item(rule);
+            }
+            self.wrap_instantiation_errors(first_error, expansion);
+        }
+    }
 
-                let mut errs = vec![];
+    fn translate_rule_item(&mut self, rule: &ast::Rule) {
+        self.translate_rule(
+            rule,
+            &rule.reactants,
+            &rule.products,
+            rule.when.as_ref(),
+            &rule.lets,
+        );
+
+        // the reverse direction of a reversible rule is its own rule,
+        // the `where` clause only belongs to the forward direction
+        if rule.reversible.is_some() {
+            if let Some((reactants, products)) = self.reverse_rule(rule) {
+                self.translate_rule(rule, &reactants, &products, rule.reverse_when.as_ref(), &[]);
+            }
+        }
+    }
 
-                // already ordered/sorted by parameter name
-                let mut args = vec![];
-                let mut call_params = BTreeMap::new();
+    /// Fills in the type arguments of all template instantiations. The
+    /// arguments have to be records.
+    fn expand_templates<'a>(&mut self, files: &'a [ast::File]) -> Vec<Expansion<'a>> {
+        let mut templates: HashMap<&str, &ast::Template> = HashMap::new();
+        for template in files.iter().flat_map(|file| &file.templates) {
+            if let Some(prev) = templates.get(template.name.1.as_str()) {
+                self.add_error(Error::RedefinedItem {
+                    orig_name: prev.name.clone(),
+                    redef_name: template.name.clone(),
+                });
+                continue;
+            }
 
-                for (ident, expr) in arguments {
-                    match call_params.entry(ident.1.as_str()) {
-                        Entry::Vacant(e) => {
-                            e.insert((ident, expr));
-                        }
-                        Entry::Occupied(e) => {
-                            let (orig_ident, _) = e.get();
-                            errs.push(Error::CallDuplicateParameter {
-                                ext_name: ext.name.clone(),
-                                duplicate_param: ident.clone(),
-                                original_param: (*orig_ident).clone(),
-                            });
-                            continue;
-                        }
-                    }
+            for (i, param) in template.params.iter().enumerate() {
+                if let Some(prev) = template.params[..i].iter().find(|p| p.1 == param.1) {
+                    self.add_error(Error::DuplicateTemplateParameter {
+                        template_name: template.name.clone(),
+                        param: param.clone(),
+                        first_occurance: prev.0,
+                    });
                 }
+            }
 
-                for (param_name, param_ty) in ext.parameter_names.iter().zip(&ext.parameters) {
-                    // find the parameter inside the extern function def
+            templates.insert(&template.name.1, template);
+        }
 
-                    if let Some((_, expr)) = call_params.remove(&param_name.1.as_str()) {
-                        if let Some(expr_id) = self.translate_expr(vars, expr) {
-                            let ty = self.prog.expr_type(expr_id).unwrap();
-                            if param_ty != &ty {
-                                errs.push(Error::TypeMismatch {
-                                    fc: expr.fc(),
-                                    expected: *param_ty,
-                                    found: ty,
-                                });
-                                continue;
-                            }
-                            args.push(expr_id);
-                        } else {
-                            continue;
-                        }
-                    } else {
-                        errs.push(Error::CallMissingParameter {
-                            ext_name: ext.name.clone(),
-                            call_fc: fc,
-                            missing_param: param_name.clone(),
-                        });
-                    }
-                }
+        let mut expansions = vec![];
 
-                for (_, (ident, _)) in call_params {
-                    errs.push(Error::CallUnknownParameter {
-                        ext_name: ext.name.clone(),
-                        parameter: ident.clone(),
+        for instantiation in files.iter().flat_map(|file| &file.instantiations) {
+            let template = match templates.get(instantiation.template.1.as_str()) {
+                Some(template) => *template,
+                None => {
+                    self.add_error(Error::UnknownTemplate {
+                        name: instantiation.template.clone(),
                     });
+                    continue;
                 }
+            };
 
-                self.errors.extend(errs);
+            if instantiation.args.len() != template.params.len() {
+                self.add_error(Error::TemplateArgumentCount {
+                    template_name: template.name.clone(),
+                    instantiation: instantiation.fc,
+                    expected: template.params.len(),
+                    found: instantiation.args.len(),
+                });
+                continue;
+            }
 
-                let stmt = GeneStatement::Call {
-                    ext: ext_id,
-                    arguments: args,
-                };
+            let mut valid = true;
+            for arg in &instantiation.args {
+                if self.prog.record_by_name(&arg.1).is_some() {
+                    continue;
+                }
 
-                let id = self.prog.add_gene_statement(fc, stmt);
+                if self.prog.type_by_name(&arg.1).is_some() {
+                    self.add_error(Error::TemplateArgumentNotRecord { arg: arg.clone() });
+                } else {
+                    self.add_error(Error::UnknownType { name: arg.clone() });
+                }
+                valid = false;
+            }
+            if !valid {
+                continue;
+            }
 
-                Some(id)
+            let args = template
+                .params
+                .iter()
+                .zip(&instantiation.args)
+                .map(|(param, arg)| (param.1.as_str(), arg.1.as_str()))
+                .collect::<TypeArgs>();
+
+            let mut rules = template.rules.clone();
+            for rule in &mut rules {
+                substitute_rule(rule, &args);
             }
-            ast::GeneStatement::Express(_, prod) => {
-                let product = self.translate_product(vars, prod)?;
-                let id = self
-                    .prog
-                    .add_gene_statement(fc, GeneStatement::Express(product));
-                Some(id)
+            let mut genes = template.genes.clone();
+            for gene in &mut genes {
+                substitute_gene(gene, &args);
             }
+
+            expansions.push(Expansion {
+                template,
+                instantiation,
+                rules,
+                genes,
+            });
         }
+
+        expansions
     }
 
-    fn translate_expr(
-        &mut self,
-        vars: &VariableMap,
-        expr: &ast::Expression,
-    ) -> Option<ExpressionId> {
-        let fc = expr.fc();
-        let (expr, ty) = match expr {
-            ast::Expression::Literal(ast::Literal::Bool(_, b)) => {
-                let expr = Expression::BoolLiteral(*b);
-                let ty = self.prog.type_bool_id;
-                (expr, ty)
+    /// Marks the errors since `first` as coming from the expansion of a
+    /// template, so they can point at the instantiation too.
+    fn wrap_instantiation_errors(&mut self, first: usize, expansion: &Expansion) {
+        let errors = self.errors.split_off(first);
+        self.errors.extend(
+            errors
+                .into_iter()
+                .map(|error| Error::InTemplateInstantiation {
+                    template_name: expansion.template.name.clone(),
+                    instantiation: expansion.instantiation.fc,
+                    error: Box::new(error),
+                }),
+        );
+    }
+
+    /// Swaps the reactants and products of a reversible rule. Catalysts stay
+    /// reactants in both directions.
+    fn reverse_rule(&mut self, rule: &ast::Rule) -> Option<(Vec<ast::Binding>, Vec<ast::Product>)> {
+        let mut reversible = true;
+
+        // the variables that the field patterns of the reverse direction bind,
+        // the products turn into those patterns and the catalysts stay
+        let product_patterns = rule.products.iter().flat_map(|p| &p.fields);
+        let catalyst_patterns = rule
+            .reactants
+            .iter()
+            .filter(|r| r.catalyst.is_some())
+            .flat_map(|r| &r.fields);
+        let mut rebound = product_patterns
+            .chain(catalyst_patterns)
+            .filter_map(|(_, value)| match value {
+                ast::Expression::Variable(var) => Some(var.1.as_str()),
+                _ => None,
+            })
+            .collect::<HashSet<_>>();
+        for reactant in rule.reactants.iter().filter(|r| r.catalyst.is_some()) {
+            if let Some(ast::BindingAttribute::Name(name)) = &reactant.attr {
+                rebound.insert(&name.1);
             }
-            ast::Expression::Literal(ast::Literal::Integer(_, n)) => {
-                let expr = Expression::IntegerLiteral(*n);
-                let ty = self.prog.type_int_id;
-                (expr, ty)
+        }
+
+        // the names the products use, directly or through the `where` clause
+        let mut used = vec![];
+        for product in &rule.products {
+            if let Some(ast::Quantity::Expression(expr)) = &product.quantity {
+                collect_variable_names(expr, &mut used);
             }
-            ast::Expression::Literal(ast::Literal::String(_, s)) => {
-                let expr = Expression::StringLiteral(s.clone());
-                let ty = self.prog.type_string_id;
-                (expr, ty)
+            if let Some(base) = &product.base {
+                collect_variable_names(base, &mut used);
             }
-            ast::Expression::Variable(ident) => {
-                //
-                if let Some((_, ty)) = vars.get(&ident.1.as_ref()) {
-                    (Expression::Variable(ident.clone()), *ty)
-                } else {
-                    let in_scope = vars.values().map(|(i, _)| i.clone()).collect();
-                    self.add_error(Error::UndefinedVariable {
-                        name: ident.clone(),
-                        in_scope,
-                    });
-                    return None;
-                }
+            for (_, value) in &product.fields {
+                collect_variable_names(value, &mut used);
             }
-            ast::Expression::FieldAccess { base, field_name } => {
-                //
-                let base_id = self.translate_expr(vars, base)?;
-                let base_type = self.prog.expr_type(base_id)?;
-                let ty = &self.prog[base_type];
-                match ty {
-                    Type::Record(record_id) => {
-                        let record = &self.prog[*record_id];
-                        if let Some((idx, _)) = record
-                            .field_names
-                            .iter()
-                            .enumerate()
-                            .find(|(_, name)| name.1 == field_name.1)
-                        {
-                            let expr = Expression::FieldAccess {
-                                base: base_id,
-                                field: idx,
-                            };
-                            let ty = record.fields[idx];
-                            (expr, ty)
-                        } else {
-                            let err = Error::InvalidRecordFieldIndex {
-                                record_name: record.name.clone(),
-                                field_name: field_name.clone(),
-                            };
-                            self.add_error(err);
-                            return None;
-                        }
+        }
+        for (_, value) in &rule.lets {
+            collect_variable_names(value, &mut used);
+        }
+
+        // only reported if nothing else is in the way, like a computed
+        // quantity that uses the binding
+        let mut unbound = vec![];
+
+        let mut reactants = rule
+            .reactants
+            .iter()
+            .filter(|r| r.catalyst.is_some())
+            .cloned()
+            .collect::<Vec<_>>();
+        let mut products = vec![];
+
+        for reactant in rule.reactants.iter().filter(|r| r.catalyst.is_none()) {
+            let quantity = match &reactant.attr {
+                Some(ast::BindingAttribute::Quantity(fc, n)) => {
+                    Some(ast::Quantity::Literal(*fc, *n))
+                }
+                Some(ast::BindingAttribute::Name(name)) => {
+                    if used.iter().any(|n| n.1 == name.1) {
+                        self.add_error(Error::IrreversibleNamedBinding {
+                            rule_fc: rule.fc,
+                            name: name.clone(),
+                        });
+                        reversible = false;
+                        continue;
                     }
-                    _ => {
-                        self.errors.push(Error::FieldIndexOnNonRecord {
-                            field_name: field_name.clone(),
-                            base_type,
+                    None
+                }
+                None => None,
+            };
+
+            // the reverse direction has to know the value of every field
+            // binding to express the reactant again
+            for (_, value) in &reactant.fields {
+                match value {
+                    ast::Expression::Variable(var)
+                        if !rebound.contains(var.1.as_str())
+                            && self.prog.const_by_name(&var.1).is_none() =>
+                    {
+                        unbound.push(Error::IrreversibleFieldBinding {
+                            rule_fc: rule.fc,
+                            name: var.clone(),
                         });
-                        return None;
                     }
+                    _ => {}
                 }
             }
-            ast::Expression::PrefixOp {
-                op: (op_fc, op),
-                expr,
-            } => {
-                let expr_id = self.translate_expr(vars, expr)?;
-                let expr_ty = self.prog.expr_type(expr_id)?;
 
-                match op {
-                    ast::PrefixOperator::Neg => {
-                        let expected_types = vec![self.prog.type_int_id];
-                        if !expected_types.contains(&expr_ty) {
-                            self.add_error(Error::TypeMismatchPrefixOp {
-                                op_fc: *op_fc,
-                                op: *op,
-                                expr: expr_id,
-                                expected_types,
-                            });
-                            return None;
-                        }
-                        debug_assert_eq!(expr_ty, self.prog.type_int_id);
-                        let ty = self.prog.type_int_id;
-                        let expr = Expression::PrefixOp {
-                            op: *op,
-                            expr: expr_id,
-                        };
-                        (expr, ty)
+            products.push(ast::Product {
+                fc: reactant.fc,
+                quantity,
+                name: reactant.name.clone(),
+                base: None,
+                fields: reactant.fields.clone(),
+            });
+        }
+
+        for product in &rule.products {
+            if let Some(base) = &product.base {
+                self.add_error(Error::IrreversibleProductBase {
+                    rule_fc: rule.fc,
+                    base_fc: base.fc(),
+                });
+                reversible = false;
+                continue;
+            }
+
+            let attr = match &product.quantity {
+                Some(ast::Quantity::Literal(fc, n)) => {
+                    Some(ast::BindingAttribute::Quantity(*fc, *n))
+                }
+                Some(ast::Quantity::Expression(expr)) => {
+                    self.add_error(Error::IrreversibleQuantity {
+                        rule_fc: rule.fc,
+                        quantity_fc: expr.fc(),
+                    });
+                    reversible = false;
+                    continue;
+                }
+                None => None,
+            };
+
+            reactants.push(ast::Binding {
+                fc: product.fc,
+                catalyst: None,
+                attr,
+                name: product.name.clone(),
+                fields: product.fields.clone(),
+            });
+        }
+
+        if reversible && !unbound.is_empty() {
+            self.errors.extend(unbound);
+            reversible = false;
+        }
+
+        reversible.then_some((reactants, products))
+    }
+
+    fn translate_rule(
+        &mut self,
+        rule: &ast::Rule,
+        reactants: &[ast::Binding],
+        products: &[ast::Product],
+        when: Option<&ast::Expression>,
+        lets: &[(Identifier, ast::Expression)],
+    ) {
+        let mut binds = vec![];
+        let mut catalysts = vec![];
+        let mut bound_vars = VariableMap::new();
+
+        for reactant in reactants {
+            let bind_attr = match &reactant.attr {
+                Some(ast::BindingAttribute::Quantity(fc, n)) => {
+                    if *n == 0 {
+                        self.errors.push(Error::ZeroBind {
+                            bind_number: *fc,
+                            item: rule.fc,
+                            type_name: reactant.name.clone(),
+                        });
+                        return;
+                    } else {
+                        Bind::Quantity(*n)
+                    }
+                }
+                Some(ast::BindingAttribute::Name(name)) => {
+                    let ty = if let Some(ty) = self.prog.type_by_name(&reactant.name.1) {
+                        ty
+                    } else {
+                        self.add_error(Error::UnknownType {
+                            name: reactant.name.clone(),
+                        });
+                        continue;
+                    };
+                    if let Some((prev, _)) = bound_vars.insert(&name.1, (name.clone(), ty)) {
+                        self.add_error(Error::NameRebound {
+                            item_fc: rule.fc,
+                            name: name.clone(),
+                            orig_name: prev,
+                        });
+                        continue;
                     }
+
+                    Bind::Named(name.clone())
+                }
+                None => Bind::Quantity(1),
+            };
+
+            if let Some(record_id) = self.prog.record_by_name(&reactant.name.1) {
+                let bind = self.translate_bind_fields(
+                    &mut bound_vars,
+                    rule.fc,
+                    bind_attr,
+                    record_id,
+                    &reactant.fields,
+                );
+                if reactant.catalyst.is_some() {
+                    catalysts.push((bind, record_id))
+                } else {
+                    binds.push((bind, record_id))
                 }
+            } else {
+                self.add_error(Error::InvalidReactantType {
+                    name: reactant.name.clone(),
+                });
             }
-            ast::Expression::InfixOp {
-                op: (op_fc, op),
-                args,
-            } => {
-                let lhs_id = self.translate_expr(vars, &args[0])?;
-                let rhs_id = self.translate_expr(vars, &args[1])?;
+        }
 
-                let lhs_type = self.prog.expr_type(lhs_id)?;
-                let rhs_type = self.prog.expr_type(rhs_id)?;
+        let when = if let Some(expr) = when {
+            // bound vars are not available, maybe that should be done?
+            match self.translate_expr(&VariableMap::new(), expr) {
+                Some(expr) => {
+                    let ty_id = self
+                        .prog
+                        .expr_type(expr)
+                        .expect("after translate_expr() a type should be assigned to expr");
+
+                    if ty_id != self.prog.type_bool_id {
+                        self.errors.push(Error::WhenClauseMustBeOfTypeBool {
+                            expr,
+                            type_id: ty_id,
+                        });
+                        return;
+                    }
+                    Some(expr)
+                }
+                None => {
+                    // check next rule
+                    return;
+                }
+            }
+        } else {
+            None
+        };
 
-                macro_rules! prim_type {
-                    (bool) => {
-                        self.prog.type_bool_id
-                    };
-                    (int) => {
-                        self.prog.type_int_id
+        let mut rule_lets = vec![];
+        for (name, value) in lets {
+            if let Some(id) = self.translate_let(&mut bound_vars, rule.fc, name, value) {
+                rule_lets.push((name.clone(), id));
+            }
+        }
+
+        let products = products
+            .iter()
+            .flat_map(|p| self.translate_product(&bound_vars, p))
+            .collect();
+
+        let _ = self.prog.add_rule(
+            rule.fc,
+            Rule {
+                binds,
+                catalysts,
+                priority: rule.scheduling.priority.map_or(0, |(_, n)| n),
+                weight: rule.scheduling.weight.map_or(1, |(_, n)| n),
+                products,
+                when,
+                lets: rule_lets,
+            },
+        );
+    }
+
+    fn setup_externs(&mut self, files: &[ast::File]) {
+        let mut fields = BTreeMap::<&str, Identifier>::new();
+
+        for file in files {
+            for ext in &file.externs {
+                fields.clear();
+
+                let mut hir_ext = Extern {
+                    name: ext.name.clone(),
+                    parameters: vec![],
+                    parameter_names: vec![],
+                };
+
+                for (name, ty) in &ext.parameters {
+                    let ty_id = if let Some(id) = self.resolve_type(ty) {
+                        id
+                    } else {
+                        continue;
                     };
-                    (string) => {
-                        self.prog.type_string_id
+
+                    if let Some(prev) = fields.insert(name.1.as_str(), name.clone()) {
+                        self.add_error(Error::ExternDuplicateParameterName {
+                            ext_name: ext.name.clone(),
+                            duplicate_param: name.clone(),
+                            original_param: prev,
+                        });
+                        continue;
+                    }
+
+                    hir_ext.parameter_names.push(name.clone());
+                    hir_ext.parameters.push(ty_id);
+                }
+
+                if self.prog.add_extern(ext.fc, hir_ext).is_none() {
+                    let redef_id = self.prog.extern_by_name(&ext.name.1).unwrap();
+                    let redef_ext = &self.prog[redef_id];
+
+                    let err = Error::RedefinedItem {
+                        orig_name: redef_ext.name.clone(),
+                        redef_name: ext.name.clone(),
                     };
+                    self.add_error(err);
+                    continue;
                 }
-                macro_rules! binop {
-                    ($a:ident, $b:ident => $c:ident) => {
-                        ((prim_type!($a), prim_type!($b)), prim_type!($c))
+            }
+        }
+    }
+
+    fn setup_genes(&mut self, files: &[ast::File], expansions: &[Expansion]) {
+        for file in files {
+            for gene in &file.genes {
+                self.translate_gene(gene);
+            }
+        }
+
+        for expansion in expansions {
+            let first_error = self.errors.len();
+            for gene in &expansion.genes {
+                self.translate_gene(gene);
+            }
+            self.wrap_instantiation_errors(first_error, expansion);
+        }
+    }
+
+    fn translate_gene(&mut self, gene: &ast::Gene) {
+        let mut binds = vec![];
+        let mut bound_vars = VariableMap::new();
+
+        for factor in &gene.factors {
+            let bind_attr = match &factor.attr {
+                Some(ast::BindingAttribute::Quantity(fc, n)) => {
+                    if *n == 0 {
+                        self.errors.push(Error::ZeroBind {
+                            bind_number: *fc,
+                            item: gene.fc,
+                            type_name: factor.name.clone(),
+                        });
+                        return;
+                    } else {
+                        Bind::Quantity(*n)
+                    }
+                }
+                Some(ast::BindingAttribute::Name(name)) => {
+                    let ty = if let Some(ty) = self.prog.type_by_name(&factor.name.1) {
+                        ty
+                    } else {
+                        self.add_error(Error::UnknownType {
+                            name: factor.name.clone(),
+                        });
+                        continue;
                     };
+                    if let Some((prev, _)) = bound_vars.insert(&name.1, (name.clone(), ty)) {
+                        self.add_error(Error::NameRebound {
+                            item_fc: gene.fc,
+                            name: name.clone(),
+                            orig_name: prev,
+                        });
+                        continue;
+                    }
+
+                    Bind::Named(name.clone())
                 }
+                None => Bind::Quantity(1),
+            };
 
-                let operator_types = match op {
-                    InfixOperator::Add => {
-                        vec![binop!(int, int => int), binop!(string, string => string)]
+            if let Some(record_id) = self.prog.record_by_name(&factor.name.1) {
+                let bind = self.translate_bind_fields(
+                    &mut bound_vars,
+                    gene.fc,
+                    bind_attr,
+                    record_id,
+                    &factor.fields,
+                );
+                binds.push((bind, record_id))
+            } else {
+                self.add_error(Error::InvalidFactorType {
+                    name: factor.name.clone(),
+                });
+            }
+        }
+
+        // a `gene on` runs for single instances of a single factor
+        if let Some(on_fc) = gene.on {
+            let is_single_instance = gene.factors.len() == 1
+                && !matches!(
+                    &gene.factors[0].attr,
+                    Some(ast::BindingAttribute::Quantity(_, n)) if *n != 1
+                );
+            if !is_single_instance {
+                self.add_error(Error::InvalidGeneTrigger {
+                    gene_fc: gene.fc,
+                    on_fc,
+                });
+                return;
+            }
+        }
+
+        let when = if let Some(expr) = &gene.when {
+            // bound vars are not moved, so they can be accessed without problems
+            match self.translate_expr(&bound_vars, expr) {
+                Some(expr) => {
+                    let ty_id = self
+                        .prog
+                        .expr_type(expr)
+                        .expect("after translate_expr() a type should be assigned to expr");
+
+                    if ty_id != self.prog.type_bool_id {
+                        self.errors.push(Error::WhenClauseMustBeOfTypeBool {
+                            expr,
+                            type_id: ty_id,
+                        });
+                        return;
                     }
-                    InfixOperator::Sub => vec![binop!(int, int => int)],
-                    InfixOperator::Mul => vec![binop!(int, int => int)],
-                    InfixOperator::Div => vec![binop!(int, int => int)],
-                    InfixOperator::Eq => vec![
-                        binop!(bool, bool => bool),
-                        binop!(int, int => bool),
-                        binop!(string, string => bool),
-                    ],
-                    InfixOperator::Neq => vec![
-                        binop!(bool, bool => bool),
-                        binop!(int, int => bool),
-                        binop!(string, string => bool),
-                    ],
-                    InfixOperator::Lt => vec![binop!(int, int => bool)],
-                    InfixOperator::Lte => vec![binop!(int, int => bool)],
-                    InfixOperator::Gt => vec![binop!(int, int => bool)],
-                    InfixOperator::Gte => vec![binop!(int, int => bool)],
+                    Some(expr)
+                }
+                None => {
+                    // the error was already reported
+                    return;
+                }
+            }
+        } else {
+            None
+        };
 
-                    InfixOperator::And => vec![binop!(bool, bool => bool)],
-                    InfixOperator::Or => vec![binop!(bool, bool => bool)],
-                };
+        if let Some(expr) = &gene.when {
+            collect_narrowed_places(expr, true, &mut self.narrowed);
+        }
+        let body = self.translate_gene_statements(&mut bound_vars, &gene.body);
+        self.narrowed.clear();
+
+        let hir_gene = Gene {
+            once: gene.once.is_some(),
+            on_arrival: gene.on.is_some(),
+            cooldown: gene.cooldown.map_or(0, |(_, n)| n),
+            priority: gene.scheduling.priority.map_or(0, |(_, n)| n),
+            weight: gene.scheduling.weight.map_or(1, |(_, n)| n),
+            binds,
+            body,
+            when,
+        };
 
-                if let Some((_, res_ty)) = operator_types
-                    .iter()
-                    .find(|(args, _)| args == &(lhs_type, rhs_type))
-                {
-                    let expr = Expression::InfixOp {
-                        op: *op,
-                        args: [lhs_id, rhs_id],
-                    };
-                    let ty = *res_ty;
-                    (expr, ty)
-                } else {
-                    self.add_error(Error::TypeMismatchInfixOp {
-                        op_fc: *op_fc,
-                        op: *op,
-                        lhs: lhs_id,
-                        rhs: rhs_id,
-                        expected_types: operator_types.into_iter().map(|(args, _)| args).collect(),
+        self.prog.add_gene(gene.fc(), hir_gene);
+    }
+
+    /// Turns `bind` into a pattern bind if the binding has field patterns.
+    ///
+    /// Field patterns that are plain names bind the field to a new variable,
+    /// all other field patterns have to be constants the field is compared to.
+    fn translate_bind_fields<'a>(
+        &mut self,
+        vars: &mut VariableMap<'a>,
+        item_fc: FC,
+        bind: Bind,
+        record_id: RecordId,
+        fields: &'a [(Identifier, ast::Expression)],
+    ) -> Bind {
+        if fields.is_empty() {
+            return bind;
+        }
+
+        let name = match bind {
+            Bind::Named(name) => Some(name),
+            _ => None,
+        };
+
+        let record = self.prog[record_id].clone();
+        let mut patterns: Vec<(FieldIndex, FieldPattern)> = vec![];
+
+        for (field_name, value) in fields {
+            let idx = if let Some(idx) = record.field_names.iter().position(|f| f.1 == field_name.1)
+            {
+                idx
+            } else {
+                self.add_error(Error::ProductUnknownRecordField {
+                    record_name: record.name.clone(),
+                    field: field_name.clone(),
+                });
+                continue;
+            };
+
+            if let Some((orig, _)) = fields.iter().find(|(f, _)| f.1 == field_name.1) {
+                if orig.0 != field_name.0 {
+                    self.add_error(Error::ProductDuplicateRecordField {
+                        record_name: record.name.clone(),
+                        duplicate_field: field_name.clone(),
+                        original_field: orig.clone(),
                     });
-                    return None;
+                    continue;
                 }
             }
-            ast::Expression::Concentration(ty_name) => {
-                let ty_id = self.prog.type_by_name(&ty_name.1)?;
 
-                let ty = &self.prog[ty_id];
-                let record_id = match ty {
-                    Type::Record(id) => *id,
-                    _ => {
-                        self.errors.push(Error::ConcentrationOfNonRecordType {
-                            fc,
-                            type_name: ty_name.clone(),
+            let field_ty = record.fields[idx];
+
+            let pattern = match value {
+                // a name would be bound as a variable that shadows the
+                // parameter, which can't be matched because it changes at runtime
+                ast::Expression::Variable(var) if self.prog.param_by_name(&var.1).is_some() => {
+                    let param = self.prog.param_by_name(&var.1).unwrap();
+                    self.add_error(Error::ParamInPattern {
+                        name: var.clone(),
+                        param_name: self.prog[param].name.clone(),
+                    });
+                    continue;
+                }
+                ast::Expression::Variable(var) if self.prog.const_by_name(&var.1).is_none() => {
+                    if let Some((prev, _)) = vars.insert(&var.1, (var.clone(), field_ty)) {
+                        self.add_error(Error::NameRebound {
+                            item_fc,
+                            name: var.clone(),
+                            orig_name: prev,
                         });
-                        return None;
+                        continue;
                     }
-                };
+                    FieldPattern::Bind(var.clone())
+                }
+                _ => {
+                    let id = match self.translate_const_expr(&VariableMap::new(), value) {
+                        Some(id) => id,
+                        None => continue,
+                    };
+                    let ty = self.prog.expr_type(id).unwrap();
+                    if !self.prog.type_is_assignable(field_ty, ty) {
+                        self.add_error(Error::TypeMismatch {
+                            fc: value.fc(),
+                            expected: field_ty,
+                            found: ty,
+                        });
+                        continue;
+                    }
+                    FieldPattern::Value(self.folded_literal(id))
+                }
+            };
 
-                (Expression::Concentration(record_id), self.prog.type_int_id)
-            }
+            patterns.push((idx, pattern));
+        }
+
+        self.prog.records[record_id].inspected = true;
+
+        Bind::Pattern {
+            name,
+            fields: patterns,
+        }
+    }
+
+    fn translate_product(&mut self, vars: &VariableMap, product: &ast::Product) -> Option<Product> {
+        let type_id = if let Some(id) = self.prog.type_by_name(&product.name.1) {
+            id
+        } else {
+            self.add_error(Error::UnknownType {
+                name: product.name.clone(),
+            });
+            return None;
         };
-        Some(self.prog.add_expression(fc, expr, ty))
+        let ty = self.prog.typ(type_id).unwrap();
+        match ty {
+            Type::Bool
+            | Type::Int
+            | Type::Float
+            | Type::String
+            | Type::List(_)
+            | Type::Optional(_)
+            | Type::None => {
+                self.add_error(Error::UsingBuiltinTypeAsProduct {
+                    product_name: product.name.clone(),
+                });
+                None
+            }
+            Type::Enum(_) => {
+                self.add_error(Error::InvalidProductType {
+                    name: product.name.clone(),
+                });
+                None
+            }
+            Type::Record(id) => {
+                let id = *id;
+                let value = self.translate_record_fields(
+                    vars,
+                    id,
+                    type_id,
+                    product.fc,
+                    product.base.as_ref(),
+                    &product.fields,
+                );
+                let value = self.prog.add_expression(product.fc, value, type_id);
+
+                let quantity = match &product.quantity {
+                    Some(ast::Quantity::Literal(_, n)) => Quantity::Literal(*n),
+                    Some(ast::Quantity::Expression(expr)) => {
+                        let expr_id = self.translate_expr(vars, expr)?;
+                        let ty = self.prog.expr_type(expr_id).unwrap();
+                        if ty != self.prog.type_int_id {
+                            self.add_error(Error::TypeMismatch {
+                                fc: expr.fc(),
+                                expected: self.prog.type_int_id,
+                                found: ty,
+                            });
+                            return None;
+                        }
+                        Quantity::Expression(expr_id)
+                    }
+                    None => Quantity::Literal(1),
+                };
+
+                Some(Product {
+                    quantity,
+                    record: id,
+                    value,
+                })
+            }
+        }
+    }
+
+    /// Translates the fields of a product or record literal into a record
+    /// value. Omitted fields are taken from `base` if present, which is only
+    /// evaluated once.
+    fn translate_record_fields(
+        &mut self,
+        vars: &VariableMap,
+        id: RecordId,
+        type_id: TypeId,
+        fc: FC,
+        base: Option<&ast::Expression>,
+        fields: &[(Identifier, ast::Expression)],
+    ) -> Expression {
+        use std::collections::btree_map::Entry;
+
+        let mut errs = vec![];
+
+        let record = self.prog[id].clone();
+
+        // an invalid base was already reported, don't complain about the fields it lacks
+        let has_base = base.is_some();
+        let base = if let Some(base) = base {
+            self.translate_expr(vars, base).and_then(|base_id| {
+                let ty = self.prog.expr_type(base_id).unwrap();
+                if ty == type_id {
+                    Some(base_id)
+                } else {
+                    errs.push(Error::TypeMismatch {
+                        fc: base.fc(),
+                        expected: type_id,
+                        found: ty,
+                    });
+                    None
+                }
+            })
+        } else {
+            None
+        };
+
+        // already ordered/sorted by field name
+        let mut args = vec![];
+        let mut overrides = vec![];
+        let mut call_fields = BTreeMap::new();
+
+        for (ident, expr) in fields {
+            match call_fields.entry(ident.1.as_str()) {
+                Entry::Vacant(e) => {
+                    e.insert((ident, expr));
+                }
+                Entry::Occupied(e) => {
+                    let (orig_ident, _) = e.get();
+                    errs.push(Error::ProductDuplicateRecordField {
+                        record_name: record.name.clone(),
+                        duplicate_field: ident.clone(),
+                        original_field: (*orig_ident).clone(),
+                    });
+                    continue;
+                }
+            }
+        }
+
+        for (idx, (field_name, field_ty)) in
+            record.field_names.iter().zip(&record.fields).enumerate()
+        {
+            // find the field inside the product call
+
+            if let Some((_, expr)) = call_fields.remove(&field_name.1.as_str()) {
+                if let Some(expr_id) = self.translate_expr(vars, expr) {
+                    let ty = self.prog.expr_type(expr_id).unwrap();
+                    if !self.prog.type_is_assignable(*field_ty, ty) {
+                        errs.push(Error::TypeMismatch {
+                            fc: expr.fc(),
+                            expected: *field_ty,
+                            found: ty,
+                        });
+                        continue;
+                    }
+                    if base.is_some() {
+                        overrides.push((idx, expr_id));
+                    } else {
+                        args.push(expr_id);
+                    }
+                } else {
+                    continue;
+                }
+            } else if base.is_some() {
+                // the field is copied from the base
+            } else if let Some(default) = record.defaults[idx] {
+                args.push(default);
+            } else if let Some(none) = self.none_for_omitted_field(fc, *field_ty) {
+                args.push(none);
+            } else if !has_base {
+                errs.push(Error::ProductMissingRecordField {
+                    record_name: record.name.clone(),
+                    product_fc: fc,
+                    missing_field: field_name.clone(),
+                });
+            }
+        }
+
+        for (_, (ident, _)) in call_fields {
+            errs.push(Error::ProductUnknownRecordField {
+                record_name: record.name.clone(),
+                field: ident.clone(),
+            });
+        }
+
+        self.errors.extend(errs);
+
+        match base {
+            Some(base) => Expression::RecordUpdate { base, overrides },
+            None => Expression::Record {
+                record: id,
+                fields: args,
+            },
+        }
+    }
+
+    /// Translates the value of a `let` binding and adds the variable to `vars`.
+    fn translate_let<'a>(
+        &mut self,
+        vars: &mut VariableMap<'a>,
+        item_fc: FC,
+        name: &'a Identifier,
+        value: &ast::Expression,
+    ) -> Option<ExpressionId> {
+        let id = self.translate_expr(vars, value)?;
+        let ty = self.prog.expr_type(id).unwrap();
+
+        if let Some((prev, _)) = vars.get(name.1.as_str()) {
+            self.add_error(Error::NameRebound {
+                item_fc,
+                name: name.clone(),
+                orig_name: prev.clone(),
+            });
+            return None;
+        }
+
+        vars.insert(&name.1, (name.clone(), ty));
+        Some(id)
+    }
+
+    fn translate_gene_statement<'a>(
+        &mut self,
+        vars: &mut VariableMap<'a>,
+        stmt: &'a ast::GeneStatement,
+    ) -> Option<GeneStatementId> {
+        let fc = stmt.fc();
+        match stmt {
+            ast::GeneStatement::Call {
+                fc: _,
+                name,
+                arguments,
+            } => {
+                use std::collections::btree_map::Entry;
+
+                let ext_id = if let Some(id) = self.prog.extern_by_name(&name.1) {
+                    id
+                } else {
+                    self.add_error(Error::UnknownExtern { name: name.clone() });
+                    return None;
+                };
+
+                let ext = self.prog[ext_id].clone();
+
+                let mut errs = vec![];
+
+                // already ordered/sorted by parameter name
+                let mut args = vec![];
+                let mut call_params = BTreeMap::new();
+
+                for (ident, expr) in arguments {
+                    match call_params.entry(ident.1.as_str()) {
+                        Entry::Vacant(e) => {
+                            e.insert((ident, expr));
+                        }
+                        Entry::Occupied(e) => {
+                            let (orig_ident, _) = e.get();
+                            errs.push(Error::CallDuplicateParameter {
+                                ext_name: ext.name.clone(),
+                                duplicate_param: ident.clone(),
+                                original_param: (*orig_ident).clone(),
+                            });
+                            continue;
+                        }
+                    }
+                }
+
+                for (param_name, param_ty) in ext.parameter_names.iter().zip(&ext.parameters) {
+                    // find the parameter inside the extern function def
+
+                    if let Some((_, expr)) = call_params.remove(&param_name.1.as_str()) {
+                        if let Some(expr_id) = self.translate_expr(vars, expr) {
+                            let ty = self.prog.expr_type(expr_id).unwrap();
+                            if !self.prog.type_is_assignable(*param_ty, ty) {
+                                errs.push(Error::TypeMismatch {
+                                    fc: expr.fc(),
+                                    expected: *param_ty,
+                                    found: ty,
+                                });
+                                continue;
+                            }
+                            args.push(expr_id);
+                        } else {
+                            continue;
+                        }
+                    } else {
+                        errs.push(Error::CallMissingParameter {
+                            ext_name: ext.name.clone(),
+                            call_fc: fc,
+                            missing_param: param_name.clone(),
+                        });
+                    }
+                }
+
+                for (_, (ident, _)) in call_params {
+                    errs.push(Error::CallUnknownParameter {
+                        ext_name: ext.name.clone(),
+                        parameter: ident.clone(),
+                    });
+                }
+
+                self.errors.extend(errs);
+
+                let stmt = GeneStatement::Call {
+                    ext: ext_id,
+                    arguments: args,
+                };
+
+                let id = self.prog.add_gene_statement(fc, stmt);
+
+                Some(id)
+            }
+            ast::GeneStatement::Express(_, prod) => {
+                let product = self.translate_product(vars, prod)?;
+                let id = self
+                    .prog
+                    .add_gene_statement(fc, GeneStatement::Express(product));
+                Some(id)
+            }
+            ast::GeneStatement::ExpressAfter {
+                fc: _,
+                delay,
+                product,
+            } => {
+                let delay_id = self.translate_expr(vars, delay);
+                let product = self.translate_product(vars, product);
+
+                let delay_id = delay_id?;
+                let delay_ty = self.prog.expr_type(delay_id).unwrap();
+                if delay_ty != self.prog.type_int_id {
+                    self.add_error(Error::TypeMismatch {
+                        fc: delay.fc(),
+                        expected: self.prog.type_int_id,
+                        found: delay_ty,
+                    });
+                    return None;
+                }
+
+                let stmt = GeneStatement::ExpressAfter {
+                    delay: delay_id,
+                    product: product?,
+                };
+                Some(self.prog.add_gene_statement(fc, stmt))
+            }
+            ast::GeneStatement::WithProbability {
+                fc: _,
+                probability,
+                statement,
+            } => {
+                let prob_id = self.translate_expr(vars, probability);
+                let stmt_id = self.translate_gene_statement(vars, statement);
+
+                let prob_id = prob_id?;
+                let prob_ty = self.prog.expr_type(prob_id).unwrap();
+                if prob_ty != self.prog.type_float_id {
+                    self.add_error(Error::TypeMismatch {
+                        fc: probability.fc(),
+                        expected: self.prog.type_float_id,
+                        found: prob_ty,
+                    });
+                    return None;
+                }
+
+                // the same as `if chance(p: probability) { statement }`
+                let cond = Expression::BuiltinCall {
+                    func: Builtin::Chance,
+                    arguments: vec![prob_id],
+                };
+                let cond_id =
+                    self.prog
+                        .add_expression(probability.fc(), cond, self.prog.type_bool_id);
+
+                let stmt = GeneStatement::If {
+                    cond: cond_id,
+                    then_body: vec![stmt_id?],
+                    else_body: vec![],
+                };
+                Some(self.prog.add_gene_statement(fc, stmt))
+            }
+            ast::GeneStatement::Let { fc, name, value } => {
+                let value = self.translate_let(vars, *fc, name, value)?;
+                let stmt = GeneStatement::Let {
+                    name: name.clone(),
+                    value,
+                };
+                Some(self.prog.add_gene_statement(*fc, stmt))
+            }
+            ast::GeneStatement::If {
+                fc: _,
+                cond,
+                then_body,
+                else_body,
+            } => {
+                // variables defined inside of a block are not visible outside of it
+                let cond_id = self.translate_expr(vars, cond);
+
+                let narrowed_len = self.narrowed.len();
+                collect_narrowed_places(cond, true, &mut self.narrowed);
+                let then_body = self.translate_gene_statements(&mut vars.clone(), then_body);
+                self.narrowed.truncate(narrowed_len);
+
+                collect_narrowed_places(cond, false, &mut self.narrowed);
+                let else_body = self.translate_gene_statements(&mut vars.clone(), else_body);
+                self.narrowed.truncate(narrowed_len);
+
+                let cond_id = cond_id?;
+                let cond_ty = self.prog.expr_type(cond_id).unwrap();
+                if cond_ty != self.prog.type_bool_id {
+                    self.add_error(Error::TypeMismatch {
+                        fc: cond.fc(),
+                        expected: self.prog.type_bool_id,
+                        found: cond_ty,
+                    });
+                    return None;
+                }
+
+                let stmt = GeneStatement::If {
+                    cond: cond_id,
+                    then_body,
+                    else_body,
+                };
+                Some(self.prog.add_gene_statement(fc, stmt))
+            }
+            ast::GeneStatement::Repeat { fc: _, count, body } => {
+                let count_id = self.translate_expr(vars, count);
+                let body = self.translate_gene_statements(&mut vars.clone(), body);
+
+                let count_id = count_id?;
+                let count_ty = self.prog.expr_type(count_id).unwrap();
+                if count_ty != self.prog.type_int_id {
+                    self.add_error(Error::TypeMismatch {
+                        fc: count.fc(),
+                        expected: self.prog.type_int_id,
+                        found: count_ty,
+                    });
+                    return None;
+                }
+
+                let stmt = GeneStatement::Repeat {
+                    count: count_id,
+                    body,
+                };
+                Some(self.prog.add_gene_statement(fc, stmt))
+            }
+        }
+    }
+
+    fn translate_gene_statements<'a>(
+        &mut self,
+        vars: &mut VariableMap<'a>,
+        stmts: &'a [ast::GeneStatement],
+    ) -> Vec<GeneStatementId> {
+        stmts
+            .iter()
+            .filter_map(|s| self.translate_gene_statement(vars, s))
+            .collect()
+    }
+
+    fn translate_expr(
+        &mut self,
+        vars: &VariableMap,
+        expr: &ast::Expression,
+    ) -> Option<ExpressionId> {
+        let fc = expr.fc();
+        let place = place_path(expr);
+        let (expr, ty) = match expr {
+            ast::Expression::Literal(ast::Literal::Bool(_, b)) => {
+                let expr = Expression::BoolLiteral(*b);
+                let ty = self.prog.type_bool_id;
+                (expr, ty)
+            }
+            ast::Expression::Literal(ast::Literal::Integer(_, n)) => {
+                let expr = Expression::IntegerLiteral(*n as isize);
+                let ty = self.prog.type_int_id;
+                (expr, ty)
+            }
+            ast::Expression::Literal(ast::Literal::Float(_, f)) => {
+                let expr = Expression::FloatLiteral(*f);
+                let ty = self.prog.type_float_id;
+                (expr, ty)
+            }
+            ast::Expression::Literal(ast::Literal::String(_, s)) => {
+                let expr = Expression::StringLiteral(s.clone());
+                let ty = self.prog.type_string_id;
+                (expr, ty)
+            }
+            ast::Expression::Literal(ast::Literal::None(_)) => {
+                (Expression::NoneLiteral, self.prog.type_none_id)
+            }
+            ast::Expression::Variable(ident) => {
+                //
+                if let Some((_, ty)) = vars.get(&ident.1.as_ref()) {
+                    (Expression::Variable(ident.clone()), *ty)
+                } else if let Some(id) = self.prog.const_by_name(&ident.1) {
+                    let c = &self.prog[id];
+                    (self.prog[c.value].clone(), c.ty)
+                } else if let Some(id) = self.prog.param_by_name(&ident.1) {
+                    (Expression::Param(id), self.prog[id].ty)
+                } else {
+                    let in_scope = vars.values().map(|(i, _)| i.clone()).collect();
+                    self.add_error(Error::UndefinedVariable {
+                        name: ident.clone(),
+                        in_scope,
+                    });
+                    return None;
+                }
+            }
+            ast::Expression::FieldAccess { base, field_name } => {
+                //
+                let base_id = self.translate_expr(vars, base)?;
+                let base_type = self.prog.expr_type(base_id)?;
+                let ty = &self.prog[base_type];
+                match ty {
+                    Type::Record(record_id) => {
+                        let record = &self.prog[*record_id];
+                        if let Some((idx, _)) = record
+                            .field_names
+                            .iter()
+                            .enumerate()
+                            .find(|(_, name)| name.1 == field_name.1)
+                        {
+                            let expr = Expression::FieldAccess {
+                                base: base_id,
+                                field: idx,
+                            };
+                            let ty = record.fields[idx];
+                            (expr, ty)
+                        } else {
+                            let err = Error::InvalidRecordFieldIndex {
+                                record_name: record.name.clone(),
+                                field_name: field_name.clone(),
+                            };
+                            self.add_error(err);
+                            return None;
+                        }
+                    }
+                    _ => {
+                        self.errors.push(Error::FieldIndexOnNonRecord {
+                            field_name: field_name.clone(),
+                            base_type,
+                        });
+                        return None;
+                    }
+                }
+            }
+            ast::Expression::PrefixOp {
+                op: (op_fc, op),
+                expr,
+            } => {
+                let expr_id = self.translate_expr(vars, expr)?;
+                let expr_ty = self.prog.expr_type(expr_id)?;
+
+                // all prefix operators return the type of their operand
+                let expected_types = match op {
+                    ast::PrefixOperator::Neg => {
+                        vec![self.prog.type_int_id, self.prog.type_float_id]
+                    }
+                    ast::PrefixOperator::Not => vec![self.prog.type_bool_id],
+                };
+
+                if !expected_types.contains(&expr_ty) {
+                    self.add_error(Error::TypeMismatchPrefixOp {
+                        op_fc: *op_fc,
+                        op: *op,
+                        expr: expr_id,
+                        expected_types,
+                    });
+                    return None;
+                }
+
+                let expr = Expression::PrefixOp {
+                    op: *op,
+                    expr: expr_id,
+                };
+                (expr, expr_ty)
+            }
+            ast::Expression::InfixOp {
+                op: (op_fc, op),
+                args,
+            } => {
+                let lhs_id = self.translate_expr(vars, &args[0])?;
+
+                // the right side is only evaluated if the left side didn't decide the result
+                let narrowed_len = self.narrowed.len();
+                match op {
+                    InfixOperator::And => {
+                        collect_narrowed_places(&args[0], true, &mut self.narrowed)
+                    }
+                    InfixOperator::Or => {
+                        collect_narrowed_places(&args[0], false, &mut self.narrowed)
+                    }
+                    _ => {}
+                }
+                let rhs_id = self.translate_expr(vars, &args[1]);
+                self.narrowed.truncate(narrowed_len);
+                let rhs_id = rhs_id?;
+
+                let lhs_type = self.prog.expr_type(lhs_id)?;
+                let rhs_type = self.prog.expr_type(rhs_id)?;
+
+                macro_rules! prim_type {
+                    (bool) => {
+                        self.prog.type_bool_id
+                    };
+                    (int) => {
+                        self.prog.type_int_id
+                    };
+                    (float) => {
+                        self.prog.type_float_id
+                    };
+                    (string) => {
+                        self.prog.type_string_id
+                    };
+                }
+                macro_rules! binop {
+                    ($a:ident, $b:ident => $c:ident) => {
+                        ((prim_type!($a), prim_type!($b)), prim_type!($c))
+                    };
+                }
+
+                let operator_types = match op {
+                    InfixOperator::Add => vec![
+                        binop!(int, int => int),
+                        binop!(float, float => float),
+                        binop!(string, string => string),
+                    ],
+                    InfixOperator::Sub => {
+                        vec![binop!(int, int => int), binop!(float, float => float)]
+                    }
+                    InfixOperator::Mul => {
+                        vec![binop!(int, int => int), binop!(float, float => float)]
+                    }
+                    InfixOperator::Div => {
+                        vec![binop!(int, int => int), binop!(float, float => float)]
+                    }
+                    InfixOperator::Mod => {
+                        vec![binop!(int, int => int), binop!(float, float => float)]
+                    }
+                    InfixOperator::Eq => vec![
+                        binop!(bool, bool => bool),
+                        binop!(int, int => bool),
+                        binop!(float, float => bool),
+                        binop!(string, string => bool),
+                    ],
+                    InfixOperator::Neq => vec![
+                        binop!(bool, bool => bool),
+                        binop!(int, int => bool),
+                        binop!(float, float => bool),
+                        binop!(string, string => bool),
+                    ],
+                    InfixOperator::Lt => {
+                        vec![binop!(int, int => bool), binop!(float, float => bool)]
+                    }
+                    InfixOperator::Lte => {
+                        vec![binop!(int, int => bool), binop!(float, float => bool)]
+                    }
+                    InfixOperator::Gt => {
+                        vec![binop!(int, int => bool), binop!(float, float => bool)]
+                    }
+                    InfixOperator::Gte => {
+                        vec![binop!(int, int => bool), binop!(float, float => bool)]
+                    }
+
+                    InfixOperator::And => vec![binop!(bool, bool => bool)],
+                    InfixOperator::Or => vec![binop!(bool, bool => bool)],
+                    InfixOperator::Xor => {
+                        vec![binop!(bool, bool => bool), binop!(int, int => int)]
+                    }
+                };
+
+                // values of the same enum type can always be compared
+                let is_enum_comparison = matches!(op, InfixOperator::Eq | InfixOperator::Neq)
+                    && lhs_type == rhs_type
+                    && matches!(self.prog[lhs_type], Type::Enum(_));
+
+                if is_enum_comparison {
+                    let expr = Expression::InfixOp {
+                        op: *op,
+                        args: [lhs_id, rhs_id],
+                    };
+                    (expr, self.prog.type_bool_id)
+                } else if let Some((_, res_ty)) = operator_types
+                    .iter()
+                    .find(|(args, _)| args == &(lhs_type, rhs_type))
+                {
+                    let expr = Expression::InfixOp {
+                        op: *op,
+                        args: [lhs_id, rhs_id],
+                    };
+                    let ty = *res_ty;
+                    (expr, ty)
+                } else {
+                    self.add_error(Error::TypeMismatchInfixOp {
+                        op_fc: *op_fc,
+                        op: *op,
+                        lhs: lhs_id,
+                        rhs: rhs_id,
+                        expected_types: operator_types.into_iter().map(|(args, _)| args).collect(),
+                    });
+                    return None;
+                }
+            }
+            ast::Expression::Concentration(ty_name) => {
+                let is_value = vars.contains_key(ty_name.1.as_str())
+                    || self.prog.const_by_name(&ty_name.1).is_some()
+                    || self.prog.param_by_name(&ty_name.1).is_some();
+
+                // `[x]` is parsed as a concentration, but it is a list if `x` is a value
+                if self.prog.type_by_name(&ty_name.1).is_none() && is_value {
+                    let elem = ast::Expression::Variable(ty_name.clone());
+                    return self.translate_list(vars, fc, None, std::slice::from_ref(&elem));
+                }
+
+                let record_id = self.resolve_measured_record(fc, ty_name)?;
+
+                (Expression::Concentration(record_id), self.prog.type_int_id)
+            }
+            ast::Expression::FilteredConcentration {
+                fc: _,
+                name,
+                filter,
+            } => {
+                let record_id = self.resolve_measured_record(fc, name)?;
+                let record_ty = self.prog.type_by_name(&name.1).unwrap();
+                self.prog.records[record_id].inspected = true;
+
+                let outer = self.filter_instance.replace((record_id, record_ty));
+                let filter_id = self.translate_expr(vars, filter);
+                self.filter_instance = outer;
+                let filter_id = filter_id?;
+
+                let filter_ty = self.prog.expr_type(filter_id).unwrap();
+                if filter_ty != self.prog.type_bool_id {
+                    self.add_error(Error::TypeMismatch {
+                        fc: filter.fc(),
+                        expected: self.prog.type_bool_id,
+                        found: filter_ty,
+                    });
+                    return None;
+                }
+
+                (
+                    Expression::FilteredConcentration {
+                        record: record_id,
+                        filter: filter_id,
+                    },
+                    self.prog.type_int_id,
+                )
+            }
+            ast::Expression::Iteration(_) => (Expression::Iteration, self.prog.type_int_id),
+            ast::Expression::Time(_) => (Expression::Time, self.prog.type_float_id),
+            ast::Expression::InstanceField { fc: _, field_name } => {
+                let (record_id, record_ty) = if let Some(instance) = self.filter_instance {
+                    instance
+                } else {
+                    self.add_error(Error::InstanceFieldOutsideFilter { fc });
+                    return None;
+                };
+
+                let (idx, ty) = self.resolve_record_field(record_id, field_name)?;
+                let instance = self
+                    .prog
+                    .add_expression(fc, Expression::Instance, record_ty);
+
+                (
+                    Expression::FieldAccess {
+                        base: instance,
+                        field: idx,
+                    },
+                    ty,
+                )
+            }
+            ast::Expression::Aggregate {
+                fc: _,
+                func,
+                record,
+                field,
+            } => {
+                let func = Aggregate::from_name(&func.1).unwrap();
+                let record_id = self.resolve_measured_record(fc, record)?;
+                let (idx, field_ty) = self.resolve_record_field(record_id, field)?;
+                self.prog.records[record_id].inspected = true;
+
+                if field_ty != self.prog.type_int_id && field_ty != self.prog.type_float_id {
+                    self.add_error(Error::AggregateOfNonNumber {
+                        field_name: field.clone(),
+                        type_id: field_ty,
+                    });
+                    return None;
+                }
+
+                let ty = match func {
+                    Aggregate::Avg => self.prog.type_float_id,
+                    Aggregate::Sum | Aggregate::Min | Aggregate::Max => field_ty,
+                };
+
+                (
+                    Expression::Aggregate {
+                        func,
+                        record: record_id,
+                        field: idx,
+                    },
+                    ty,
+                )
+            }
+            ast::Expression::List {
+                fc: _,
+                elem_ty,
+                elems,
+            } => return self.translate_list(vars, fc, elem_ty.as_ref(), elems),
+            ast::Expression::OptionTest {
+                fc: _,
+                expr,
+                is_some,
+            } => {
+                let expr_id = self.translate_expr(vars, expr)?;
+                let expr_ty = self.prog.expr_type(expr_id).unwrap();
+
+                if !matches!(self.prog[expr_ty], Type::Optional(_) | Type::None) {
+                    self.add_error(Error::OptionTestOnNonOptional {
+                        expr: expr_id,
+                        type_id: expr_ty,
+                    });
+                    return None;
+                }
+
+                let expr = Expression::OptionTest {
+                    expr: expr_id,
+                    is_some: *is_some,
+                };
+                (expr, self.prog.type_bool_id)
+            }
+            ast::Expression::Call {
+                fc: _,
+                name,
+                base,
+                arguments,
+            } => {
+                if let Some(record_id) = self.prog.record_by_name(&name.1) {
+                    let ty = self.prog.type_by_name(&name.1).unwrap();
+                    let record = self.translate_record_fields(
+                        vars,
+                        record_id,
+                        ty,
+                        fc,
+                        base.as_deref(),
+                        arguments,
+                    );
+                    (record, ty)
+                } else {
+                    let func = if let Some(func) = Builtin::from_name(&name.1) {
+                        func
+                    } else {
+                        self.add_error(Error::UnknownFunction { name: name.clone() });
+                        return None;
+                    };
+
+                    if let Some(base) = base {
+                        self.add_error(Error::BuiltinCallWithBase {
+                            name: name.clone(),
+                            base_fc: base.fc(),
+                        });
+                        return None;
+                    }
+
+                    let args = self.translate_builtin_arguments(vars, func, name, fc, arguments)?;
+                    let ty = self.check_builtin_call(func, name, &args)?;
+
+                    (
+                        Expression::BuiltinCall {
+                            func,
+                            arguments: args,
+                        },
+                        ty,
+                    )
+                }
+            }
+            ast::Expression::Variant {
+                fc: _,
+                enum_name,
+                variant,
+                fields,
+            } => {
+                let (ty, enum_id) = self.resolve_enum(enum_name)?;
+                let idx = self.resolve_variant(enum_id, enum_name, variant)?;
+                let fields = self.translate_variant_fields(vars, enum_id, idx, fc, fields)?;
+
+                (
+                    Expression::Variant {
+                        variant: idx,
+                        fields,
+                    },
+                    ty,
+                )
+            }
+            ast::Expression::Match { fc: _, expr, arms } => {
+                let expr_id = self.translate_expr(vars, expr)?;
+                let (arms, ty) = self.translate_match_arms(vars, fc, expr_id, arms)?;
+
+                (
+                    Expression::Match {
+                        expr: expr_id,
+                        arms,
+                    },
+                    ty,
+                )
+            }
+        };
+
+        // optional values that were checked to not be `none` can be used directly
+        let ty = match self.prog[ty] {
+            Type::Optional(inner) if place.is_some_and(|p| self.narrowed.contains(&p)) => inner,
+            _ => ty,
+        };
+
+        Some(self.prog.add_expression(fc, expr, ty))
+    }
+
+    fn resolve_type(&mut self, ty: &ast::Type) -> Option<TypeId> {
+        match ty {
+            ast::Type::Named(name) => {
+                let id = self.prog.type_by_name(&name.1);
+                if id.is_none() {
+                    self.add_error(Error::UnknownType { name: name.clone() });
+                }
+                id
+            }
+            ast::Type::List { fc: _, elem } => {
+                let elem = self.resolve_type(elem)?;
+                Some(self.prog.list_type(elem))
+            }
+            ast::Type::Optional { fc: _, inner } => {
+                let inner = self.resolve_type(inner)?;
+                Some(self.prog.optional_type(inner))
+            }
+        }
+    }
+
+    /// Resolves the record in a concentration or aggregate.
+    fn resolve_measured_record(&mut self, fc: FC, ty_name: &Identifier) -> Option<RecordId> {
+        let ty_id = if let Some(id) = self.prog.type_by_name(&ty_name.1) {
+            id
+        } else {
+            self.add_error(Error::UnknownType {
+                name: ty_name.clone(),
+            });
+            return None;
+        };
+
+        match self.prog[ty_id] {
+            Type::Record(id) => Some(id),
+            _ => {
+                self.errors.push(Error::ConcentrationOfNonRecordType {
+                    fc,
+                    type_name: ty_name.clone(),
+                });
+                None
+            }
+        }
+    }
+
+    fn resolve_record_field(
+        &mut self,
+        record_id: RecordId,
+        field_name: &Identifier,
+    ) -> Option<(FieldIndex, TypeId)> {
+        let record = &self.prog[record_id];
+        if let Some(idx) = record.field_names.iter().position(|f| f.1 == field_name.1) {
+            Some((idx, record.fields[idx]))
+        } else {
+            let err = Error::InvalidRecordFieldIndex {
+                record_name: record.name.clone(),
+                field_name: field_name.clone(),
+            };
+            self.add_error(err);
+            None
+        }
+    }
+
+    /// Optional fields that are left out are `none`.
+    fn none_for_omitted_field(&mut self, fc: FC, field_ty: TypeId) -> Option<ExpressionId> {
+        if let Type::Optional(_) = self.prog[field_ty] {
+            let ty = self.prog.type_none_id;
+            Some(self.prog.add_expression(fc, Expression::NoneLiteral, ty))
+        } else {
+            None
+        }
+    }
+
+    fn translate_list(
+        &mut self,
+        vars: &VariableMap,
+        fc: FC,
+        elem_ty: Option<&ast::Type>,
+        elems: &[ast::Expression],
+    ) -> Option<ExpressionId> {
+        let mut elem_ty = match elem_ty {
+            Some(ty) => Some(self.resolve_type(ty)?),
+            None => None,
+        };
+
+        let mut ids = vec![];
+        let mut ok = true;
+
+        for elem in elems {
+            let id = match self.translate_expr(vars, elem) {
+                Some(id) => id,
+                None => {
+                    ok = false;
+                    continue;
+                }
+            };
+            let ty = self.prog.expr_type(id).unwrap();
+
+            match elem_ty {
+                Some(expected) if expected != ty => {
+                    self.add_error(Error::TypeMismatch {
+                        fc: elem.fc(),
+                        expected,
+                        found: ty,
+                    });
+                    ok = false;
+                }
+                Some(_) => {}
+                // the first element decides the type of the list
+                None => elem_ty = Some(ty),
+            }
+
+            ids.push(id);
+        }
+
+        let elem_ty = if let Some(ty) = elem_ty {
+            ty
+        } else {
+            self.add_error(Error::UntypedEmptyList { fc });
+            return None;
+        };
+
+        if !ok {
+            return None;
+        }
+
+        let ty = self.prog.list_type(elem_ty);
+        Some(self.prog.add_expression(fc, Expression::List(ids), ty))
+    }
+
+    /// Looks up the enum type called `name`.
+    fn resolve_enum(&mut self, name: &Identifier) -> Option<(TypeId, EnumId)> {
+        let ty = if let Some(ty) = self.prog.type_by_name(&name.1) {
+            ty
+        } else {
+            self.add_error(Error::UnknownType { name: name.clone() });
+            return None;
+        };
+
+        if let Type::Enum(id) = self.prog[ty] {
+            Some((ty, id))
+        } else {
+            self.add_error(Error::NotAnEnum { name: name.clone() });
+            None
+        }
+    }
+
+    fn resolve_variant(
+        &mut self,
+        enum_id: EnumId,
+        enum_name: &Identifier,
+        variant: &Identifier,
+    ) -> Option<VariantIndex> {
+        let idx = self.prog[enum_id].variant_by_name(&variant.1);
+        if idx.is_none() {
+            self.add_error(Error::UnknownEnumVariant {
+                enum_name: enum_name.clone(),
+                variant: variant.clone(),
+            });
+        }
+        idx
+    }
+
+    /// Translates the payload of a variant and puts the fields in the order of
+    /// the variant definition.
+    fn translate_variant_fields(
+        &mut self,
+        vars: &VariableMap,
+        enum_id: EnumId,
+        idx: VariantIndex,
+        use_fc: FC,
+        fields: &[(Identifier, ast::Expression)],
+    ) -> Option<Vec<ExpressionId>> {
+        use std::collections::btree_map::Entry;
+
+        let variant = self.prog[enum_id].variants[idx].clone();
+
+        let mut errs = vec![];
+
+        let mut given = BTreeMap::new();
+        for (ident, expr) in fields {
+            match given.entry(ident.1.as_str()) {
+                Entry::Vacant(e) => {
+                    e.insert((ident, expr));
+                }
+                Entry::Occupied(e) => {
+                    let (orig_ident, _) = e.get();
+                    errs.push(Error::VariantDuplicateField {
+                        variant: variant.name.clone(),
+                        duplicate_field: ident.clone(),
+                        original_field: (*orig_ident).clone(),
+                    });
+                }
+            }
+        }
+
+        let mut args = vec![];
+        let mut failed = false;
+
+        for (field_name, field_ty) in variant.field_names.iter().zip(&variant.fields) {
+            if let Some((_, expr)) = given.remove(field_name.1.as_str()) {
+                if let Some(expr_id) = self.translate_expr(vars, expr) {
+                    let ty = self.prog.expr_type(expr_id).unwrap();
+                    if !self.prog.type_is_assignable(*field_ty, ty) {
+                        errs.push(Error::TypeMismatch {
+                            fc: expr.fc(),
+                            expected: *field_ty,
+                            found: ty,
+                        });
+                        continue;
+                    }
+                    args.push(expr_id);
+                } else {
+                    failed = true;
+                }
+            } else if let Some(none) = self.none_for_omitted_field(use_fc, *field_ty) {
+                args.push(none);
+            } else {
+                errs.push(Error::VariantMissingField {
+                    variant: variant.name.clone(),
+                    use_fc,
+                    missing_field: field_name.clone(),
+                });
+            }
+        }
+
+        for (_, (ident, _)) in given {
+            errs.push(Error::VariantUnknownField {
+                variant: variant.name.clone(),
+                field: ident.clone(),
+            });
+        }
+
+        if failed || !errs.is_empty() {
+            self.errors.extend(errs);
+            return None;
+        }
+
+        Some(args)
+    }
+
+    /// Translates the arms of a `match` and checks that every variant of the
+    /// enum is handled exactly once. Returns the arms and the type of the
+    /// whole `match` expression.
+    fn translate_match_arms(
+        &mut self,
+        vars: &VariableMap,
+        match_fc: FC,
+        expr: ExpressionId,
+        arms: &[ast::MatchArm],
+    ) -> Option<(Vec<MatchArm>, TypeId)> {
+        let expr_ty = self.prog.expr_type(expr).unwrap();
+        let enum_id = if let Type::Enum(id) = self.prog[expr_ty] {
+            id
+        } else {
+            self.add_error(Error::MatchOnNonEnum {
+                expr,
+                type_id: expr_ty,
+            });
+            return None;
+        };
+
+        let num_variants = self.prog[enum_id].variants.len();
+
+        let mut covered = vec![false; num_variants];
+        let mut has_wildcard = false;
+
+        let mut hir_arms = vec![];
+        let mut result_ty = None;
+        let mut failed = false;
+
+        for arm in arms {
+            let all_covered = has_wildcard || covered.iter().all(|c| *c);
+
+            let mut arm_vars = vars.clone();
+
+            let pattern = match &arm.pattern {
+                ast::Pattern::Wildcard(fc) => {
+                    if all_covered {
+                        self.add_error(Error::UnreachableMatchArm { fc: *fc });
+                        failed = true;
+                    }
+                    has_wildcard = true;
+                    Pattern::Wildcard
+                }
+                ast::Pattern::Variant {
+                    fc,
+                    enum_name,
+                    variant,
+                    binds,
+                } => {
+                    let (pattern_ty, _) = match self.resolve_enum(enum_name) {
+                        Some(res) => res,
+                        None => {
+                            failed = true;
+                            continue;
+                        }
+                    };
+                    if pattern_ty != expr_ty {
+                        self.add_error(Error::TypeMismatch {
+                            fc: *fc,
+                            expected: expr_ty,
+                            found: pattern_ty,
+                        });
+                        failed = true;
+                        continue;
+                    }
+
+                    let idx = match self.resolve_variant(enum_id, enum_name, variant) {
+                        Some(idx) => idx,
+                        None => {
+                            failed = true;
+                            continue;
+                        }
+                    };
+
+                    if all_covered || covered[idx] {
+                        self.add_error(Error::UnreachableMatchArm { fc: *fc });
+                        failed = true;
+                    }
+                    covered[idx] = true;
+
+                    let hir_variant = self.prog[enum_id].variants[idx].clone();
+
+                    let mut hir_binds = vec![];
+                    for (field, var) in binds {
+                        let field_idx = hir_variant.field_names.iter().position(|f| f.1 == field.1);
+                        let field_idx = if let Some(field_idx) = field_idx {
+                            field_idx
+                        } else {
+                            self.add_error(Error::VariantUnknownField {
+                                variant: variant.clone(),
+                                field: field.clone(),
+                            });
+                            failed = true;
+                            continue;
+                        };
+
+                        let field_ty = hir_variant.fields[field_idx];
+                        if let Some((prev, _)) = arm_vars.insert(&var.1, (var.clone(), field_ty)) {
+                            self.add_error(Error::NameRebound {
+                                item_fc: *fc,
+                                name: var.clone(),
+                                orig_name: prev,
+                            });
+                            failed = true;
+                            continue;
+                        }
+
+                        hir_binds.push((field_idx, var.clone()));
+                    }
+
+                    Pattern::Variant {
+                        variant: idx,
+                        binds: hir_binds,
+                    }
+                }
+            };
+
+            let body = match self.translate_expr(&arm_vars, &arm.body) {
+                Some(body) => body,
+                None => {
+                    failed = true;
+                    continue;
+                }
+            };
+
+            let body_ty = self.prog.expr_type(body).unwrap();
+            match result_ty {
+                None => result_ty = Some(body_ty),
+                Some(ty) if ty != body_ty => {
+                    self.add_error(Error::TypeMismatch {
+                        fc: arm.body.fc(),
+                        expected: ty,
+                        found: body_ty,
+                    });
+                    failed = true;
+                    continue;
+                }
+                Some(_) => {}
+            }
+
+            hir_arms.push(MatchArm { pattern, body });
+        }
+
+        if failed {
+            return None;
+        }
+
+        if !has_wildcard && (arms.is_empty() || covered.iter().any(|c| !c)) {
+            let variants = &self.prog[enum_id].variants;
+            let missing = covered
+                .iter()
+                .zip(variants)
+                .filter(|(c, _)| !**c)
+                .map(|(_, v)| v.name.clone())
+                .collect();
+            self.add_error(Error::NonExhaustiveMatch {
+                fc: match_fc,
+                missing,
+            });
+            return None;
+        }
+
+        Some((hir_arms, result_ty?))
+    }
+
+    /// Translates the named arguments of a builtin call and puts them in the
+    /// order of the parameters of the builtin.
+    fn translate_builtin_arguments(
+        &mut self,
+        vars: &VariableMap,
+        func: Builtin,
+        func_name: &Identifier,
+        call_fc: FC,
+        arguments: &[(Identifier, ast::Expression)],
+    ) -> Option<Vec<ExpressionId>> {
+        use std::collections::btree_map::Entry;
+
+        let mut errs = vec![];
+
+        let mut call_params = BTreeMap::new();
+
+        for (ident, expr) in arguments {
+            match call_params.entry(ident.1.as_str()) {
+                Entry::Vacant(e) => {
+                    e.insert((ident, expr));
+                }
+                Entry::Occupied(e) => {
+                    let (orig_ident, _) = e.get();
+                    errs.push(Error::CallDuplicateParameter {
+                        ext_name: func_name.clone(),
+                        duplicate_param: ident.clone(),
+                        original_param: (*orig_ident).clone(),
+                    });
+                }
+            }
+        }
+
+        let mut args = vec![];
+        let mut failed = false;
+
+        for param_name in func.parameter_names() {
+            if let Some((_, expr)) = call_params.remove(param_name) {
+                match self.translate_expr(vars, expr) {
+                    Some(id) => args.push(id),
+                    None => failed = true,
+                }
+            } else {
+                errs.push(Error::BuiltinMissingParameter {
+                    func_name: func_name.clone(),
+                    call_fc,
+                    missing_param: param_name,
+                });
+            }
+        }
+
+        for (_, (ident, _)) in call_params {
+            errs.push(Error::BuiltinUnknownParameter {
+                func_name: func_name.clone(),
+                parameter: ident.clone(),
+            });
+        }
+
+        if failed || !errs.is_empty() {
+            self.errors.extend(errs);
+            return None;
+        }
+
+        Some(args)
+    }
+
+    /// Checks the argument types of a builtin call and returns the type of the
+    /// result.
+    fn check_builtin_call(
+        &mut self,
+        func: Builtin,
+        func_name: &Identifier,
+        args: &[ExpressionId],
+    ) -> Option<TypeId> {
+        let bool_ty = self.prog.type_bool_id;
+        let int_ty = self.prog.type_int_id;
+        let float_ty = self.prog.type_float_id;
+        let string_ty = self.prog.type_string_id;
+
+        let numeric = vec![int_ty, float_ty];
+
+        let first_ty = self.prog.expr_type(args[0]).unwrap();
+        let first_elem = match self.prog[first_ty] {
+            Type::List(elem) => Some(elem),
+            _ => None,
+        };
+
+        let takes_list = matches!(func, Builtin::Get | Builtin::Push | Builtin::Concat);
+        if takes_list && first_elem.is_none() {
+            self.add_error(Error::BuiltinExpectedList {
+                func_name: func_name.clone(),
+                param_name: func.parameter_names()[0],
+                expr: args[0],
+            });
+            return None;
+        }
+
+        // `len` and `contains` work on strings and lists
+        let string_or_list = if first_elem.is_some() {
+            vec![first_ty]
+        } else {
+            vec![string_ty]
+        };
+
+        // `None` means the result has the type of the first argument
+        let (params, ret) = match func {
+            Builtin::ToString => (
+                vec![vec![bool_ty, int_ty, float_ty, string_ty]],
+                Some(string_ty),
+            ),
+            Builtin::ToInt => (vec![vec![float_ty]], Some(int_ty)),
+            Builtin::ToFloat => (vec![vec![int_ty]], Some(float_ty)),
+            Builtin::Len => (vec![string_or_list], Some(int_ty)),
+            Builtin::Abs => (vec![numeric], None),
+            Builtin::Min | Builtin::Max => (vec![numeric; 2], None),
+            Builtin::Clamp => (vec![numeric; 3], None),
+            Builtin::Substring => (
+                vec![vec![string_ty], vec![int_ty], vec![int_ty]],
+                Some(string_ty),
+            ),
+            Builtin::Contains => (
+                vec![string_or_list, vec![first_elem.unwrap_or(string_ty)]],
+                Some(bool_ty),
+            ),
+            Builtin::StartsWith => (vec![vec![string_ty]; 2], Some(bool_ty)),
+            Builtin::Get => (vec![vec![first_ty], vec![int_ty]], first_elem),
+            Builtin::Push => (
+                vec![vec![first_ty], vec![first_elem.unwrap()]],
+                Some(first_ty),
+            ),
+            Builtin::Concat => (vec![vec![first_ty]; 2], Some(first_ty)),
+            Builtin::Random => (vec![numeric; 2], None),
+            Builtin::Chance => (vec![vec![float_ty]], Some(bool_ty)),
+        };
+
+        // numeric builtins need all arguments to be of the same type
+        let params = if ret.is_none() {
+            if params[0].contains(&first_ty) {
+                vec![vec![first_ty]; params.len()]
+            } else {
+                params
+            }
+        } else {
+            params
+        };
+
+        let mut ok = true;
+
+        for ((arg, expected_types), param_name) in
+            args.iter().zip(params).zip(func.parameter_names())
+        {
+            let ty = self.prog.expr_type(*arg).unwrap();
+            if !expected_types.contains(&ty) {
+                self.add_error(Error::TypeMismatchBuiltinCall {
+                    func_name: func_name.clone(),
+                    param_name,
+                    expr: *arg,
+                    expected_types,
+                });
+                ok = false;
+            }
+        }
+
+        if ok {
+            Some(ret.unwrap_or(first_ty))
+        } else {
+            None
+        }
+    }
+
+    /// Translates an expression that has to be known at compile time.
+    ///
+    /// The returned expression is always a literal.
+    fn translate_const_expr(
+        &mut self,
+        vars: &VariableMap,
+        expr: &ast::Expression,
+    ) -> Option<ExpressionId> {
+        let id = self.translate_expr(vars, expr)?;
+
+        if let Some(folded) = self.fold_constant(id) {
+            let ty = self.prog.expr_type(id).unwrap();
+            Some(self.prog.add_expression(expr.fc(), folded, ty))
+        } else {
+            self.add_error(Error::NonConstantExpression { fc: expr.fc() });
+            None
+        }
+    }
+
+    /// The literal of an expression that was folded by `translate_const_expr`.
+    fn folded_literal(&self, id: ExpressionId) -> Literal {
+        match &self.prog[id] {
+            Expression::BoolLiteral(b) => Literal::Bool(*b),
+            Expression::IntegerLiteral(i) => Literal::Integer(*i),
+            Expression::FloatLiteral(f) => Literal::Float(*f),
+            Expression::StringLiteral(s) => Literal::String(s.clone()),
+            Expression::NoneLiteral => Literal::None,
+            Expression::Variant { variant, .. } => Literal::Variant(*variant),
+            _ => unreachable!("constant expressions are folded into literals"),
+        }
+    }
+
+    /// Evaluates a translated expression into a literal, if that is possible
+    /// without running the program.
+    fn fold_constant(&self, id: ExpressionId) -> Option<Expression> {
+        use Expression::*;
+
+        match &self.prog[id] {
+            lit @ (BoolLiteral(_) | IntegerLiteral(_) | FloatLiteral(_) | StringLiteral(_)
+            | NoneLiteral) => Some(lit.clone()),
+            // only variants without a payload are literals
+            lit @ Variant { fields, .. } if fields.is_empty() => Some(lit.clone()),
+            PrefixOp { op, expr } => match (op, self.fold_constant(*expr)?) {
+                (PrefixOperator::Neg, IntegerLiteral(i)) => Some(IntegerLiteral(i.checked_neg()?)),
+                (PrefixOperator::Neg, FloatLiteral(f)) => Some(FloatLiteral(-f)),
+                (PrefixOperator::Not, BoolLiteral(b)) => Some(BoolLiteral(!b)),
+                _ => None,
+            },
+            InfixOp {
+                op,
+                args: [lhs, rhs],
+            } => {
+                let lhs = self.fold_constant(*lhs)?;
+                let rhs = self.fold_constant(*rhs)?;
+
+                use InfixOperator::*;
+
+                match (op, lhs, rhs) {
+                    (Add, IntegerLiteral(a), IntegerLiteral(b)) => {
+                        Some(IntegerLiteral(a.checked_add(b)?))
+                    }
+                    (Add, StringLiteral(a), StringLiteral(b)) => Some(StringLiteral(a + &b)),
+                    (Sub, IntegerLiteral(a), IntegerLiteral(b)) => {
+                        Some(IntegerLiteral(a.checked_sub(b)?))
+                    }
+                    (Mul, IntegerLiteral(a), IntegerLiteral(b)) => {
+                        Some(IntegerLiteral(a.checked_mul(b)?))
+                    }
+                    (Div, IntegerLiteral(a), IntegerLiteral(b)) => {
+                        Some(IntegerLiteral(a.checked_div(b)?))
+                    }
+                    (Mod, IntegerLiteral(a), IntegerLiteral(b)) => {
+                        Some(IntegerLiteral(a.checked_rem(b)?))
+                    }
+
+                    (Add, FloatLiteral(a), FloatLiteral(b)) => Some(FloatLiteral(a + b)),
+                    (Sub, FloatLiteral(a), FloatLiteral(b)) => Some(FloatLiteral(a - b)),
+                    (Mul, FloatLiteral(a), FloatLiteral(b)) => Some(FloatLiteral(a * b)),
+                    (Div, FloatLiteral(a), FloatLiteral(b)) => Some(FloatLiteral(a / b)),
+                    (Mod, FloatLiteral(a), FloatLiteral(b)) => Some(FloatLiteral(a % b)),
+
+                    (Eq, BoolLiteral(a), BoolLiteral(b)) => Some(BoolLiteral(a == b)),
+                    (Eq, IntegerLiteral(a), IntegerLiteral(b)) => Some(BoolLiteral(a == b)),
+                    (Eq, FloatLiteral(a), FloatLiteral(b)) => Some(BoolLiteral(a == b)),
+                    (Eq, StringLiteral(a), StringLiteral(b)) => Some(BoolLiteral(a == b)),
+
+                    (Neq, BoolLiteral(a), BoolLiteral(b)) => Some(BoolLiteral(a != b)),
+                    (Neq, IntegerLiteral(a), IntegerLiteral(b)) => Some(BoolLiteral(a != b)),
+                    (Neq, FloatLiteral(a), FloatLiteral(b)) => Some(BoolLiteral(a != b)),
+                    (Neq, StringLiteral(a), StringLiteral(b)) => Some(BoolLiteral(a != b)),
+
+                    (Lt, IntegerLiteral(a), IntegerLiteral(b)) => Some(BoolLiteral(a < b)),
+                    (Lte, IntegerLiteral(a), IntegerLiteral(b)) => Some(BoolLiteral(a <= b)),
+                    (Gt, IntegerLiteral(a), IntegerLiteral(b)) => Some(BoolLiteral(a > b)),
+                    (Gte, IntegerLiteral(a), IntegerLiteral(b)) => Some(BoolLiteral(a >= b)),
+
+                    (Lt, FloatLiteral(a), FloatLiteral(b)) => Some(BoolLiteral(a < b)),
+                    (Lte, FloatLiteral(a), FloatLiteral(b)) => Some(BoolLiteral(a <= b)),
+                    (Gt, FloatLiteral(a), FloatLiteral(b)) => Some(BoolLiteral(a > b)),
+                    (Gte, FloatLiteral(a), FloatLiteral(b)) => Some(BoolLiteral(a >= b)),
+
+                    (And, BoolLiteral(a), BoolLiteral(b)) => Some(BoolLiteral(a && b)),
+                    (Or, BoolLiteral(a), BoolLiteral(b)) => Some(BoolLiteral(a || b)),
+                    (Xor, BoolLiteral(a), BoolLiteral(b)) => Some(BoolLiteral(a ^ b)),
+                    (Xor, IntegerLiteral(a), IntegerLiteral(b)) => Some(IntegerLiteral(a ^ b)),
+
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
+/// The path of a variable or a field of a variable, like `p.partner`.
+fn place_path(expr: &ast::Expression) -> Option<String> {
+    match expr {
+        ast::Expression::Variable(name) => Some(name.1.clone()),
+        ast::Expression::FieldAccess { base, field_name } => {
+            Some(format!("{}.{}", place_path(base)?, field_name.1))
+        }
+        _ => None,
+    }
+}
+
+/// Collects the paths that can't be `none` when `cond` evaluates to `value`.
+fn collect_narrowed_places(cond: &ast::Expression, value: bool, places: &mut Vec<String>) {
+    match cond {
+        ast::Expression::OptionTest { expr, is_some, .. } if *is_some == value => {
+            places.extend(place_path(expr));
+        }
+        ast::Expression::PrefixOp {
+            op: (_, ast::PrefixOperator::Not),
+            expr,
+        } => collect_narrowed_places(expr, !value, places),
+        ast::Expression::InfixOp {
+            op: (_, InfixOperator::And),
+            args,
+        } if value => {
+            collect_narrowed_places(&args[0], value, places);
+            collect_narrowed_places(&args[1], value, places);
+        }
+        ast::Expression::InfixOp {
+            op: (_, InfixOperator::Or),
+            args,
+        } if !value => {
+            collect_narrowed_places(&args[0], value, places);
+            collect_narrowed_places(&args[1], value, places);
+        }
+        _ => {}
+    }
+}
+
+/// The name of the record, enum or builtin type that a type is made of.
+fn type_base_name(ty: &ast::Type) -> &Identifier {
+    match ty {
+        ast::Type::Named(n) => n,
+        ast::Type::List { elem, .. } => type_base_name(elem),
+        ast::Type::Optional { inner, .. } => type_base_name(inner),
+    }
+}
+
+/// Collects the names of all variables referenced in an expression.
+fn collect_variable_names<'a>(expr: &'a ast::Expression, names: &mut Vec<&'a Identifier>) {
+    match expr {
+        ast::Expression::Literal(_) => {}
+        // `[x]` might be a list of a single variable
+        ast::Expression::Concentration(name) => names.push(name),
+        ast::Expression::FilteredConcentration { filter, .. } => {
+            collect_variable_names(filter, names)
+        }
+        ast::Expression::InstanceField { .. }
+        | ast::Expression::Aggregate { .. }
+        | ast::Expression::Iteration(_)
+        | ast::Expression::Time(_) => {}
+        ast::Expression::List { elems, .. } => {
+            for elem in elems {
+                collect_variable_names(elem, names);
+            }
+        }
+        ast::Expression::Variable(name) => names.push(name),
+        ast::Expression::FieldAccess { base, .. } => collect_variable_names(base, names),
+        ast::Expression::OptionTest { expr, .. } => collect_variable_names(expr, names),
+        ast::Expression::PrefixOp { expr, .. } => collect_variable_names(expr, names),
+        ast::Expression::InfixOp { args, .. } => {
+            collect_variable_names(&args[0], names);
+            collect_variable_names(&args[1], names);
+        }
+        ast::Expression::Call {
+            base, arguments, ..
+        } => {
+            if let Some(base) = base {
+                collect_variable_names(base, names);
+            }
+            for (_, arg) in arguments {
+                collect_variable_names(arg, names);
+            }
+        }
+        ast::Expression::Variant { fields, .. } => {
+            for (_, field) in fields {
+                collect_variable_names(field, names);
+            }
+        }
+        ast::Expression::Match { expr, arms, .. } => {
+            collect_variable_names(expr, names);
+            for arm in arms {
+                collect_variable_names(&arm.body, names);
+            }
+        }
     }
 }
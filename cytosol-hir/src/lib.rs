@@ -4,6 +4,7 @@ use cytosol_syntax::{Identifier, FC};
 use id_arena::Arena;
 
 pub mod ast_to_hir;
+mod templates;
 pub mod types;
 
 use types::*;
@@ -13,12 +14,25 @@ pub use cytosol_syntax as ast;
 pub struct Program {
     pub types: Arena<Type>,
     pub types_by_name: HashMap<String, TypeId>,
+    /// `list<T>` types, keyed by the element type
+    pub list_types: HashMap<TypeId, TypeId>,
+    /// `T?` types, keyed by the inner type
+    pub optional_types: HashMap<TypeId, TypeId>,
     pub exts: Arena<Extern>,
     pub exts_by_name: HashMap<String, ExternId>,
     pub exts_fc: HashMap<ExternId, FC>,
     pub records: Arena<Record>,
     pub records_by_name: HashMap<String, RecordId>,
     pub records_fc: HashMap<RecordId, FC>,
+    pub enums: Arena<Enum>,
+    pub enums_by_name: HashMap<String, EnumId>,
+    pub enums_fc: HashMap<EnumId, FC>,
+    pub consts: Arena<Const>,
+    pub consts_by_name: HashMap<String, ConstId>,
+    pub consts_fc: HashMap<ConstId, FC>,
+    pub params: Arena<Param>,
+    pub params_by_name: HashMap<String, ParamId>,
+    pub params_fc: HashMap<ParamId, FC>,
 
     pub rules: Arena<Rule>,
     pub rules_fc: HashMap<RuleId, FC>,
@@ -33,7 +47,9 @@ pub struct Program {
 
     pub type_bool_id: TypeId,
     pub type_int_id: TypeId,
+    pub type_float_id: TypeId,
     pub type_string_id: TypeId,
+    pub type_none_id: TypeId,
 }
 
 impl Default for Program {
@@ -47,22 +63,36 @@ impl Program {
         let mut types = Arena::new();
         let type_bool_id = types.alloc(Type::Bool);
         let type_int_id = types.alloc(Type::Int);
+        let type_float_id = types.alloc(Type::Float);
         let type_string_id = types.alloc(Type::String);
+        let type_none_id = types.alloc(Type::None);
 
         let mut types_by_name = HashMap::new();
         let _ = types_by_name.insert("bool".to_string(), type_bool_id);
         let _ = types_by_name.insert("int".to_string(), type_int_id);
+        let _ = types_by_name.insert("float".to_string(), type_float_id);
         let _ = types_by_name.insert("string".to_string(), type_string_id);
 
         Self {
             types,
             types_by_name,
+            list_types: Default::default(),
+            optional_types: Default::default(),
             exts: Default::default(),
             exts_by_name: Default::default(),
             exts_fc: Default::default(),
             records: Default::default(),
             records_by_name: Default::default(),
             records_fc: Default::default(),
+            enums: Default::default(),
+            enums_by_name: Default::default(),
+            enums_fc: Default::default(),
+            consts: Default::default(),
+            consts_by_name: Default::default(),
+            consts_fc: Default::default(),
+            params: Default::default(),
+            params_by_name: Default::default(),
+            params_fc: Default::default(),
             rules: Default::default(),
             rules_fc: Default::default(),
             genes: Default::default(),
@@ -75,7 +105,9 @@ impl Program {
 
             type_bool_id,
             type_int_id,
+            type_float_id,
             type_string_id,
+            type_none_id,
         }
     }
 
@@ -94,9 +126,64 @@ impl Program {
                     }
                 }
             }
+            Type::Enum(enum_id) => {
+                let name = self[*enum_id].name.1.clone();
+
+                match self.types_by_name.entry(name) {
+                    std::collections::hash_map::Entry::Occupied(entry) => *entry.get(),
+                    std::collections::hash_map::Entry::Vacant(entry) => {
+                        let id = self.types.alloc(val);
+                        let _ = entry.insert(id);
+                        id
+                    }
+                }
+            }
             Type::Bool => unreachable!("Should ever add `bool` as a type"),
             Type::Int => unreachable!("Shouldn't ever add `int` as a type"),
+            Type::Float => unreachable!("Shouldn't ever add `float` as a type"),
             Type::String => unreachable!("Shouldn't ever add `string` as a type"),
+            Type::List(_) => unreachable!("List types are added with `list_type`"),
+            Type::Optional(_) => unreachable!("Optional types are added with `optional_type`"),
+            Type::None => unreachable!("Shouldn't ever add `none` as a type"),
+        }
+    }
+
+    /// Returns the type `list<elem>`, adding it if it doesn't exist yet.
+    pub fn list_type(&mut self, elem: TypeId) -> TypeId {
+        let types = &mut self.types;
+        *self
+            .list_types
+            .entry(elem)
+            .or_insert_with(|| types.alloc(Type::List(elem)))
+    }
+
+    /// Returns the type `inner?`, adding it if it doesn't exist yet.
+    ///
+    /// Types that can already be `none` are returned unchanged.
+    pub fn optional_type(&mut self, inner: TypeId) -> TypeId {
+        if matches!(self.types[inner], Type::Optional(_) | Type::None) {
+            return inner;
+        }
+
+        let types = &mut self.types;
+        *self
+            .optional_types
+            .entry(inner)
+            .or_insert_with(|| types.alloc(Type::Optional(inner)))
+    }
+
+    /// Checks if a value of type `from` can be used where a value of type
+    /// `to` is expected.
+    ///
+    /// Besides equal types, `none` and values of type `T` can be used as `T?`.
+    pub fn type_is_assignable(&self, to: TypeId, from: TypeId) -> bool {
+        if to == from {
+            return true;
+        }
+
+        match self.types[to] {
+            Type::Optional(inner) => from == inner || from == self.type_none_id,
+            _ => false,
         }
     }
 
@@ -105,8 +192,13 @@ impl Program {
         match ty {
             Type::Bool => None,
             Type::Int => None,
+            Type::Float => None,
             Type::String => None,
+            Type::List(_) => None,
+            Type::Optional(_) => None,
+            Type::None => None,
             Type::Record(id) => self.record(*id).map(|a| &a.name),
+            Type::Enum(id) => self.enum_def(*id).map(|e| &e.name),
         }
     }
 
@@ -116,7 +208,17 @@ impl Program {
         match ty {
             Type::Bool => Some(("bool".to_string(), None)),
             Type::Int => Some(("int".to_string(), None)),
+            Type::Float => Some(("float".to_string(), None)),
             Type::String => Some(("string".to_string(), None)),
+            Type::List(elem) => {
+                let (elem_name, _) = self.type_name(*elem)?;
+                Some((format!("list<{}>", elem_name), None))
+            }
+            Type::Optional(inner) => {
+                let (inner_name, _) = self.type_name(*inner)?;
+                Some((format!("{}?", inner_name), None))
+            }
+            Type::None => Some(("none".to_string(), None)),
             _ => {
                 let ident = self.type_ident(type_id).unwrap();
                 Some((ident.1.clone(), Some(ident.0)))
@@ -135,8 +237,8 @@ impl Program {
                 let id = self.exts.alloc(val);
                 let _ = entry.insert(id);
                 let overwritten = self.exts_fc.insert(id, fc).is_some();
-                debug_assert_eq!(
-                    overwritten, false,
+                debug_assert!(
+                    !overwritten,
                     "FC should only be inserted for a fresh ExternId"
                 );
                 Some(id)
@@ -164,8 +266,8 @@ impl Program {
                 let id = self.records.alloc(val);
                 let _ = entry.insert(id);
                 let overwritten = self.records_fc.insert(id, fc).is_some();
-                debug_assert_eq!(
-                    overwritten, false,
+                debug_assert!(
+                    !overwritten,
                     "FC should only be inserted for a fresh RecordId"
                 );
                 Some(id)
@@ -177,11 +279,93 @@ impl Program {
         self.records_by_name.get(name).copied()
     }
 
+    /// The records whose instances count as instances of `family`, starting
+    /// with the family record itself.
+    pub fn family_members(&self, family: RecordId) -> impl Iterator<Item = RecordId> + '_ {
+        std::iter::once(family).chain(self[family].members.iter().copied())
+    }
+
+    /// The index that field `idx` of `family` has in the fields of `member`.
+    pub fn family_field(&self, family: RecordId, member: RecordId, idx: FieldIndex) -> FieldIndex {
+        if family == member {
+            idx
+        } else {
+            self[member].family_fields[idx]
+        }
+    }
+
+    pub fn add_enum(&mut self, fc: FC, val: Enum) -> Option<EnumId> {
+        let name = val.name.1.clone();
+
+        if self.type_by_name(&name).is_some() {
+            return None;
+        }
+
+        let id = self.enums.alloc(val);
+        self.enums_by_name.insert(name, id);
+        self.enums_fc.insert(id, fc);
+        Some(id)
+    }
+
+    pub fn enum_by_name(&self, name: &str) -> Option<EnumId> {
+        self.enums_by_name.get(name).copied()
+    }
+
+    /// Constants and parameters share one namespace, so adding a constant
+    /// fails if either one with the same name already exists.
+    pub fn add_const(&mut self, fc: FC, val: Const) -> Option<ConstId> {
+        if self.param_by_name(&val.name.1).is_some() {
+            return None;
+        }
+
+        match self.consts_by_name.entry(val.name.1.clone()) {
+            std::collections::hash_map::Entry::Occupied(_) => None,
+            std::collections::hash_map::Entry::Vacant(entry) => {
+                let id = self.consts.alloc(val);
+                let _ = entry.insert(id);
+                let overwritten = self.consts_fc.insert(id, fc).is_some();
+                debug_assert!(
+                    !overwritten,
+                    "FC should only be inserted for a fresh ConstId"
+                );
+                Some(id)
+            }
+        }
+    }
+
+    pub fn const_by_name(&self, name: &str) -> Option<ConstId> {
+        self.consts_by_name.get(name).copied()
+    }
+
+    pub fn add_param(&mut self, fc: FC, val: Param) -> Option<ParamId> {
+        if self.const_by_name(&val.name.1).is_some() {
+            return None;
+        }
+
+        match self.params_by_name.entry(val.name.1.clone()) {
+            std::collections::hash_map::Entry::Occupied(_) => None,
+            std::collections::hash_map::Entry::Vacant(entry) => {
+                let id = self.params.alloc(val);
+                let _ = entry.insert(id);
+                let overwritten = self.params_fc.insert(id, fc).is_some();
+                debug_assert!(
+                    !overwritten,
+                    "FC should only be inserted for a fresh ParamId"
+                );
+                Some(id)
+            }
+        }
+    }
+
+    pub fn param_by_name(&self, name: &str) -> Option<ParamId> {
+        self.params_by_name.get(name).copied()
+    }
+
     pub fn add_rule(&mut self, fc: FC, rule: Rule) -> RuleId {
         let id = self.rules.alloc(rule);
         let overwritten = self.rules_fc.insert(id, fc).is_some();
-        debug_assert_eq!(
-            overwritten, false,
+        debug_assert!(
+            !overwritten,
             "FC should only be inserted for a fresh RuleId"
         );
         id
@@ -190,8 +374,8 @@ impl Program {
     pub fn add_gene(&mut self, fc: FC, val: Gene) -> GeneId {
         let id = self.genes.alloc(val);
         let overwritten = self.genes_fc.insert(id, fc).is_some();
-        debug_assert_eq!(
-            overwritten, false,
+        debug_assert!(
+            !overwritten,
             "FC should only be inserted for a fresh GeneId"
         );
         id
@@ -206,13 +390,13 @@ impl Program {
     pub fn add_expression(&mut self, fc: FC, val: Expression, ty: TypeId) -> ExpressionId {
         let id = self.exprs.alloc(val);
         let fc_overwritten = self.exprs_fc.insert(id, fc).is_some();
-        debug_assert_eq!(
-            fc_overwritten, false,
+        debug_assert!(
+            !fc_overwritten,
             "FC should only be inserted for a fresh ExpressionId"
         );
         let ty_overwritten = self.exprs_type.insert(id, ty).is_some();
-        debug_assert_eq!(
-            ty_overwritten, false,
+        debug_assert!(
+            !ty_overwritten,
             "TypeId should only be inserted for a fresh ExpressionId"
         );
         id
@@ -246,6 +430,13 @@ impl Program {
     fc_impl!(ext_fc, ExternId, exts_fc);
     get_impl!(record, RecordId, Record, records);
     fc_impl!(record_fc, RecordId, records_fc);
+
+    get_impl!(enum_def, EnumId, Enum, enums);
+    fc_impl!(enum_fc, EnumId, enums_fc);
+    get_impl!(constant, ConstId, Const, consts);
+    fc_impl!(const_fc, ConstId, consts_fc);
+    get_impl!(param, ParamId, Param, params);
+    fc_impl!(param_fc, ParamId, params_fc);
     get_impl!(rule, RuleId, Rule, rules);
     fc_impl!(rule_fc, RuleId, rules_fc);
     get_impl!(gene, GeneId, Gene, genes);
@@ -271,6 +462,9 @@ macro_rules! index_impl {
 index_impl!(TypeId, Type, types);
 index_impl!(ExternId, Extern, exts);
 index_impl!(RecordId, Record, records);
+index_impl!(EnumId, Enum, enums);
+index_impl!(ConstId, Const, consts);
+index_impl!(ParamId, Param, params);
 index_impl!(RuleId, Rule, rules);
 index_impl!(GeneId, Gene, genes);
 index_impl!(GeneStatementId, GeneStatement, gene_stmts);
@@ -0,0 +1,166 @@
+//! Filling in the type arguments of template instantiations.
+//!
+//! The type parameters of a template are used like record names, so every
+//! place that names a record or a type is replaced. The replaced names keep
+//! the position in the template, so errors point at the template.
+
+use ahash::AHashMap as HashMap;
+
+use cytosol_syntax as ast;
+
+/// The record names that the type parameters stand for
+pub(crate) type TypeArgs<'a> = HashMap<&'a str, &'a str>;
+
+pub(crate) fn substitute_rule(rule: &mut ast::Rule, args: &TypeArgs) {
+    for reactant in &mut rule.reactants {
+        substitute_binding(reactant, args);
+    }
+    for product in &mut rule.products {
+        substitute_product(product, args);
+    }
+    for expr in rule.when.iter_mut().chain(&mut rule.reverse_when) {
+        substitute_expr(expr, args);
+    }
+    for (_, expr) in &mut rule.lets {
+        substitute_expr(expr, args);
+    }
+}
+
+pub(crate) fn substitute_gene(gene: &mut ast::Gene, args: &TypeArgs) {
+    for factor in &mut gene.factors {
+        substitute_binding(factor, args);
+    }
+    if let Some(expr) = &mut gene.when {
+        substitute_expr(expr, args);
+    }
+    substitute_statements(&mut gene.body, args);
+}
+
+fn substitute_name(name: &mut ast::Identifier, args: &TypeArgs) {
+    if let Some(arg) = args.get(name.1.as_str()) {
+        name.1 = arg.to_string();
+    }
+}
+
+fn substitute_fields(fields: &mut [(ast::Identifier, ast::Expression)], args: &TypeArgs) {
+    for (_, expr) in fields {
+        substitute_expr(expr, args);
+    }
+}
+
+fn substitute_binding(binding: &mut ast::Binding, args: &TypeArgs) {
+    substitute_name(&mut binding.name, args);
+    substitute_fields(&mut binding.fields, args);
+}
+
+fn substitute_product(product: &mut ast::Product, args: &TypeArgs) {
+    if let Some(ast::Quantity::Expression(expr)) = &mut product.quantity {
+        substitute_expr(expr, args);
+    }
+    substitute_name(&mut product.name, args);
+    if let Some(base) = &mut product.base {
+        substitute_expr(base, args);
+    }
+    substitute_fields(&mut product.fields, args);
+}
+
+fn substitute_type(ty: &mut ast::Type, args: &TypeArgs) {
+    match ty {
+        ast::Type::Named(name) => substitute_name(name, args),
+        ast::Type::List { elem: inner, .. } | ast::Type::Optional { inner, .. } => {
+            substitute_type(inner, args)
+        }
+    }
+}
+
+fn substitute_statements(stmts: &mut [ast::GeneStatement], args: &TypeArgs) {
+    for stmt in stmts {
+        substitute_statement(stmt, args);
+    }
+}
+
+fn substitute_statement(stmt: &mut ast::GeneStatement, args: &TypeArgs) {
+    match stmt {
+        ast::GeneStatement::Call { arguments, .. } => substitute_fields(arguments, args),
+        ast::GeneStatement::Express(_, product) => substitute_product(product, args),
+        ast::GeneStatement::ExpressAfter { delay, product, .. } => {
+            substitute_expr(delay, args);
+            substitute_product(product, args);
+        }
+        ast::GeneStatement::WithProbability {
+            probability,
+            statement,
+            ..
+        } => {
+            substitute_expr(probability, args);
+            substitute_statement(statement, args);
+        }
+        ast::GeneStatement::Let { value, .. } => substitute_expr(value, args),
+        ast::GeneStatement::If {
+            cond,
+            then_body,
+            else_body,
+            ..
+        } => {
+            substitute_expr(cond, args);
+            substitute_statements(then_body, args);
+            substitute_statements(else_body, args);
+        }
+        ast::GeneStatement::Repeat { count, body, .. } => {
+            substitute_expr(count, args);
+            substitute_statements(body, args);
+        }
+    }
+}
+
+fn substitute_expr(expr: &mut ast::Expression, args: &TypeArgs) {
+    match expr {
+        ast::Expression::Literal(_)
+        | ast::Expression::Variable(_)
+        | ast::Expression::InstanceField { .. }
+        | ast::Expression::Iteration(_)
+        | ast::Expression::Time(_) => {}
+        ast::Expression::FieldAccess { base, .. } => substitute_expr(base, args),
+        ast::Expression::PrefixOp { expr, .. } => substitute_expr(expr, args),
+        ast::Expression::InfixOp { args: operands, .. } => {
+            for operand in operands.iter_mut() {
+                substitute_expr(operand, args);
+            }
+        }
+        ast::Expression::Concentration(name) => substitute_name(name, args),
+        ast::Expression::FilteredConcentration { name, filter, .. } => {
+            substitute_name(name, args);
+            substitute_expr(filter, args);
+        }
+        ast::Expression::Aggregate { record, .. } => substitute_name(record, args),
+        ast::Expression::List { elem_ty, elems, .. } => {
+            if let Some(ty) = elem_ty {
+                substitute_type(ty, args);
+            }
+            for elem in elems {
+                substitute_expr(elem, args);
+            }
+        }
+        ast::Expression::OptionTest { expr, .. } => substitute_expr(expr, args),
+        // record constructions name the record like a function
+        ast::Expression::Call {
+            name,
+            base,
+            arguments,
+            ..
+        } => {
+            substitute_name(name, args);
+            if let Some(base) = base {
+                substitute_expr(base, args);
+            }
+            substitute_fields(arguments, args);
+        }
+        ast::Expression::Variant { fields, .. } => substitute_fields(fields, args),
+        ast::Expression::Match { expr, arms, .. } => {
+            substitute_expr(expr, args);
+            for arm in arms {
+                substitute_expr(&mut arm.body, args);
+            }
+        }
+    }
+}
@@ -7,8 +7,16 @@ pub type TypeId = Id<Type>;
 pub enum Type {
     Bool,
     Int,
+    Float,
     String,
     Record(RecordId),
+    Enum(EnumId),
+    /// `list<T>` with the element type
+    List(TypeId),
+    /// `T?` with the type of the value when it is not `none`
+    Optional(TypeId),
+    /// The type of the `none` literal, it can be used as any optional type
+    None,
 }
 
 pub type RecordId = Id<Record>;
@@ -18,21 +26,91 @@ pub struct Record {
     pub name: Identifier,
     pub field_names: Vec<Identifier>,
     pub fields: Vec<TypeId>,
+    /// Values used for omitted fields, already folded into literal expressions.
+    pub defaults: Vec<Option<ExpressionId>>,
+    /// Expressing a singleton record while an instance is present does nothing
+    pub singleton: bool,
+    /// The family named in `record R(..) is Family`
+    pub family: Option<RecordId>,
+    /// The index of each field of the family in the fields of this record
+    pub family_fields: Vec<FieldIndex>,
+    /// The records that named this record as their family
+    pub members: Vec<RecordId>,
+    /// Field patterns, filters or aggregates look at the instances, so the
+    /// runtime has to know more than their number
+    pub inspected: bool,
 }
 
+pub type EnumId = Id<Enum>;
+
+#[derive(Debug, Clone)]
+pub struct Enum {
+    pub name: Identifier,
+    pub variants: Vec<Variant>,
+}
+
+impl Enum {
+    pub fn variant_by_name(&self, name: &str) -> Option<VariantIndex> {
+        self.variants.iter().position(|v| v.name.1 == name)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Variant {
+    pub name: Identifier,
+    pub field_names: Vec<Identifier>,
+    pub fields: Vec<TypeId>,
+}
+
+pub type VariantIndex = usize;
+
 pub type FieldIndex = usize;
 
+#[derive(Debug)]
+pub enum Quantity {
+    Literal(usize),
+    /// Evaluated every time the product is created, must be an `int`
+    Expression(ExpressionId),
+}
+
 #[derive(Debug)]
 pub struct Product {
-    pub quantity: usize,
+    pub quantity: Quantity,
     pub record: RecordId,
-    pub arguments: Vec<ExpressionId>,
+    /// The record value that is expressed, a `Record` or `RecordUpdate`
+    /// expression
+    pub value: ExpressionId,
 }
 
 #[derive(Debug)]
 pub enum Bind {
     Quantity(usize),
     Named(Identifier),
+    /// A single instance whose fields match, like `Person(name: n, age: 30)`
+    Pattern {
+        name: Option<Identifier>,
+        fields: Vec<(FieldIndex, FieldPattern)>,
+    },
+}
+
+#[derive(Debug)]
+pub enum FieldPattern {
+    /// The value of the field is bound to a variable
+    Bind(Identifier),
+    /// Only instances with this value match
+    Value(Literal),
+}
+
+/// The value of a constant expression, folded at compile time.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Literal {
+    Bool(bool),
+    Integer(isize),
+    Float(f64),
+    String(String),
+    None,
+    /// A variant without a payload
+    Variant(VariantIndex),
 }
 
 pub type RuleId = Id<Rule>;
@@ -40,8 +118,16 @@ pub type RuleId = Id<Rule>;
 #[derive(Debug)]
 pub struct Rule {
     pub binds: Vec<(Bind, RecordId)>,
+    /// Reactants that are required but not consumed
+    pub catalysts: Vec<(Bind, RecordId)>,
+    /// Rules with a higher priority claim their reactants first
+    pub priority: isize,
+    /// Share of the runs among rules with the same priority
+    pub weight: usize,
     pub when: Option<ExpressionId>,
     pub products: Vec<Product>,
+    /// evaluated in order before the products
+    pub lets: Vec<(Identifier, ExpressionId)>,
 }
 
 pub type ExternId = Id<Extern>;
@@ -53,10 +139,42 @@ pub struct Extern {
     pub parameters: Vec<TypeId>,
 }
 
+pub type ConstId = Id<Const>;
+
+#[derive(Debug, Clone)]
+pub struct Const {
+    pub name: Identifier,
+    pub ty: TypeId,
+    /// The value of the constant, already folded into a literal expression.
+    pub value: ExpressionId,
+}
+
+pub type ParamId = Id<Param>;
+
+#[derive(Debug, Clone)]
+pub struct Param {
+    pub name: Identifier,
+    pub ty: TypeId,
+    /// The value used when the host application does not provide one,
+    /// already folded into a literal expression.
+    pub default: ExpressionId,
+}
+
 pub type GeneId = Id<Gene>;
 
 #[derive(Debug)]
 pub struct Gene {
+    /// Runs at most once for each environment
+    pub once: bool,
+    /// Runs once for every new instance of its only factor instead of while
+    /// the factor is present
+    pub on_arrival: bool,
+    /// Number of iterations the gene is not eligable after it ran
+    pub cooldown: usize,
+    /// Genes with a higher priority claim their factors first
+    pub priority: isize,
+    /// Bias of the random order among genes with the same priority
+    pub weight: usize,
     pub binds: Vec<(Bind, RecordId)>,
     pub when: Option<ExpressionId>,
     pub body: Vec<GeneStatementId>,
@@ -71,6 +189,24 @@ pub enum GeneStatement {
         arguments: Vec<ExpressionId>,
     },
     Express(Product),
+    /// The product is created now, but only added `delay` iterations later
+    ExpressAfter {
+        delay: ExpressionId,
+        product: Product,
+    },
+    Let {
+        name: Identifier,
+        value: ExpressionId,
+    },
+    If {
+        cond: ExpressionId,
+        then_body: Vec<GeneStatementId>,
+        else_body: Vec<GeneStatementId>,
+    },
+    Repeat {
+        count: ExpressionId,
+        body: Vec<GeneStatementId>,
+    },
 }
 
 pub type ExpressionId = Id<Expression>;
@@ -78,9 +214,12 @@ pub type ExpressionId = Id<Expression>;
 #[derive(Debug, Clone)]
 pub enum Expression {
     BoolLiteral(bool),
-    IntegerLiteral(usize),
+    IntegerLiteral(isize),
+    FloatLiteral(f64),
+    NoneLiteral,
     StringLiteral(String),
     Variable(Identifier),
+    Param(ParamId),
     FieldAccess {
         base: ExpressionId,
         field: FieldIndex,
@@ -94,4 +233,172 @@ pub enum Expression {
         args: [ExpressionId; 2],
     },
     Concentration(RecordId),
+    /// Number of instances of the record for which `filter` is `true`
+    FilteredConcentration {
+        record: RecordId,
+        filter: ExpressionId,
+    },
+    /// The instance that is checked by the filter of a `FilteredConcentration`
+    Instance,
+    Aggregate {
+        func: Aggregate,
+        record: RecordId,
+        field: FieldIndex,
+    },
+    /// The number of iterations that ran before the current one
+    Iteration,
+    /// The simulated time, which is set by the driver
+    Time,
+    List(Vec<ExpressionId>),
+    /// Record value, the fields are ordered like in the record definition
+    Record {
+        record: RecordId,
+        fields: Vec<ExpressionId>,
+    },
+    /// Copy of the `base` record value with some fields replaced, like
+    /// `Fib(..f, a: f.b)`. The base is only evaluated once.
+    RecordUpdate {
+        base: ExpressionId,
+        overrides: Vec<(FieldIndex, ExpressionId)>,
+    },
+    OptionTest {
+        expr: ExpressionId,
+        is_some: bool,
+    },
+    BuiltinCall {
+        func: Builtin,
+        /// ordered like the parameters of the builtin function
+        arguments: Vec<ExpressionId>,
+    },
+    Variant {
+        variant: VariantIndex,
+        fields: Vec<ExpressionId>,
+    },
+    Match {
+        expr: ExpressionId,
+        arms: Vec<MatchArm>,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct MatchArm {
+    pub pattern: Pattern,
+    pub body: ExpressionId,
+}
+
+#[derive(Debug, Clone)]
+pub enum Pattern {
+    Wildcard,
+    Variant {
+        variant: VariantIndex,
+        binds: Vec<(FieldIndex, Identifier)>,
+    },
+}
+
+/// Functions over a field of all instances of a record, like `sum(Person.age)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Aggregate {
+    Sum,
+    Min,
+    Max,
+    Avg,
+}
+
+impl Aggregate {
+    pub fn from_name(name: &str) -> Option<Self> {
+        let func = match name {
+            "sum" => Aggregate::Sum,
+            "min" => Aggregate::Min,
+            "max" => Aggregate::Max,
+            "avg" => Aggregate::Avg,
+            _ => return None,
+        };
+        Some(func)
+    }
+}
+
+/// Functions that are available in every expression. All of them are pure,
+/// except for `random` and `chance` which draw from the random number
+/// generator of the run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Builtin {
+    ToString,
+    ToInt,
+    ToFloat,
+    Len,
+    Abs,
+    Min,
+    Max,
+    Clamp,
+    Substring,
+    Contains,
+    StartsWith,
+    Get,
+    Push,
+    Concat,
+    Random,
+    Chance,
+}
+
+impl Builtin {
+    pub fn from_name(name: &str) -> Option<Self> {
+        let func = match name {
+            "to_string" => Builtin::ToString,
+            "to_int" => Builtin::ToInt,
+            "to_float" => Builtin::ToFloat,
+            "len" => Builtin::Len,
+            "abs" => Builtin::Abs,
+            "min" => Builtin::Min,
+            "max" => Builtin::Max,
+            "clamp" => Builtin::Clamp,
+            "substring" => Builtin::Substring,
+            "contains" => Builtin::Contains,
+            "starts_with" => Builtin::StartsWith,
+            "get" => Builtin::Get,
+            "push" => Builtin::Push,
+            "concat" => Builtin::Concat,
+            "random" => Builtin::Random,
+            "chance" => Builtin::Chance,
+            _ => return None,
+        };
+        Some(func)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Builtin::ToString => "to_string",
+            Builtin::ToInt => "to_int",
+            Builtin::ToFloat => "to_float",
+            Builtin::Len => "len",
+            Builtin::Abs => "abs",
+            Builtin::Min => "min",
+            Builtin::Max => "max",
+            Builtin::Clamp => "clamp",
+            Builtin::Substring => "substring",
+            Builtin::Contains => "contains",
+            Builtin::StartsWith => "starts_with",
+            Builtin::Get => "get",
+            Builtin::Push => "push",
+            Builtin::Concat => "concat",
+            Builtin::Random => "random",
+            Builtin::Chance => "chance",
+        }
+    }
+
+    pub fn parameter_names(self) -> &'static [&'static str] {
+        match self {
+            Builtin::ToString | Builtin::ToInt | Builtin::ToFloat | Builtin::Len | Builtin::Abs => {
+                &["value"]
+            }
+            Builtin::Min | Builtin::Max | Builtin::Concat => &["a", "b"],
+            Builtin::Clamp => &["value", "min", "max"],
+            Builtin::Substring => &["value", "start", "end"],
+            Builtin::Contains => &["value", "part"],
+            Builtin::StartsWith => &["value", "prefix"],
+            Builtin::Get => &["value", "index"],
+            Builtin::Push => &["value", "elem"],
+            Builtin::Random => &["lo", "hi"],
+            Builtin::Chance => &["p"],
+        }
+    }
 }
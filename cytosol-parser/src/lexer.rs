@@ -6,7 +6,7 @@ pub struct Token<'src> {
     pub kind: TokenKind<'src>,
 }
 
-#[derive(Debug, Logos, PartialEq, Eq)]
+#[derive(Debug, Logos, PartialEq)]
 pub enum TokenKind<'src> {
     #[token("record")]
     Record,
@@ -29,6 +29,22 @@ pub enum TokenKind<'src> {
     #[token("when")]
     When,
 
+    #[token("match")]
+    Match,
+
+    #[token("@iteration")]
+    Iteration,
+
+    #[token("@time")]
+    Time,
+
+    #[token("true", |_| true)]
+    #[token("false", |_| false)]
+    BoolLiteral(bool),
+
+    #[token("none")]
+    NoneLiteral,
+
     #[token("Ø")]
     #[token("ø")]
     #[token("nothing")]
@@ -40,21 +56,37 @@ pub enum TokenKind<'src> {
     #[regex(r"[0-9][_0-9]*", |lex| parse_integer_literal(lex.slice()))]
     IntegerLiteral(usize),
 
+    #[regex(r"[0-9][_0-9]*\.[0-9][_0-9]*([eE][+-]?[0-9]+)?", |lex| parse_float_literal(lex.slice()))]
+    #[regex(r"[0-9][_0-9]*[eE][+-]?[0-9]+", |lex| parse_float_literal(lex.slice()))]
+    FloatLiteral(f64),
+
     #[token("\"", parse_string_literal)]
     StringLiteral(String),
 
     #[token("->")]
     #[token("→")]
     ArrowR,
+    #[token("<->")]
+    #[token("⇌")]
+    ArrowLR,
+    #[token("=>")]
+    #[token("⇒")]
+    FatArrowR,
 
     #[token(".")]
     Dot,
+    #[token("..")]
+    DotDot,
     #[token(",")]
     Comma,
     #[token(":")]
     Colon,
+    #[token("::")]
+    ColonColon,
     #[token(";")]
     SemiColon,
+    #[token("?")]
+    Question,
 
     #[token("(")]
     ParenOpen,
@@ -80,6 +112,8 @@ pub enum TokenKind<'src> {
     OpStar,
     #[token("/")]
     OpSlash,
+    #[token("%")]
+    OpPercent,
 
     #[token("=")]
     OpEquals,
@@ -102,6 +136,11 @@ pub enum TokenKind<'src> {
     OpAnd,
     #[token("or")]
     OpOr,
+    #[token("⊻")]
+    OpXor,
+    #[token("not")]
+    #[token("¬")]
+    OpNot,
 
     #[error]
     // skip whitespace
@@ -139,6 +178,10 @@ fn parse_integer_literal(s: &str) -> Option<usize> {
     Some(acc)
 }
 
+fn parse_float_literal(s: &str) -> Option<f64> {
+    s.replace('_', "").parse().ok()
+}
+
 fn parse_string_literal<'src>(lex: &mut logos::Lexer<'src, TokenKind<'src>>) -> Option<String> {
     let s = lex.remainder();
 
@@ -226,6 +269,28 @@ mod tests {
         assert_eq!(toks[3].kind, TokenKind::IntegerLiteral(10_000_000));
     }
 
+    #[test]
+    fn float_literal() {
+        let input = r#"
+        1.5
+        0.25
+        1_000.0
+        2e3
+        1.5E-2
+        "#;
+
+        let mut files = SimpleFiles::new();
+        let id = files.add("<test>", input);
+
+        let toks = tokenise(id, input).collect::<Vec<_>>();
+        assert_eq!(toks.len(), 5);
+        assert_eq!(toks[0].kind, TokenKind::FloatLiteral(1.5));
+        assert_eq!(toks[1].kind, TokenKind::FloatLiteral(0.25));
+        assert_eq!(toks[2].kind, TokenKind::FloatLiteral(1000.0));
+        assert_eq!(toks[3].kind, TokenKind::FloatLiteral(2000.0));
+        assert_eq!(toks[4].kind, TokenKind::FloatLiteral(0.015));
+    }
+
     #[test]
     fn identifiers() {
         let input = "A53α";
@@ -237,4 +302,20 @@ mod tests {
         assert_eq!(toks.len(), 1);
         assert_eq!(toks[0].kind, TokenKind::Identifier("A53α"));
     }
+
+    #[test]
+    fn logic_operators() {
+        let input = "not ¬ xor ⊻ %";
+
+        let mut files = SimpleFiles::new();
+        let id = files.add("<test>", input);
+
+        let toks = tokenise(id, input).collect::<Vec<_>>();
+        assert_eq!(toks.len(), 5);
+        assert_eq!(toks[0].kind, TokenKind::OpNot);
+        assert_eq!(toks[1].kind, TokenKind::OpNot);
+        assert_eq!(toks[2].kind, TokenKind::Identifier("xor"));
+        assert_eq!(toks[3].kind, TokenKind::OpXor);
+        assert_eq!(toks[4].kind, TokenKind::OpPercent);
+    }
 }
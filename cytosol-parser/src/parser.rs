@@ -3,8 +3,9 @@ use std::iter::Peekable;
 use thiserror::Error;
 
 use cytosol_syntax::{
-    Binding, BindingAttribute, Expression, Extern, File, FileId, Gene, GeneStatement, HasFC,
-    Identifier, InfixOperator, Literal, PrefixOperator, Product, Record, Rule, Type, FC,
+    Binding, BindingAttribute, Const, Enum, Expression, Extern, File, FileId, Gene, GeneStatement,
+    HasFC, Identifier, InfixOperator, Instantiation, Literal, MatchArm, Param, Pattern,
+    PrefixOperator, Product, Quantity, Record, Rule, Scheduling, Template, Type, Variant, FC,
 };
 
 use crate::{lexer::TokenKind, Token};
@@ -53,6 +54,42 @@ pub enum Error {
 
 type Result<T> = core::result::Result<T, Error>;
 
+/// Functions that can also aggregate a field over all instances of a record
+const AGGREGATES: &[&str] = &["sum", "min", "max", "avg"];
+
+/// Words that only mean something in certain positions. They are lexed as
+/// identifiers, so that they can still be used as names everywhere else.
+const CONTEXTUAL_KEYWORDS: &[&str] = &[
+    "after",
+    "catalyst",
+    "const",
+    "cooldown",
+    "else",
+    "enum",
+    "if",
+    "instantiate",
+    "is",
+    "let",
+    "list",
+    "on",
+    "once",
+    "param",
+    "priority",
+    "probability",
+    "repeat",
+    "reverse",
+    "singleton",
+    "some",
+    "template",
+    "weight",
+    "where",
+    "with",
+    "xor",
+];
+
+/// The end of the argument list, the `..base` value and the named fields
+type RecordArguments = (FC, Option<Expression>, Vec<(Identifier, Expression)>);
+
 /// Parse a list of tokens into the [`File`](cytosol_syntax::types::File) AST.
 ///
 /// All tokens should be from the same file, so should have the same FileId.
@@ -78,43 +115,52 @@ impl<'src, I: Iterator<Item = Token<'src>>> Parser<'src, I> {
         while let Some(t) = self.peek() {
             match t.kind {
                 TokenKind::Record => {
+                    let record = self.parse_record(None)?;
+                    file.records.push(record);
+                }
+                TokenKind::Identifier("singleton") => {
+                    let modifier = self.next().unwrap();
+                    self.expect_item_after_modifier(modifier.fc, "`record`", TokenKind::Record)?;
+                    let record = self.parse_record(Some(modifier.fc))?;
+                    file.records.push(record);
+                }
+                TokenKind::Identifier("enum") => {
                     let start_tok = self.next().unwrap();
-                    let t = &start_tok;
                     let ec = CTX
-                        .start(start_tok.fc, "record definition")
-                        .while_parsing("a record definition");
+                        .start(start_tok.fc, "enum definition")
+                        .while_parsing("an enum definition");
 
                     let name = self.parse_identifier(ec)?;
 
-                    let (fc, fields) = if self.peek_kind(|t| t == &TokenKind::ParenOpen) {
-                        self.grouped_separated(
-                            (TokenKind::ParenOpen, TokenKind::ParenClose),
-                            ec.start(t.fc, "field list")
-                                .while_parsing("the field list of a record item")
-                                .expected("`(`"),
-                            TokenKind::Comma,
-                            ec.start(t.fc, "field list")
-                                .while_parsing("the field list of a record item")
-                                .expected("`,` or `)`"),
-                            |s| {
-                                let ident =
-                                    s.parse_identifier(ec.while_parsing("a record field"))?;
-                                let (colon_fc, _) = s.expect_tok_and_fc(
-                                    ec.while_parsing("a record field").expected("`:`"),
-                                    |t| matches!(t.kind, TokenKind::Colon),
+                    let (end_fc, variants) = self.grouped_separated(
+                        (TokenKind::BraceOpen, TokenKind::BraceClose),
+                        ec.while_parsing("the variant list of an enum item")
+                            .expected("`{`"),
+                        TokenKind::Comma,
+                        ec.while_parsing("the variant list of an enum item")
+                            .expected("`,` or `}`"),
+                        |s| {
+                            let name = s.parse_identifier(ec.while_parsing("an enum variant"))?;
+
+                            let (fc, fields) = if s.peek_kind(|t| t == &TokenKind::ParenOpen) {
+                                let (fields_fc, fields) = s.parse_field_list(
+                                    ec.start(name.fc(), "variant"),
+                                    "the field list of an enum variant",
+                                    "a variant field",
                                 )?;
-                                let ty = s.parse_type(ec.start(colon_fc, "beginning of type"))?;
-                                Ok((ident, ty))
-                            },
-                        )?
-                    } else {
-                        (name.fc(), vec![])
-                    };
+                                (name.fc().merge(fields_fc), fields)
+                            } else {
+                                (name.fc(), vec![])
+                            };
 
-                    file.records.push(Record {
-                        fc: start_tok.fc.merge(fc),
+                            Ok(Variant { fc, name, fields })
+                        },
+                    )?;
+
+                    file.enums.push(Enum {
+                        fc: start_tok.fc.merge(end_fc),
                         name,
-                        fields,
+                        variants,
                     });
                 }
                 TokenKind::Extern => {
@@ -153,111 +199,83 @@ impl<'src, I: Iterator<Item = Token<'src>>> Parser<'src, I> {
                     });
                 }
                 TokenKind::Gene => {
+                    let gene = self.parse_gene(None)?;
+                    file.genes.push(gene);
+                }
+                TokenKind::Identifier("once") => {
+                    let modifier = self.next().unwrap();
+                    self.expect_item_after_modifier(modifier.fc, "`gene`", TokenKind::Gene)?;
+                    let gene = self.parse_gene(Some(modifier.fc))?;
+                    file.genes.push(gene);
+                }
+                TokenKind::Rule => {
+                    let rule = self.parse_rule()?;
+                    file.rules.push(rule);
+                }
+                TokenKind::Identifier("template") => {
+                    let template = self.parse_template()?;
+                    file.templates.push(template);
+                }
+                TokenKind::Identifier("instantiate") => {
                     let start_tok = self.next().unwrap();
                     let ec = CTX
-                        .start(start_tok.fc, "gene item")
-                        .while_parsing("a gene item");
-
-                    let (_, factors) = self.grouped_separated(
-                        (TokenKind::ParenOpen, TokenKind::ParenClose),
-                        ec.while_parsing("a gene factor list").expected("`(`"),
+                        .start(start_tok.fc, "instantiate item")
+                        .while_parsing("an instantiate item");
+
+                    let template = self.parse_identifier(ec)?;
+                    let (end_fc, args) = self.grouped_separated(
+                        (TokenKind::OpLessThan, TokenKind::OpGreaterThan),
+                        ec.while_parsing("the type arguments of a template")
+                            .expected("`<`"),
                         TokenKind::Comma,
-                        ec.while_parsing("a gene factor list")
-                            .expected("`,` or `)`"),
-                        |s| s.parse_binding(ec),
-                    )?;
-
-                    let next = {
-                        let file = self.file;
-                        self.peek().ok_or_else({
-                            || Error::UnexpectedEnd(file, ec.while_parsing("gene item"))
-                        })?
-                    };
-
-                    let when = match &next.kind {
-                        TokenKind::When => {
-                            let next = self.next().unwrap();
-                            let wec = ec
-                                .while_parsing("a when clause")
-                                .start(next.fc, "when clause");
-
-                            let expr = self.parse_expression(wec)?;
-
-                            Some(expr)
-                        }
-                        _ => None,
-                    };
-
-                    let (end_fc, stmts) = self.grouped(
-                        (TokenKind::BraceOpen, TokenKind::BraceClose),
-                        ec.while_parsing("a gene statement list").expected("`{`"),
-                        |s| s.parse_gene_statement(ec),
+                        ec.while_parsing("the type arguments of a template")
+                            .expected("`,` or `>`"),
+                        |s| s.parse_identifier(ec.while_parsing("a type argument")),
                     )?;
 
-                    let fc = start_tok.fc.merge(end_fc);
-
-                    file.genes.push(Gene {
-                        fc,
-                        factors,
-                        when,
-                        body: stmts,
+                    file.instantiations.push(Instantiation {
+                        fc: start_tok.fc.merge(end_fc),
+                        template,
+                        args,
                     });
                 }
-                TokenKind::Rule => {
+                TokenKind::Identifier("const") => {
                     let start_tok = self.next().unwrap();
                     let ec = CTX
-                        .start(start_tok.fc, "rule item")
-                        .while_parsing("a rule item");
-
-                    let (_, reactants) = self.grouped_separated(
-                        (TokenKind::ParenOpen, TokenKind::ParenClose),
-                        ec.while_parsing("a rule reactant list").expected("`(`"),
-                        TokenKind::Comma,
-                        ec.while_parsing("a rule reactant list")
-                            .expected("`,` or `)`"),
-                        |s| s.parse_binding(ec),
-                    )?;
-
-                    self.expect(
-                        ec.while_parsing("a rule reaction description")
-                            .expected("`->`"),
-                        |t| t.kind == TokenKind::ArrowR,
-                    )?;
+                        .start(start_tok.fc, "constant item")
+                        .while_parsing("a constant item");
 
-                    let (product_fc, products) = self.parse_product_list(ec)?;
+                    let (name, ty, value) = self.parse_value_definition(ec)?;
 
-                    let (when, end_fc) = match self.peek() {
-                        Some(Token {
-                            kind: TokenKind::When,
-                            ..
-                        }) => {
-                            let next = self.next().unwrap();
-                            let wec = ec
-                                .while_parsing("a when clause")
-                                .start(next.fc, "when clause");
-
-                            let expr = self.parse_expression(wec)?;
+                    file.consts.push(Const {
+                        fc: start_tok.fc.merge(value.fc()),
+                        name,
+                        ty,
+                        value,
+                    });
+                }
+                TokenKind::Identifier("param") => {
+                    let start_tok = self.next().unwrap();
+                    let ec = CTX
+                        .start(start_tok.fc, "parameter item")
+                        .while_parsing("a parameter item");
 
-                            let fc = expr.fc();
+                    let (name, ty, default) = self.parse_value_definition(ec)?;
 
-                            (Some(expr), fc)
-                        }
-                        _ => (None, product_fc),
-                    };
-
-                    let fc = start_tok.fc.merge(end_fc);
-                    file.rules.push(Rule {
-                        fc,
-                        reactants,
-                        products,
-                        when,
+                    file.params.push(Param {
+                        fc: start_tok.fc.merge(default.fc()),
+                        name,
+                        ty,
+                        default,
                     });
                 }
                 _ => {
                     return Err(Error::UnexpectedToken(
                         t.fc,
-                        CTX.while_parsing("a top level item")
-                            .expected("`record`, `gene`, `rule` or `extern`"),
+                        CTX.while_parsing("a top level item").expected(
+                            "`record`, `singleton`, `enum`, `gene`, `once`, `rule`, `extern`, \
+                                 `const`, `param`, `template` or `instantiate`",
+                        ),
                     ))
                 }
             }
@@ -266,6 +284,24 @@ impl<'src, I: Iterator<Item = Token<'src>>> Parser<'src, I> {
         Ok(file)
     }
 
+    /// Parses the `name: type = expr` part of `const` and `param` items.
+    fn parse_value_definition(
+        &mut self,
+        ec: ErrorContext,
+    ) -> Result<(Identifier, Type, Expression)> {
+        let name = self.parse_identifier(ec)?;
+        let (colon_fc, _) =
+            self.expect_tok_and_fc(ec.expected("`:`"), |t| t.kind == TokenKind::Colon)?;
+        let ty = self.parse_type(ec.start(colon_fc, "beginning of type"))?;
+        let (eq_fc, _) =
+            self.expect_tok_and_fc(ec.expected("`=`"), |t| t.kind == TokenKind::OpEquals)?;
+        let value = self.parse_expression(
+            CTX.start(eq_fc, "beginning of expression")
+                .while_parsing("an expression"),
+        )?;
+        Ok((name, ty, value))
+    }
+
     fn parse_gene_statement(&mut self, pec: ErrorContext) -> Result<GeneStatement> {
         let file = self.file;
         let next = self
@@ -277,26 +313,8 @@ impl<'src, I: Iterator<Item = Token<'src>>> Parser<'src, I> {
                 let call_tok = self.next().unwrap();
                 let ec = CTX.start(call_tok.fc, "call statement");
                 let name = self.parse_identifier(ec.while_parsing("a call statement"))?;
-                let (end_fc, arguments) = self.grouped_separated(
-                    (TokenKind::ParenOpen, TokenKind::ParenClose),
-                    ec.while_parsing("a call statement parameter list")
-                        .expected("`(`"),
-                    TokenKind::Comma,
-                    ec.while_parsing("a call statement parameter list")
-                        .expected("`,` or `)`"),
-                    |s| {
-                        let name = s.parse_identifier(ec.while_parsing("a named argument"))?;
-                        let (colon_fc, _) = s.expect_tok_and_fc(
-                            ec.while_parsing("a named argument").expected("`:`"),
-                            |t| matches!(t.kind, TokenKind::Colon),
-                        )?;
-                        let val = s.parse_expression(
-                            CTX.start(colon_fc, "beginning of expression")
-                                .while_parsing("an expression"),
-                        )?;
-                        Ok((name, val))
-                    },
-                )?;
+                let (end_fc, arguments) =
+                    self.parse_call_arguments(ec, "a call statement parameter list")?;
                 let fc = call_tok.fc.merge(end_fc);
                 Ok(GeneStatement::Call {
                     fc,
@@ -306,20 +324,661 @@ impl<'src, I: Iterator<Item = Token<'src>>> Parser<'src, I> {
             }
             TokenKind::Express => {
                 let expr_tok = self.next().unwrap();
-                let prod = self.parse_product(
-                    CTX.start(expr_tok.fc, "express statement")
-                        .while_parsing("an express statement"),
+                let ec = CTX
+                    .start(expr_tok.fc, "express statement")
+                    .while_parsing("an express statement");
+
+                let stmt = if self.peek_keyword("after") {
+                    let _ = self.next();
+                    let delay = self
+                        .parse_expression(ec.while_parsing("the delay of an express statement"))?;
+                    let product = self.parse_product(ec)?;
+                    GeneStatement::ExpressAfter {
+                        fc: expr_tok.fc.merge(product.fc),
+                        delay,
+                        product,
+                    }
+                } else {
+                    let prod = self.parse_product(ec)?;
+                    GeneStatement::Express(expr_tok.fc, prod)
+                };
+
+                if self.peek_keyword("with") {
+                    let _ = self.next();
+                    let pec = ec.while_parsing("the probability of an express statement");
+                    self.expect(pec.expected("`probability`"), |t| {
+                        t.kind == TokenKind::Identifier("probability")
+                    })?;
+                    let probability = self.parse_expression(pec)?;
+                    Ok(GeneStatement::WithProbability {
+                        fc: expr_tok.fc.merge(probability.fc()),
+                        probability,
+                        statement: Box::new(stmt),
+                    })
+                } else {
+                    Ok(stmt)
+                }
+            }
+            TokenKind::Identifier("let") => {
+                let let_tok = self.next().unwrap();
+                let (name, value) = self.parse_let_binding(
+                    CTX.start(let_tok.fc, "let statement")
+                        .while_parsing("a let statement"),
                 )?;
-                Ok(GeneStatement::Express(expr_tok.fc, prod))
+                Ok(GeneStatement::Let {
+                    fc: let_tok.fc.merge(value.fc()),
+                    name,
+                    value,
+                })
+            }
+            TokenKind::Identifier("if") => {
+                let if_tok = self.next().unwrap();
+                let ec = CTX.start(if_tok.fc, "if statement");
+                let cond = self.parse_expression(ec.while_parsing("an if condition"))?;
+                let (then_fc, then_body) =
+                    self.parse_gene_statement_block(ec.while_parsing("an if body"))?;
+
+                let mut fc = if_tok.fc.merge(then_fc);
+
+                let else_body = if self.peek_keyword("else") {
+                    let else_tok = self.next().unwrap();
+                    let ec = CTX.start(else_tok.fc, "else branch");
+
+                    if self.peek_keyword("if") {
+                        let stmt = self.parse_gene_statement(ec)?;
+                        fc = fc.merge(stmt.fc());
+                        vec![stmt]
+                    } else {
+                        let (else_fc, body) =
+                            self.parse_gene_statement_block(ec.while_parsing("an else body"))?;
+                        fc = fc.merge(else_fc);
+                        body
+                    }
+                } else {
+                    vec![]
+                };
+
+                Ok(GeneStatement::If {
+                    fc,
+                    cond,
+                    then_body,
+                    else_body,
+                })
+            }
+            TokenKind::Identifier("repeat") => {
+                let repeat_tok = self.next().unwrap();
+                let ec = CTX.start(repeat_tok.fc, "repeat statement");
+                let count = self.parse_expression(ec.while_parsing("a repeat count"))?;
+                let (body_fc, body) =
+                    self.parse_gene_statement_block(ec.while_parsing("a repeat body"))?;
+
+                Ok(GeneStatement::Repeat {
+                    fc: repeat_tok.fc.merge(body_fc),
+                    count,
+                    body,
+                })
             }
             _ => Err(Error::UnexpectedToken(
                 next.fc,
                 pec.while_parsing("a gene statement")
-                    .expected("`call` or `express`"),
+                    .expected("`call`, `express`, `let`, `if` or `repeat`"),
             )),
         }
     }
 
+    /// Parses `_` or `Enum::Variant(field: name, ...)`
+    fn parse_pattern(&mut self, ec: ErrorContext) -> Result<Pattern> {
+        let name = self.parse_identifier(ec)?;
+
+        if name.1 == "_" {
+            return Ok(Pattern::Wildcard(name.0));
+        }
+
+        self.expect(ec.expected("`::`"), |t| t.kind == TokenKind::ColonColon)?;
+        let variant = self.parse_identifier(ec)?;
+
+        let (end_fc, binds) = if self.peek_kind(|k| *k == TokenKind::ParenOpen) {
+            self.grouped_separated(
+                (TokenKind::ParenOpen, TokenKind::ParenClose),
+                ec.while_parsing("the fields of a pattern").expected("`(`"),
+                TokenKind::Comma,
+                ec.while_parsing("the fields of a pattern")
+                    .expected("`,` or `)`"),
+                |s| {
+                    let field = s.parse_identifier(ec.while_parsing("a pattern field"))?;
+                    s.expect(ec.while_parsing("a pattern field").expected("`:`"), |t| {
+                        t.kind == TokenKind::Colon
+                    })?;
+                    let var = s.parse_identifier(ec.while_parsing("a pattern field"))?;
+                    Ok((field, var))
+                },
+            )?
+        } else {
+            (variant.fc(), vec![])
+        };
+
+        Ok(Pattern::Variant {
+            fc: name.fc().merge(end_fc),
+            enum_name: name,
+            variant,
+            binds,
+        })
+    }
+
+    /// Parses `name = expr`
+    fn parse_let_binding(&mut self, ec: ErrorContext) -> Result<(Identifier, Expression)> {
+        let name = self.parse_identifier(ec)?;
+        let (eq_fc, _) =
+            self.expect_tok_and_fc(ec.expected("`=`"), |t| t.kind == TokenKind::OpEquals)?;
+        let value = self.parse_expression(
+            CTX.start(eq_fc, "beginning of expression")
+                .while_parsing("an expression"),
+        )?;
+        Ok((name, value))
+    }
+
+    fn parse_gene_statement_block(&mut self, ec: ErrorContext) -> Result<(FC, Vec<GeneStatement>)> {
+        self.grouped(
+            (TokenKind::BraceOpen, TokenKind::BraceClose),
+            ec.expected("`{`"),
+            |s| s.parse_gene_statement(ec),
+        )
+    }
+
+    /// Parses `(name: type, ...)`
+    fn parse_field_list(
+        &mut self,
+        ec: ErrorContext,
+        list_desc: &'static str,
+        field_desc: &'static str,
+    ) -> Result<(FC, Vec<(Identifier, Type)>)> {
+        self.grouped_separated(
+            (TokenKind::ParenOpen, TokenKind::ParenClose),
+            ec.while_parsing(list_desc).expected("`(`"),
+            TokenKind::Comma,
+            ec.while_parsing(list_desc).expected("`,` or `)`"),
+            |s| s.parse_field(ec, field_desc),
+        )
+    }
+
+    fn parse_field(
+        &mut self,
+        ec: ErrorContext,
+        field_desc: &'static str,
+    ) -> Result<(Identifier, Type)> {
+        let ident = self.parse_identifier(ec.while_parsing(field_desc))?;
+        let (colon_fc, _) = self
+            .expect_tok_and_fc(ec.while_parsing(field_desc).expected("`:`"), |t| {
+                matches!(t.kind, TokenKind::Colon)
+            })?;
+        let ty = self.parse_type(ec.start(colon_fc, "beginning of type"))?;
+        Ok((ident, ty))
+    }
+
+    fn parse_call_arguments(
+        &mut self,
+        ec: ErrorContext,
+        list_desc: &'static str,
+    ) -> Result<(FC, Vec<(Identifier, Expression)>)> {
+        self.grouped_separated(
+            (TokenKind::ParenOpen, TokenKind::ParenClose),
+            ec.while_parsing(list_desc).expected("`(`"),
+            TokenKind::Comma,
+            ec.while_parsing(list_desc).expected("`,` or `)`"),
+            |s| {
+                let name = s.parse_identifier(ec.while_parsing("a named argument"))?;
+                let (colon_fc, _) = s.expect_tok_and_fc(
+                    ec.while_parsing("a named argument").expected("`:`"),
+                    |t| matches!(t.kind, TokenKind::Colon),
+                )?;
+                let val = s.parse_expression(
+                    CTX.start(colon_fc, "beginning of expression")
+                        .while_parsing("an expression"),
+                )?;
+                Ok((name, val))
+            },
+        )
+    }
+
+    /// Parses the fields of a record construction, optionally starting with a
+    /// `..base` whose value provides the omitted fields.
+    fn parse_record_arguments(
+        &mut self,
+        ec: ErrorContext,
+        list_desc: &'static str,
+        field_desc: &'static str,
+    ) -> Result<RecordArguments> {
+        enum Argument {
+            Base(FC, Expression),
+            Field(Identifier, Expression),
+        }
+
+        let (fc, args) = self.grouped_separated(
+            (TokenKind::ParenOpen, TokenKind::ParenClose),
+            ec.while_parsing(list_desc).expected("`(`"),
+            TokenKind::Comma,
+            ec.while_parsing(list_desc).expected("`,` or `)`"),
+            |s| {
+                if s.peek_kind(|k| *k == TokenKind::DotDot) {
+                    let dots = s.next().unwrap();
+                    let base = s.parse_expression(
+                        CTX.start(dots.fc, "record base")
+                            .while_parsing("an expression"),
+                    )?;
+                    return Ok(Argument::Base(dots.fc, base));
+                }
+
+                let name = s.parse_identifier(ec.while_parsing(field_desc))?;
+                let (colon_fc, _) = s
+                    .expect_tok_and_fc(ec.while_parsing(field_desc).expected("`:`"), |t| {
+                        t.kind == TokenKind::Colon
+                    })?;
+                let expr = s.parse_expression(
+                    CTX.start(colon_fc, "beginning of expression")
+                        .while_parsing("an expression"),
+                )?;
+                Ok(Argument::Field(name, expr))
+            },
+        )?;
+
+        let mut base = None;
+        let mut fields = Vec::with_capacity(args.len());
+        for (i, arg) in args.into_iter().enumerate() {
+            match arg {
+                Argument::Base(dots_fc, expr) => {
+                    if i != 0 {
+                        return Err(Error::UnexpectedToken(
+                            dots_fc,
+                            ec.while_parsing(list_desc).expected(field_desc),
+                        ));
+                    }
+                    base = Some(expr);
+                }
+                Argument::Field(name, expr) => fields.push((name, expr)),
+            }
+        }
+
+        Ok((fc, base, fields))
+    }
+
+    /// A record item, `singleton` is the position of the modifier in
+    /// `singleton record`.
+    fn parse_record(&mut self, singleton: Option<FC>) -> Result<Record> {
+        let start_tok = self.next().unwrap();
+        let t = &start_tok;
+        let ec = CTX
+            .start(start_tok.fc, "record definition")
+            .while_parsing("a record definition");
+
+        let name = self.parse_identifier(ec)?;
+
+        let (fc, fields) = if self.peek_kind(|t| t == &TokenKind::ParenOpen) {
+            let ec = ec.start(t.fc, "field list");
+            self.grouped_separated(
+                (TokenKind::ParenOpen, TokenKind::ParenClose),
+                ec.while_parsing("the field list of a record item")
+                    .expected("`(`"),
+                TokenKind::Comma,
+                ec.while_parsing("the field list of a record item")
+                    .expected("`,` or `)`"),
+                |s| {
+                    let field = s.parse_field(ec, "a record field")?;
+                    let default = if s.peek_kind(|k| *k == TokenKind::OpEquals) {
+                        let eq_tok = s.next().unwrap();
+                        Some(
+                            s.parse_expression(
+                                CTX.start(eq_tok.fc, "beginning of expression")
+                                    .while_parsing("an expression"),
+                            )?,
+                        )
+                    } else {
+                        None
+                    };
+                    Ok((field, default))
+                },
+            )?
+        } else {
+            (name.fc(), vec![])
+        };
+        let (fields, defaults) = fields.into_iter().unzip();
+
+        let (fc, family) = if self.peek_keyword("is") {
+            let is_tok = self.next().unwrap();
+            let family = self.parse_identifier(
+                ec.start(is_tok.fc, "`is`")
+                    .while_parsing("the family of a record item"),
+            )?;
+            (fc.merge(family.fc()), Some(family))
+        } else {
+            (fc, None)
+        };
+
+        Ok(Record {
+            fc: singleton.unwrap_or(start_tok.fc).merge(fc),
+            singleton,
+            name,
+            fields,
+            defaults,
+            family,
+        })
+    }
+
+    /// A rule item, starting with the `rule` keyword.
+    fn parse_rule(&mut self) -> Result<Rule> {
+        let start_tok = self.next().unwrap();
+        let ec = CTX
+            .start(start_tok.fc, "rule item")
+            .while_parsing("a rule item");
+
+        let (_, reactants) = self.grouped_separated(
+            (TokenKind::ParenOpen, TokenKind::ParenClose),
+            ec.while_parsing("a rule reactant list").expected("`(`"),
+            TokenKind::Comma,
+            ec.while_parsing("a rule reactant list")
+                .expected("`,` or `)`"),
+            |s| s.parse_reactant(ec),
+        )?;
+
+        let reversible = self.expect(
+            ec.while_parsing("a rule reaction description")
+                .expected("`->` or `<->`"),
+            |t| match t.kind {
+                TokenKind::ArrowR => Some(None),
+                TokenKind::ArrowLR => Some(Some(t.fc)),
+                _ => None,
+            },
+        )?;
+
+        let (product_fc, products) = self.parse_product_list(ec)?;
+
+        let scheduling = self.parse_scheduling(ec)?;
+
+        let (when, end_fc) = match self.peek() {
+            Some(Token {
+                kind: TokenKind::When,
+                ..
+            }) => {
+                let next = self.next().unwrap();
+                let wec = ec
+                    .while_parsing("a when clause")
+                    .start(next.fc, "when clause");
+
+                let expr = self.parse_expression(wec)?;
+
+                let fc = expr.fc();
+
+                (Some(expr), fc)
+            }
+            _ => (None, product_fc),
+        };
+
+        let (reverse_when, end_fc) = if reversible.is_some() && self.peek_keyword("reverse") {
+            let next = self.next().unwrap();
+            let wec = ec
+                .while_parsing("a reverse when clause")
+                .start(next.fc, "reverse when clause");
+
+            self.expect(wec.expected("`when`"), |t| t.kind == TokenKind::When)?;
+
+            let expr = self.parse_expression(wec)?;
+
+            let fc = expr.fc();
+
+            (Some(expr), fc)
+        } else {
+            (None, end_fc)
+        };
+
+        let (lets, end_fc) = if self.peek_keyword("where") {
+            let next = self.next().unwrap();
+            let wec = ec
+                .while_parsing("a where clause")
+                .start(next.fc, "where clause");
+
+            let mut lets = vec![self.parse_let_binding(wec)?];
+            while self.peek_kind(|k| *k == TokenKind::Comma) {
+                let _ = self.next();
+                lets.push(self.parse_let_binding(wec)?);
+            }
+
+            let fc = lets.last().unwrap().1.fc();
+            (lets, fc)
+        } else {
+            (vec![], end_fc)
+        };
+
+        let fc = start_tok.fc.merge(end_fc);
+        Ok(Rule {
+            fc,
+            reactants,
+            reversible,
+            scheduling,
+            products,
+            when,
+            reverse_when,
+            lets,
+        })
+    }
+
+    /// A template item, which contains rules and genes that use the type
+    /// parameters like record names.
+    fn parse_template(&mut self) -> Result<Template> {
+        let start_tok = self.next().unwrap();
+        let ec = CTX
+            .start(start_tok.fc, "template item")
+            .while_parsing("a template item");
+
+        let name = self.parse_identifier(ec)?;
+        let (_, params) = self.grouped_separated(
+            (TokenKind::OpLessThan, TokenKind::OpGreaterThan),
+            ec.while_parsing("the type parameters of a template")
+                .expected("`<`"),
+            TokenKind::Comma,
+            ec.while_parsing("the type parameters of a template")
+                .expected("`,` or `>`"),
+            |s| s.parse_identifier(ec.while_parsing("a type parameter")),
+        )?;
+
+        self.expect(ec.expected("`{`"), |t| t.kind == TokenKind::BraceOpen)?;
+
+        let mut rules = vec![];
+        let mut genes = vec![];
+        let end_fc = loop {
+            let file = self.file;
+            let t = self.peek().ok_or_else(|| {
+                Error::UnexpectedEnd(file, ec.while_parsing("the body of a template"))
+            })?;
+            match t.kind {
+                TokenKind::BraceClose => break self.next().unwrap().fc,
+                TokenKind::Rule => rules.push(self.parse_rule()?),
+                TokenKind::Gene => genes.push(self.parse_gene(None)?),
+                TokenKind::Identifier("once") => {
+                    let modifier = self.next().unwrap();
+                    self.expect_item_after_modifier(modifier.fc, "`gene`", TokenKind::Gene)?;
+                    genes.push(self.parse_gene(Some(modifier.fc))?);
+                }
+                _ => {
+                    return Err(Error::UnexpectedToken(
+                        t.fc,
+                        ec.while_parsing("the body of a template")
+                            .expected("`rule`, `gene` or `}`"),
+                    ))
+                }
+            }
+        };
+
+        Ok(Template {
+            fc: start_tok.fc.merge(end_fc),
+            name,
+            params,
+            rules,
+            genes,
+        })
+    }
+
+    /// A gene item, `once` is the position of the modifier in `once gene`.
+    fn parse_gene(&mut self, once: Option<FC>) -> Result<Gene> {
+        let start_tok = self.next().unwrap();
+        let ec = CTX
+            .start(start_tok.fc, "gene item")
+            .while_parsing("a gene item");
+
+        let on = if self.peek_keyword("on") {
+            Some(self.next().unwrap().fc)
+        } else {
+            None
+        };
+
+        let (_, factors) = self.grouped_separated(
+            (TokenKind::ParenOpen, TokenKind::ParenClose),
+            ec.while_parsing("a gene factor list").expected("`(`"),
+            TokenKind::Comma,
+            ec.while_parsing("a gene factor list")
+                .expected("`,` or `)`"),
+            |s| {
+                // only rules have catalysts, genes never consume their factors
+                let binding = s.parse_reactant(ec)?;
+                match binding.catalyst {
+                    Some(fc) => Err(Error::UnexpectedToken(
+                        fc,
+                        ec.while_parsing("a record binding")
+                            .expected("a quantity or identifier"),
+                    )),
+                    None => Ok(binding),
+                }
+            },
+        )?;
+
+        let cooldown = if self.peek_keyword("cooldown") {
+            let _ = self.next();
+            let (fc, n) = self.expect_tok_and_fc(
+                ec.while_parsing("a cooldown")
+                    .expected("a number of iterations"),
+                |t| match t.kind {
+                    TokenKind::IntegerLiteral(n) => Some(n),
+                    _ => None,
+                },
+            )?;
+            Some((fc, n))
+        } else {
+            None
+        };
+
+        let scheduling = self.parse_scheduling(ec)?;
+
+        let next = {
+            let file = self.file;
+            self.peek()
+                .ok_or_else(|| Error::UnexpectedEnd(file, ec.while_parsing("gene item")))?
+        };
+
+        let when = match &next.kind {
+            TokenKind::When => {
+                let next = self.next().unwrap();
+                let wec = ec
+                    .while_parsing("a when clause")
+                    .start(next.fc, "when clause");
+
+                let expr = self.parse_expression(wec)?;
+
+                Some(expr)
+            }
+            _ => None,
+        };
+
+        let (end_fc, stmts) = self.grouped(
+            (TokenKind::BraceOpen, TokenKind::BraceClose),
+            ec.while_parsing("a gene statement list").expected("`{`"),
+            |s| s.parse_gene_statement(ec),
+        )?;
+
+        let fc = once.unwrap_or(start_tok.fc).merge(end_fc);
+
+        Ok(Gene {
+            fc,
+            once,
+            on,
+            factors,
+            cooldown,
+            scheduling,
+            when,
+            body: stmts,
+        })
+    }
+
+    /// The optional `priority n` and `weight n` annotations of a gene or rule,
+    /// in any order but each at most once.
+    fn parse_scheduling(&mut self, ec: ErrorContext) -> Result<Scheduling> {
+        let mut scheduling = Scheduling::default();
+
+        loop {
+            if self.peek_keyword("priority") {
+                let tok = self.next().unwrap();
+                if scheduling.priority.is_some() {
+                    return Err(Error::UnexpectedToken(
+                        tok.fc,
+                        ec.while_parsing("a priority")
+                            .expected("a single `priority` annotation"),
+                    ));
+                }
+
+                let pec = ec.while_parsing("a priority").expected("a whole number");
+                let minus = if self.peek_kind(|k| *k == TokenKind::OpMinus) {
+                    Some(self.next().unwrap().fc)
+                } else {
+                    None
+                };
+                let (fc, n) = self.expect_tok_and_fc(pec, |t| match t.kind {
+                    TokenKind::IntegerLiteral(n) => isize::try_from(n).ok(),
+                    _ => None,
+                })?;
+                scheduling.priority = match minus {
+                    Some(minus_fc) => Some((minus_fc.merge(fc), -n)),
+                    None => Some((fc, n)),
+                };
+            } else if self.peek_keyword("weight") {
+                let tok = self.next().unwrap();
+                if scheduling.weight.is_some() {
+                    return Err(Error::UnexpectedToken(
+                        tok.fc,
+                        ec.while_parsing("a weight")
+                            .expected("a single `weight` annotation"),
+                    ));
+                }
+
+                let (fc, n) = self.expect_tok_and_fc(
+                    ec.while_parsing("a weight").expected("a positive number"),
+                    |t| match t.kind {
+                        TokenKind::IntegerLiteral(n) if n > 0 => Some(n),
+                        _ => None,
+                    },
+                )?;
+                scheduling.weight = Some((fc, n));
+            } else {
+                return Ok(scheduling);
+            }
+        }
+    }
+
+    /// Checks that a modifier like `once` is followed by the item it modifies,
+    /// without consuming the item.
+    fn expect_item_after_modifier(
+        &mut self,
+        modifier_fc: FC,
+        item_desc: &'static str,
+        item: TokenKind<'src>,
+    ) -> Result<()> {
+        let ec = CTX
+            .start(modifier_fc, "item modifier")
+            .while_parsing("an item modifier")
+            .expected(item_desc);
+        match self.peek() {
+            Some(t) if t.kind == item => Ok(()),
+            Some(t) => Err(Error::UnexpectedToken(t.fc, ec)),
+            None => Err(Error::UnexpectedEnd(self.file, ec)),
+        }
+    }
+
     fn parse_product_list(&mut self, pec: ErrorContext) -> Result<(FC, Vec<Product>)> {
         let file = self.file;
         let next = self
@@ -344,6 +1003,28 @@ impl<'src, I: Iterator<Item = Token<'src>>> Parser<'src, I> {
         )
     }
 
+    /// A binding of a rule, which can be marked as a `catalyst`.
+    fn parse_reactant(&mut self, pec: ErrorContext) -> Result<Binding> {
+        if !self.peek_keyword("catalyst") {
+            return self.parse_binding(pec);
+        }
+
+        let id = self.parse_identifier(pec)?;
+
+        // only a modifier if another binding follows, like in `catalyst Enzyme`
+        if self.peek_kind(|k| matches!(k, TokenKind::Identifier(_) | TokenKind::IntegerLiteral(_)))
+        {
+            let fc = id.fc();
+            let mut binding = self.parse_binding(pec.while_parsing("a catalyst"))?;
+            binding.fc = fc.merge(binding.fc);
+            binding.catalyst = Some(fc);
+            Ok(binding)
+        } else {
+            let ec = pec.while_parsing("a binding").start(id.fc(), "binding");
+            self.parse_binding_after_identifier(ec, id)
+        }
+    }
+
     fn parse_binding(&mut self, pec: ErrorContext) -> Result<Binding> {
         let file = self.file;
         let ec = pec.while_parsing("a binding");
@@ -365,38 +1046,15 @@ impl<'src, I: Iterator<Item = Token<'src>>> Parser<'src, I> {
 
                 Ok(Binding {
                     fc: start_fc.merge(name.fc()),
+                    catalyst: None,
                     name,
                     attr: Some(attr),
+                    fields: vec![],
                 })
             }
             TokenKind::Identifier(_) => {
                 let id = self.parse_identifier(ec)?;
-
-                if let Some(next) = self.peek() {
-                    if next.kind == TokenKind::Colon {
-                        let _ = self.next();
-
-                        let name = self.parse_identifier(ec)?;
-
-                        Ok(Binding {
-                            fc: id.fc().merge(name.fc()),
-                            name,
-                            attr: Some(BindingAttribute::Name(id)),
-                        })
-                    } else {
-                        Ok(Binding {
-                            fc: id.fc(),
-                            name: id,
-                            attr: None,
-                        })
-                    }
-                } else {
-                    Ok(Binding {
-                        fc: id.fc(),
-                        name: id,
-                        attr: None,
-                    })
-                }
+                self.parse_binding_after_identifier(ec, id)
             }
             _ => Err(Error::UnexpectedToken(
                 next.fc,
@@ -406,9 +1064,178 @@ impl<'src, I: Iterator<Item = Token<'src>>> Parser<'src, I> {
         }
     }
 
+    /// The rest of a binding that starts with a name, like `p: Person(..)` or
+    /// `Person(..)`.
+    fn parse_binding_after_identifier(
+        &mut self,
+        ec: ErrorContext,
+        id: Identifier,
+    ) -> Result<Binding> {
+        let start_fc = id.fc();
+
+        let (attr, name) = if self.peek_kind(|k| *k == TokenKind::Colon) {
+            let _ = self.next();
+
+            let name = self.parse_identifier(ec)?;
+            (Some(BindingAttribute::Name(id)), name)
+        } else {
+            (None, id)
+        };
+
+        let (end_fc, fields) = if self.peek_kind(|k| *k == TokenKind::ParenOpen) {
+            self.parse_call_arguments(ec, "the field patterns of a binding")?
+        } else {
+            (name.fc(), vec![])
+        };
+
+        Ok(Binding {
+            fc: start_fc.merge(end_fc),
+            catalyst: None,
+            name,
+            attr,
+            fields,
+        })
+    }
+
     fn parse_type(&mut self, pec: ErrorContext) -> Result<Type> {
         let id = self.parse_identifier(pec.while_parsing("a type"))?;
-        Ok(Type::Named(id))
+
+        let ty = if id.1 == "list" && self.peek_kind(|k| *k == TokenKind::OpLessThan) {
+            let (fc, elem) = self.parse_list_type(pec.while_parsing("a list type"), id.fc())?;
+            Type::List {
+                fc,
+                elem: Box::new(elem),
+            }
+        } else {
+            Type::Named(id)
+        };
+
+        if self.peek_kind(|k| *k == TokenKind::Question) {
+            let q = self.next().unwrap();
+            Ok(Type::Optional {
+                fc: ty.fc().merge(q.fc),
+                inner: Box::new(ty),
+            })
+        } else {
+            Ok(ty)
+        }
+    }
+
+    /// Parses the `<T>` of `list<T>`, after the `list` at `start_fc`, and
+    /// returns the element type
+    fn parse_list_type(&mut self, ec: ErrorContext, start_fc: FC) -> Result<(FC, Type)> {
+        self.expect(ec.expected("`<`"), |t| t.kind == TokenKind::OpLessThan)?;
+        let elem = self.parse_type(ec)?;
+        let (end_fc, _) =
+            self.expect_tok_and_fc(ec.expected("`>`"), |t| t.kind == TokenKind::OpGreaterThan)?;
+        Ok((start_fc.merge(end_fc), elem))
+    }
+
+    /// Parses `[a, b, ...]`
+    fn parse_list_elements(&mut self, pec: ErrorContext) -> Result<(FC, Vec<Expression>)> {
+        self.grouped_separated(
+            (TokenKind::BracketOpen, TokenKind::BracketClose),
+            pec.while_parsing("a list expression").expected("`[`"),
+            TokenKind::Comma,
+            pec.while_parsing("a list expression")
+                .expected("`,` or `]`"),
+            |s| {
+                let elem = s.parse_expression(pec.while_parsing("a list element"))?;
+
+                // `[X where .field > 0]` counts the instances of `X` that match
+                match elem {
+                    Expression::Variable(name) if s.peek_keyword("where") => {
+                        let where_tok = s.next().unwrap();
+                        let filter = s.parse_expression(
+                            CTX.start(where_tok.fc, "concentration filter")
+                                .while_parsing("an expression"),
+                        )?;
+                        Ok(Expression::FilteredConcentration {
+                            fc: name.fc().merge(filter.fc()),
+                            name,
+                            filter: Box::new(filter),
+                        })
+                    }
+                    elem => Ok(elem),
+                }
+            },
+        )
+    }
+
+    /// Parses `sum(Person.age)` or a call with named arguments, like the
+    /// builtin `min(a: 1, b: 2)`.
+    fn parse_aggregate_or_call(&mut self, name: Identifier) -> Result<Expression> {
+        enum Argument {
+            Field(Identifier, Identifier),
+            Named(Identifier, Expression),
+        }
+
+        let ec = CTX.start(name.fc(), "function call");
+
+        let (end_fc, mut args) = self.grouped_separated(
+            (TokenKind::ParenOpen, TokenKind::ParenClose),
+            ec.while_parsing("a function call parameter list")
+                .expected("`(`"),
+            TokenKind::Comma,
+            ec.while_parsing("a function call parameter list")
+                .expected("`,` or `)`"),
+            |s| {
+                let first = s.parse_identifier(ec.while_parsing("a named argument"))?;
+                let (sep_fc, is_field) = s.expect_tok_and_fc(
+                    ec.while_parsing("a named argument").expected("`:` or `.`"),
+                    |t| match t.kind {
+                        TokenKind::Colon => Some(false),
+                        TokenKind::Dot => Some(true),
+                        _ => None,
+                    },
+                )?;
+
+                if is_field {
+                    let field = s.parse_identifier(ec.while_parsing("an aggregate"))?;
+                    Ok(Argument::Field(first, field))
+                } else {
+                    let val = s.parse_expression(
+                        CTX.start(sep_fc, "beginning of expression")
+                            .while_parsing("an expression"),
+                    )?;
+                    Ok(Argument::Named(first, val))
+                }
+            },
+        )?;
+
+        let fc = name.fc().merge(end_fc);
+
+        if let [Argument::Field(..)] = &args[..] {
+            if let Some(Argument::Field(record, field)) = args.pop() {
+                return Ok(Expression::Aggregate {
+                    fc,
+                    func: name,
+                    record,
+                    field,
+                });
+            }
+        }
+
+        let mut arguments = Vec::with_capacity(args.len());
+        for arg in args {
+            match arg {
+                Argument::Named(name, val) => arguments.push((name, val)),
+                Argument::Field(record, _) => {
+                    return Err(Error::UnexpectedToken(
+                        record.fc(),
+                        ec.while_parsing("a function call parameter list")
+                            .expected("a named argument"),
+                    ))
+                }
+            }
+        }
+
+        Ok(Expression::Call {
+            fc,
+            name,
+            base: None,
+            arguments,
+        })
     }
 
     fn parse_identifier(&mut self, parent_error_context: ErrorContext) -> Result<Identifier> {
@@ -424,72 +1251,79 @@ impl<'src, I: Iterator<Item = Token<'src>>> Parser<'src, I> {
         Ok(Identifier(fc, id.to_string()))
     }
 
+    /// A product with an optional quantity in front of it. A quantity that
+    /// starts with a variable is only recognised when it is followed by a
+    /// field access or the name of the product, as in `f.n Widget` or
+    /// `n Widget`, otherwise `A + B` would be ambiguous. Contextual keywords
+    /// can't be the name, so that `B priority 1` is not `B` times `priority`.
     fn parse_product(&mut self, pec: ErrorContext) -> Result<Product> {
-        let quantity = if let Some(Token {
-            fc,
-            kind: TokenKind::IntegerLiteral(l),
-        }) = self.peek()
-        {
-            let fc = *fc;
-            let l = *l;
-            let _ = self.next();
-            Some((fc, l))
+        let qec = pec.while_parsing("a product quantity");
+
+        let (quantity, name) = if self.peek_kind(|k| matches!(k, TokenKind::Identifier(_))) {
+            let id = self.parse_identifier(pec.while_parsing("a product"))?;
+
+            if self.peek_kind(|k| match k {
+                TokenKind::Dot => true,
+                TokenKind::Identifier(name) => !CONTEXTUAL_KEYWORDS.contains(name),
+                _ => false,
+            }) {
+                let atom = self.parse_atom_suffixes(qec, Expression::Variable(id))?;
+                let expr = self.parse_infix_operators(qec, atom)?;
+                let name = self.parse_identifier(pec.while_parsing("a product"))?;
+                (Some(Quantity::Expression(Box::new(expr))), name)
+            } else {
+                (None, id)
+            }
         } else {
-            None
+            let quantity = match self.parse_expression(qec)? {
+                Expression::Literal(Literal::Integer(fc, n)) => Quantity::Literal(fc, n),
+                expr => Quantity::Expression(Box::new(expr)),
+            };
+            let name = self.parse_identifier(pec.while_parsing("a product"))?;
+            (Some(quantity), name)
         };
 
-        let name = self.parse_identifier(pec.while_parsing("a product"))?;
-
-        let start_fc = if let Some((fc, _)) = &quantity {
-            *fc
-        } else {
-            name.fc()
+        let start_fc = match &quantity {
+            Some(Quantity::Literal(fc, _)) => *fc,
+            Some(Quantity::Expression(expr)) => expr.fc(),
+            None => name.fc(),
         };
 
         let ec = CTX.start(start_fc, "product").while_parsing("a product");
 
-        let (fc, fields) = if self.peek_kind(|t| t == &TokenKind::ParenOpen) {
-            self.grouped_separated(
-                (TokenKind::ParenOpen, TokenKind::ParenClose),
-                ec.while_parsing("the start of product fields")
-                    .expected("`(`"),
-                TokenKind::Comma,
-                ec.while_parsing("a product field list")
-                    .expected("`,` or `)`"),
-                |s| {
-                    let name = s.parse_identifier(ec.while_parsing("a product field"))?;
-                    let (colon_fc, _) = s.expect_tok_and_fc(
-                        ec.while_parsing("a product field").expected("`:`"),
-                        |t| t.kind == TokenKind::Colon,
-                    )?;
-                    let expr = s.parse_expression(
-                        CTX.start(colon_fc, "beginning of expression")
-                            .while_parsing("an expression"),
-                    )?;
-                    Ok((name, expr))
-                },
-            )?
+        let (fc, base, fields) = if self.peek_kind(|t| t == &TokenKind::ParenOpen) {
+            self.parse_record_arguments(ec, "a product field list", "a product field")?
         } else {
-            (name.fc(), vec![])
+            (name.fc(), None, vec![])
         };
 
         Ok(Product {
             fc: start_fc.merge(fc),
             quantity,
             name,
+            base,
             fields,
         })
     }
 
     fn parse_expression(&mut self, pec: ErrorContext) -> Result<Expression> {
-        let mut expr = self.parse_expression_atom(pec)?;
+        let atom = self.parse_expression_atom(pec)?;
+        self.parse_infix_operators(pec, atom)
+    }
 
+    /// The infix operators following the first atom of an expression.
+    fn parse_infix_operators(
+        &mut self,
+        pec: ErrorContext,
+        mut expr: Expression,
+    ) -> Result<Expression> {
         while let Some(next) = self.peek() {
             let op = match next.kind {
                 TokenKind::OpPlus => (next.fc, InfixOperator::Add),
                 TokenKind::OpMinus => (next.fc, InfixOperator::Sub),
                 TokenKind::OpStar => (next.fc, InfixOperator::Mul),
                 TokenKind::OpSlash => (next.fc, InfixOperator::Div),
+                TokenKind::OpPercent => (next.fc, InfixOperator::Mod),
                 TokenKind::OpEquals => (next.fc, InfixOperator::Eq),
                 TokenKind::OpNotEquals => (next.fc, InfixOperator::Neq),
                 TokenKind::OpLessThan => (next.fc, InfixOperator::Lt),
@@ -498,6 +1332,7 @@ impl<'src, I: Iterator<Item = Token<'src>>> Parser<'src, I> {
                 TokenKind::OpGreaterThanEqual => (next.fc, InfixOperator::Gte),
                 TokenKind::OpAnd => (next.fc, InfixOperator::And),
                 TokenKind::OpOr => (next.fc, InfixOperator::Or),
+                TokenKind::OpXor | TokenKind::Identifier("xor") => (next.fc, InfixOperator::Xor),
                 _ => return Ok(expr),
             };
 
@@ -522,33 +1357,120 @@ impl<'src, I: Iterator<Item = Token<'src>>> Parser<'src, I> {
             .ok_or_else(|| Error::UnexpectedEnd(file, pec.while_parsing("an expression atom")))?;
         let start_fc = next.fc;
 
-        let mut expr = match &next.kind {
+        let expr = match &next.kind {
             TokenKind::Identifier(n) => {
-                let n = n.to_string();
+                let name = Identifier(start_fc, n.to_string());
                 let _ = self.next();
-                Expression::Variable(Identifier(start_fc, n))
+
+                if name.1 == "list" && self.peek_kind(|k| *k == TokenKind::OpLessThan) {
+                    let (ty_fc, elem_ty) =
+                        self.parse_list_type(pec.while_parsing("a list expression"), start_fc)?;
+                    let (fc, elems) = self.parse_list_elements(pec)?;
+                    Expression::List {
+                        fc: ty_fc.merge(fc),
+                        elem_ty: Some(elem_ty),
+                        elems,
+                    }
+                } else if self.peek_kind(|k| *k == TokenKind::ColonColon) {
+                    let ec = CTX.start(start_fc, "enum variant");
+                    let _ = self.next();
+                    let variant = self.parse_identifier(ec.while_parsing("an enum variant"))?;
+
+                    let (end_fc, fields) = if self.peek_kind(|k| *k == TokenKind::ParenOpen) {
+                        self.parse_call_arguments(ec, "the fields of an enum variant")?
+                    } else {
+                        (variant.fc(), vec![])
+                    };
+
+                    Expression::Variant {
+                        fc: start_fc.merge(end_fc),
+                        enum_name: name,
+                        variant,
+                        fields,
+                    }
+                } else if AGGREGATES.contains(&name.1.as_str())
+                    && self.peek_kind(|k| *k == TokenKind::ParenOpen)
+                {
+                    self.parse_aggregate_or_call(name)?
+                } else if self.peek_kind(|k| *k == TokenKind::ParenOpen) {
+                    let ec = CTX.start(start_fc, "function call");
+                    let (end_fc, base, arguments) = self.parse_record_arguments(
+                        ec,
+                        "a function call parameter list",
+                        "a named argument",
+                    )?;
+                    Expression::Call {
+                        fc: start_fc.merge(end_fc),
+                        name,
+                        base: base.map(Box::new),
+                        arguments,
+                    }
+                } else {
+                    Expression::Variable(name)
+                }
             }
             TokenKind::IntegerLiteral(i) => {
                 let i = *i;
                 let _ = self.next();
                 Expression::Literal(Literal::Integer(start_fc, i))
             }
+            TokenKind::FloatLiteral(f) => {
+                let f = *f;
+                let _ = self.next();
+                Expression::Literal(Literal::Float(start_fc, f))
+            }
             TokenKind::StringLiteral(s) => {
                 let s = s.clone();
                 let _ = self.next();
                 Expression::Literal(Literal::String(start_fc, s))
             }
-            TokenKind::BracketOpen => {
+            TokenKind::BoolLiteral(b) => {
+                let b = *b;
                 let _ = self.next();
-                let name = self.parse_identifier(
-                    pec.while_parsing("a type inside a concentration expression"),
-                )?;
-                self.expect(
-                    pec.while_parsing("a concentration expression")
-                        .expected("`]`"),
-                    |t| t.kind == TokenKind::BracketClose,
+                Expression::Literal(Literal::Bool(start_fc, b))
+            }
+            TokenKind::NoneLiteral => {
+                let _ = self.next();
+                Expression::Literal(Literal::None(start_fc))
+            }
+            TokenKind::BracketOpen => {
+                let (fc, mut elems) = self.parse_list_elements(pec)?;
+
+                // `[X]` is the concentration of `X`, not a list
+                if let [Expression::Variable(name)] = &elems[..] {
+                    Expression::Concentration(name.clone())
+                } else if let [Expression::FilteredConcentration { .. }] = &elems[..] {
+                    let mut conc = elems.pop().unwrap();
+                    if let Expression::FilteredConcentration { fc: conc_fc, .. } = &mut conc {
+                        *conc_fc = fc;
+                    }
+                    conc
+                } else {
+                    Expression::List {
+                        fc,
+                        elem_ty: None,
+                        elems,
+                    }
+                }
+            }
+            TokenKind::Dot => {
+                let dot = self.next().unwrap();
+                let field_name = self.parse_identifier(
+                    CTX.start(dot.fc, "instance field")
+                        .while_parsing("a field of a filtered instance"),
                 )?;
-                Expression::Concentration(name)
+                Expression::InstanceField {
+                    fc: dot.fc.merge(field_name.fc()),
+                    field_name,
+                }
+            }
+            TokenKind::Iteration => {
+                let _ = self.next();
+                Expression::Iteration(start_fc)
+            }
+            TokenKind::Time => {
+                let _ = self.next();
+                Expression::Time(start_fc)
             }
             TokenKind::ParenOpen => {
                 let _ = self.next();
@@ -567,6 +1489,43 @@ impl<'src, I: Iterator<Item = Token<'src>>> Parser<'src, I> {
                     expr: Box::new(rhs),
                 }
             }
+            TokenKind::Match => {
+                let match_tok = self.next().unwrap();
+                let ec = CTX.start(match_tok.fc, "match expression");
+
+                let expr = self.parse_expression(ec.while_parsing("a match expression"))?;
+
+                let (end_fc, arms) = self.grouped_separated(
+                    (TokenKind::BraceOpen, TokenKind::BraceClose),
+                    ec.while_parsing("the arms of a match expression")
+                        .expected("`{`"),
+                    TokenKind::Comma,
+                    ec.while_parsing("the arms of a match expression")
+                        .expected("`,` or `}`"),
+                    |s| {
+                        let pattern = s.parse_pattern(ec.while_parsing("a match arm"))?;
+                        s.expect(ec.while_parsing("a match arm").expected("`=>`"), |t| {
+                            t.kind == TokenKind::FatArrowR
+                        })?;
+                        let body = s.parse_expression(ec.while_parsing("a match arm"))?;
+                        Ok(MatchArm { pattern, body })
+                    },
+                )?;
+
+                Expression::Match {
+                    fc: match_tok.fc.merge(end_fc),
+                    expr: Box::new(expr),
+                    arms,
+                }
+            }
+            TokenKind::OpNot => {
+                let t = self.next().unwrap();
+                let rhs = self.parse_expression_atom(pec)?;
+                Expression::PrefixOp {
+                    op: (t.fc, PrefixOperator::Not),
+                    expr: Box::new(rhs),
+                }
+            }
             _ => {
                 return Err(Error::UnexpectedToken(
                     start_fc,
@@ -575,6 +1534,15 @@ impl<'src, I: Iterator<Item = Token<'src>>> Parser<'src, I> {
             }
         };
 
+        self.parse_atom_suffixes(pec, expr)
+    }
+
+    /// Field accesses and `is` tests following an atom.
+    fn parse_atom_suffixes(
+        &mut self,
+        pec: ErrorContext,
+        mut expr: Expression,
+    ) -> Result<Expression> {
         while let Some(next) = self.peek() {
             if next.kind == TokenKind::Dot {
                 let _ = self.next();
@@ -585,6 +1553,24 @@ impl<'src, I: Iterator<Item = Token<'src>>> Parser<'src, I> {
                     base: Box::new(expr),
                     field_name: name,
                 };
+            } else if next.kind == TokenKind::Identifier("is") {
+                let _ = self.next();
+
+                let (end_fc, is_some) = self.expect_tok_and_fc(
+                    pec.while_parsing("an `is` expression")
+                        .expected("`some` or `none`"),
+                    |t| match t.kind {
+                        TokenKind::Identifier("some") => Some(true),
+                        TokenKind::NoneLiteral => Some(false),
+                        _ => None,
+                    },
+                )?;
+
+                expr = Expression::OptionTest {
+                    fc: expr.fc().merge(end_fc),
+                    expr: Box::new(expr),
+                    is_some,
+                };
             } else {
                 break;
             }
@@ -608,6 +1594,12 @@ impl<'src, I: Iterator<Item = Token<'src>>> Parser<'src, I> {
         }
     }
 
+    /// Checks for one of the [`CONTEXTUAL_KEYWORDS`].
+    fn peek_keyword(&mut self, keyword: &str) -> bool {
+        debug_assert!(CONTEXTUAL_KEYWORDS.contains(&keyword));
+        self.peek_kind(|k| *k == TokenKind::Identifier(keyword))
+    }
+
     fn next(&mut self) -> Option<Token<'src>> {
         self.toks.next()
     }
@@ -1,11 +1,16 @@
-use ahash::AHashMap as HashMap;
+use std::cmp::Reverse;
 
-use rand::prelude::*;
+use ahash::{AHashMap as HashMap, AHashSet as HashSet};
+
+use rand::{distributions::WeightedIndex, prelude::*};
 
 use cytosol_hir as hir;
 use hir::{
-    ast::{InfixOperator, PrefixOperator},
-    types::{Bind, ExpressionId, Gene, GeneId, Product, RecordId, Rule, RuleId},
+    ast::{Identifier, InfixOperator, PrefixOperator},
+    types::{
+        Aggregate, Bind, Builtin, ExpressionId, FieldIndex, FieldPattern, GeneId, GeneStatementId,
+        ParamId, Pattern, Product, Quantity, RecordId, Rule, RuleId, TypeId,
+    },
     Program,
 };
 
@@ -15,9 +20,28 @@ use crate::value::*;
 
 type ExtFunc = Box<dyn for<'a> FnMut(&'a [Value])>;
 
-#[derive(Default)]
 pub struct ProgramContext {
     exts: HashMap<String, ExtFunc>,
+    params: HashMap<String, Value>,
+    /// Source of every random choice of a run, like the execution order or
+    /// `random` calls
+    rng: StdRng,
+    /// The value of `@iteration`
+    iteration: usize,
+    /// The value of `@time`
+    time: f64,
+}
+
+impl Default for ProgramContext {
+    fn default() -> Self {
+        Self {
+            exts: HashMap::default(),
+            params: HashMap::default(),
+            rng: StdRng::from_entropy(),
+            iteration: 0,
+            time: 0.0,
+        }
+    }
 }
 
 impl ProgramContext {
@@ -43,59 +67,278 @@ impl ProgramContext {
             f.call_mut(Args::from_value_slice(args))
         })
     }
+
+    /// Overrides the value of a `param` item. The value has to fit the type
+    /// the parameter is declared with.
+    pub fn set_param(&mut self, prog: &Program, name: &str, val: Value) -> Result<(), ParamError> {
+        let id = prog.param_by_name(name).ok_or(ParamError::Unknown)?;
+        if !value_has_type(prog, &val, prog[id].ty) {
+            return Err(ParamError::TypeMismatch);
+        }
+
+        self.params.insert(name.to_string(), val);
+        Ok(())
+    }
+
+    /// Removes an override so the parameter uses its default value again.
+    pub fn reset_param(&mut self, name: &str) {
+        self.params.remove(name);
+    }
+
+    pub fn param(&self, name: &str) -> Option<&Value> {
+        self.params.get(name)
+    }
+
+    /// Restarts the random number generator, so that runs with the same seed
+    /// make the same random choices.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+
+    /// The number of iterations that ran before the current one.
+    pub fn iteration(&self) -> usize {
+        self.iteration
+    }
+
+    /// Counts the current iteration as finished.
+    pub fn advance_iteration(&mut self) {
+        self.iteration += 1;
+    }
+
+    pub fn time(&self) -> f64 {
+        self.time
+    }
+
+    /// Sets the simulated time that `@time` evaluates to.
+    pub fn set_time(&mut self, time: f64) {
+        self.time = time;
+    }
+
+    fn eval_param(&mut self, prog: &Program, id: ParamId) -> Value {
+        let param = &prog[id];
+        match self.params.get(&param.name.1).cloned() {
+            Some(val) => val,
+            None => eval_expr(
+                prog,
+                self,
+                &CellEnvSummary::default(),
+                &RuntimeVars::default(),
+                param.default,
+            )
+            .unwrap(),
+        }
+    }
+}
+
+/// The reasons why a parameter can't be set with [`ProgramContext::set_param`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParamError {
+    /// The program has no `param` item with the name
+    Unknown,
+    /// The value does not fit the type of the parameter
+    TypeMismatch,
+}
+
+impl std::fmt::Display for ParamError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParamError::Unknown => write!(f, "unknown parameter"),
+            ParamError::TypeMismatch => write!(f, "value does not fit the type of the parameter"),
+        }
+    }
 }
 
+impl std::error::Error for ParamError {}
+
+/// Checks if a value from the host application fits a type of the program.
+pub fn value_has_type(prog: &Program, val: &Value, ty: TypeId) -> bool {
+    use hir::types::Type;
+
+    let fields_have_types = |fields: &[Value], types: &[TypeId]| {
+        fields.len() == types.len()
+            && fields
+                .iter()
+                .zip(types)
+                .all(|(val, ty)| value_has_type(prog, val, *ty))
+    };
+
+    match (&prog[ty], val) {
+        (Type::Bool, Value::Bool(_))
+        | (Type::Int, Value::Integer(_))
+        | (Type::Float, Value::Float(_))
+        | (Type::String, Value::String(_))
+        | (Type::Optional(_) | Type::None, Value::None) => true,
+        (Type::Optional(inner), val) => value_has_type(prog, val, *inner),
+        (Type::List(elem), Value::List(vals)) => {
+            vals.iter().all(|val| value_has_type(prog, val, *elem))
+        }
+        (Type::Record(id), Value::Record(fields)) => fields_have_types(fields, &prog[*id].fields),
+        (Type::Enum(id), Value::Variant(idx, fields)) => prog[*id]
+            .variants
+            .get(*idx)
+            .is_some_and(|variant| fields_have_types(fields, &variant.fields)),
+        _ => false,
+    }
+}
+
+/// Checks if the field values from the host application fit the fields of a
+/// record.
+pub fn fields_have_types(prog: &Program, record_id: RecordId, fields: &[Value]) -> bool {
+    let types = &prog[record_id].fields;
+    fields.len() == types.len()
+        && fields
+            .iter()
+            .zip(types)
+            .all(|(val, ty)| value_has_type(prog, val, *ty))
+}
+
+/// Runs a gene if its `when` clause is satisfied. Returns whether the body ran.
 pub fn run_gene(
     ctx: &mut ProgramContext,
     prog: &Program,
     env: &mut CellEnv,
     vars: &mut RuntimeVars,
     gene_id: GeneId,
-) {
+) -> bool {
     vars.clear();
 
     let gene = &prog[gene_id];
 
+    if gene.on_arrival {
+        let (bind, rec) = &gene.binds[0];
+        let mut ran = false;
+        for fields in env.new_instances(prog, gene_id, bind, *rec) {
+            vars.clear();
+            bind_instance(bind, fields.clone(), vars);
+            // an instance that arrived while the `when` clause was not
+            // satisfied stays new until the body runs for it
+            if run_gene_body(ctx, prog, env, vars, gene_id) {
+                env.mark_instance_seen(gene_id, fields);
+                ran = true;
+            }
+        }
+        return ran;
+    }
+
     for (bind, rec) in &gene.binds {
-        env.apply_non_moving_bind(bind, *rec, vars);
+        env.apply_non_moving_bind(prog, bind, *rec, vars, &mut ctx.rng);
     }
 
+    run_gene_body(ctx, prog, env, vars, gene_id)
+}
+
+/// Runs the body of a gene whose factors are already bound, if its `when`
+/// clause is satisfied.
+fn run_gene_body(
+    ctx: &mut ProgramContext,
+    prog: &Program,
+    env: &mut CellEnv,
+    vars: &mut RuntimeVars,
+    gene_id: GeneId,
+) -> bool {
+    let gene = &prog[gene_id];
+
     if let Some(expr) = gene.when {
-        match eval_expr(prog, env, vars, expr).unwrap() {
+        match eval_expr(prog, ctx, env, vars, expr).unwrap() {
             Value::Bool(true) => {}
             Value::Bool(false) => {
                 // the `when` clause is not satisfied, don't run
-                return;
+                return false;
             }
             _ => unreachable!("when expressions must evaluate to bools"),
         }
     }
 
-    for stmt_id in &gene.body {
+    if gene.once && !env.fired_genes.insert(gene_id) {
+        return false;
+    }
+
+    run_gene_statements(ctx, prog, env, vars, &gene.body);
+    true
+}
+
+fn run_gene_statements(
+    ctx: &mut ProgramContext,
+    prog: &Program,
+    env: &mut CellEnv,
+    vars: &mut RuntimeVars,
+    stmts: &[GeneStatementId],
+) {
+    for stmt_id in stmts {
         let stmt = &prog[*stmt_id];
         match stmt {
             hir::types::GeneStatement::Call { ext, arguments } => {
                 let ext = &prog[*ext];
-                let ext_fn = ctx
-                    .exts
-                    .get_mut(&ext.name.1)
-                    .unwrap_or_else(|| panic!("Unbound extern function {}", ext.name.1));
 
                 let fn_args = arguments
                     .iter()
-                    .map(|id| eval_expr(prog, env, vars, *id).unwrap())
+                    .map(|id| eval_expr(prog, ctx, env, vars, *id).unwrap())
                     .collect::<Vec<_>>();
 
+                let ext_fn = ctx
+                    .exts
+                    .get_mut(&ext.name.1)
+                    .unwrap_or_else(|| panic!("Unbound extern function {}", ext.name.1));
+
                 (ext_fn)(&fn_args[..]);
             }
             hir::types::GeneStatement::Express(prod) => {
-                eval_product(prog, env, vars, prod);
+                eval_product(prog, ctx, env, vars, prod);
+            }
+            hir::types::GeneStatement::ExpressAfter { delay, product } => {
+                let delay = match eval_expr(prog, ctx, env, vars, *delay).unwrap() {
+                    Value::Integer(n) => usize::try_from(n).unwrap_or_else(|_| {
+                        panic!(
+                            "Delay {} of record {} is out of range",
+                            n, prog[product.record].name.1
+                        )
+                    }),
+                    _ => unreachable!("delays must evaluate to ints"),
+                };
+
+                let (quantity, fields) = eval_product_instances(prog, ctx, env, vars, product);
+
+                if delay == 0 {
+                    env.express_record(prog, quantity, product.record, fields);
+                } else {
+                    env.pending.push(PendingRecord {
+                        delay,
+                        quantity,
+                        record: product.record,
+                        fields,
+                    });
+                }
+            }
+            hir::types::GeneStatement::Let { name, value } => {
+                let val = eval_expr(prog, ctx, env, vars, *value).unwrap();
+                vars.insert(name.1.clone(), val);
+            }
+            hir::types::GeneStatement::If {
+                cond,
+                then_body,
+                else_body,
+            } => match eval_expr(prog, ctx, env, vars, *cond).unwrap() {
+                Value::Bool(true) => run_gene_statements(ctx, prog, env, vars, then_body),
+                Value::Bool(false) => run_gene_statements(ctx, prog, env, vars, else_body),
+                _ => unreachable!("if conditions must evaluate to bools"),
+            },
+            hir::types::GeneStatement::Repeat { count, body } => {
+                match eval_expr(prog, ctx, env, vars, *count).unwrap() {
+                    Value::Integer(n) => {
+                        // negative counts don't run the body at all
+                        for _ in 0..n.max(0) {
+                            run_gene_statements(ctx, prog, env, vars, body);
+                        }
+                    }
+                    _ => unreachable!("repeat counts must evaluate to ints"),
+                }
             }
         }
     }
 }
 
 pub fn run_rules(
+    ctx: &mut ProgramContext,
     prog: &Program,
     env: &mut CellEnv,
     vars: &mut RuntimeVars,
@@ -104,13 +347,14 @@ pub fn run_rules(
     let mut ran_any = false;
     for (id, n) in rules {
         let rule = &prog[id];
-        run_rule(prog, env, vars, n, rule);
+        run_rule(ctx, prog, env, vars, n, rule);
         ran_any = true;
     }
     ran_any
 }
 
 fn run_rule(
+    ctx: &mut ProgramContext,
     prog: &Program,
     env: &mut CellEnv,
     vars: &mut RuntimeVars,
@@ -120,28 +364,105 @@ fn run_rule(
     for _ in 0..quantity {
         vars.clear();
 
-        for (bind, rec) in &rule.binds {
-            env.apply_moving_bind(bind, *rec, vars);
+        // another rule or an earlier bind of this one might have taken the
+        // instances a bind needs
+        let mut taken = vec![];
+        for (bind, rec) in patterns_first(&rule.binds) {
+            if !env.can_bind(prog, bind, *rec) {
+                env.put_back(taken);
+                return;
+            }
+            taken.extend(env.apply_moving_bind(prog, bind, *rec, vars, &mut ctx.rng));
+        }
+
+        // a catalyst can't be one of the instances the rule just consumed
+        if !rule
+            .catalysts
+            .iter()
+            .all(|(bind, rec)| env.can_bind(prog, bind, *rec))
+        {
+            env.put_back(taken);
+            return;
+        }
+
+        // catalysts are bound like gene factors, they stay in the environment
+        for (bind, rec) in &rule.catalysts {
+            env.apply_non_moving_bind(prog, bind, *rec, vars, &mut ctx.rng);
+        }
+
+        for (name, value) in &rule.lets {
+            let val = eval_expr(prog, ctx, env, vars, *value).unwrap();
+            vars.insert(name.1.clone(), val);
         }
 
         for prod in &rule.products {
-            eval_product(prog, env, vars, prod);
+            eval_product(prog, ctx, env, vars, prod);
         }
     }
 }
 
-fn eval_product(prog: &Program, env: &mut CellEnv, vars: &RuntimeVars, prod: &Product) {
-    let fields = prod
-        .arguments
-        .iter()
-        .map(|id| eval_expr(prog, env, vars, *id).unwrap())
-        .collect::<Vec<_>>();
+/// The binds with field patterns first, the ones with the most field values
+/// before the others, so that other binds can't take the only matching
+/// instances.
+fn patterns_first(binds: &[(Bind, RecordId)]) -> Vec<&(Bind, RecordId)> {
+    let mut binds = binds.iter().collect::<Vec<_>>();
+    binds.sort_by_key(|(bind, _)| {
+        Reverse(match bind {
+            Bind::Pattern { fields, .. } => Some(
+                fields
+                    .iter()
+                    .filter(|(_, pattern)| matches!(pattern, FieldPattern::Value(_)))
+                    .count(),
+            ),
+            _ => None,
+        })
+    });
+    binds
+}
 
-    env.add_record(prod.quantity, prod.record, fields);
+fn eval_product(
+    prog: &Program,
+    ctx: &mut ProgramContext,
+    env: &mut CellEnv,
+    vars: &RuntimeVars,
+    prod: &Product,
+) {
+    let (quantity, fields) = eval_product_instances(prog, ctx, env, vars, prod);
+    env.express_record(prog, quantity, prod.record, fields);
+}
+
+/// The quantity and field values of a product.
+fn eval_product_instances(
+    prog: &Program,
+    ctx: &mut ProgramContext,
+    env: &CellEnv,
+    vars: &RuntimeVars,
+    prod: &Product,
+) -> (usize, RecordFields) {
+    let fields = match eval_expr(prog, ctx, env, vars, prod.value).unwrap() {
+        Value::Record(fields) => fields,
+        _ => unreachable!("products must evaluate to records"),
+    };
+
+    let quantity = match prod.quantity {
+        Quantity::Literal(n) => n,
+        Quantity::Expression(id) => match eval_expr(prog, ctx, env, vars, id).unwrap() {
+            Value::Integer(n) => usize::try_from(n).unwrap_or_else(|_| {
+                panic!(
+                    "Quantity {} of record {} is out of range",
+                    n, prog[prod.record].name.1
+                )
+            }),
+            _ => unreachable!("quantities must evaluate to ints"),
+        },
+    };
+
+    (quantity, fields)
 }
 
 fn eval_expr(
     prog: &Program,
+    ctx: &mut ProgramContext,
     env: &impl RecordContainer,
     vars: &RuntimeVars,
     id: ExpressionId,
@@ -149,11 +470,14 @@ fn eval_expr(
     let expr = &prog[id];
     match expr {
         hir::types::Expression::BoolLiteral(b) => Some(Value::Bool(*b)),
-        hir::types::Expression::IntegerLiteral(i) => Some(Value::Integer(*i as isize)),
+        hir::types::Expression::IntegerLiteral(i) => Some(Value::Integer(*i)),
+        hir::types::Expression::FloatLiteral(f) => Some(Value::Float(*f)),
+        hir::types::Expression::NoneLiteral => Some(Value::None),
         hir::types::Expression::StringLiteral(s) => Some(Value::String(s.clone())),
         hir::types::Expression::Variable(v) => vars.lookup(&v.1),
+        hir::types::Expression::Param(id) => Some(ctx.eval_param(prog, *id)),
         hir::types::Expression::FieldAccess { base, field } => {
-            let val = eval_expr(prog, env, vars, *base)?;
+            let val = eval_expr(prog, ctx, env, vars, *base)?;
             if let Value::Record(mut fields) = val {
                 Some(fields.remove(*field))
             } else {
@@ -162,9 +486,11 @@ fn eval_expr(
         }
         hir::types::Expression::PrefixOp { op, expr } => {
             //
-            let expr_val = eval_expr(prog, env, vars, *expr)?;
+            let expr_val = eval_expr(prog, ctx, env, vars, *expr)?;
             match (op, expr_val) {
                 (PrefixOperator::Neg, Value::Integer(i)) => Some(Value::Integer(-i)),
+                (PrefixOperator::Neg, Value::Float(f)) => Some(Value::Float(-f)),
+                (PrefixOperator::Not, Value::Bool(b)) => Some(Value::Bool(!b)),
                 _ => None,
             }
         }
@@ -172,8 +498,8 @@ fn eval_expr(
             op,
             args: [lhs, rhs],
         } => {
-            let lhs_val = eval_expr(prog, env, vars, *lhs)?;
-            let rhs_val = eval_expr(prog, env, vars, *rhs)?;
+            let lhs_val = eval_expr(prog, ctx, env, vars, *lhs)?;
+            let rhs_val = eval_expr(prog, ctx, env, vars, *rhs)?;
 
             use InfixOperator::*;
             use Value::*;
@@ -183,43 +509,301 @@ fn eval_expr(
                 (Add, String(a), String(b)) => Some(String(a + &b)),
                 (Sub, Integer(a), Integer(b)) => Some(Integer(a - b)),
                 (Mul, Integer(a), Integer(b)) => Some(Integer(a * b)),
-                (Div, Integer(a), Integer(b)) => Some(Integer(a / b)),
+                (Div, Integer(a), Integer(b)) => Some(Integer(
+                    a.checked_div(b)
+                        .unwrap_or_else(|| integer_division_error("/", a, b)),
+                )),
+                (Mod, Integer(a), Integer(b)) => Some(Integer(
+                    a.checked_rem(b)
+                        .unwrap_or_else(|| integer_division_error("%", a, b)),
+                )),
+
+                (Add, Float(a), Float(b)) => Some(Float(a + b)),
+                (Sub, Float(a), Float(b)) => Some(Float(a - b)),
+                (Mul, Float(a), Float(b)) => Some(Float(a * b)),
+                (Div, Float(a), Float(b)) => Some(Float(a / b)),
+                (Mod, Float(a), Float(b)) => Some(Float(a % b)),
 
                 (Eq, Bool(a), Bool(b)) => Some(Bool(a == b)),
                 (Eq, Integer(a), Integer(b)) => Some(Bool(a == b)),
+                (Eq, Float(a), Float(b)) => Some(Bool(a == b)),
                 (Eq, String(a), String(b)) => Some(Bool(a == b)),
 
                 (Neq, Bool(a), Bool(b)) => Some(Bool(a != b)),
                 (Neq, Integer(a), Integer(b)) => Some(Bool(a != b)),
+                (Neq, Float(a), Float(b)) => Some(Bool(a != b)),
                 (Neq, String(a), String(b)) => Some(Bool(a != b)),
 
+                (Eq, a @ Variant(..), b @ Variant(..)) => Some(Bool(a == b)),
+                (Neq, a @ Variant(..), b @ Variant(..)) => Some(Bool(a != b)),
+
                 (Lt, Integer(a), Integer(b)) => Some(Bool(a < b)),
                 (Lte, Integer(a), Integer(b)) => Some(Bool(a <= b)),
                 (Gt, Integer(a), Integer(b)) => Some(Bool(a > b)),
                 (Gte, Integer(a), Integer(b)) => Some(Bool(a >= b)),
 
+                (Lt, Float(a), Float(b)) => Some(Bool(a < b)),
+                (Lte, Float(a), Float(b)) => Some(Bool(a <= b)),
+                (Gt, Float(a), Float(b)) => Some(Bool(a > b)),
+                (Gte, Float(a), Float(b)) => Some(Bool(a >= b)),
+
                 (And, Bool(a), Bool(b)) => Some(Bool(a && b)),
                 (Or, Bool(a), Bool(b)) => Some(Bool(a || b)),
+                (Xor, Bool(a), Bool(b)) => Some(Bool(a ^ b)),
+                (Xor, Integer(a), Integer(b)) => Some(Integer(a ^ b)),
 
-                _ => None,
+                _ => Option::None,
             }
         }
 
         hir::types::Expression::Concentration(id) => {
-            let count = env.count_records(*id);
+            let count = prog
+                .family_members(*id)
+                .map(|member| env.count_records(member))
+                .sum::<usize>();
             Some(Value::Integer(count as isize))
         }
+        hir::types::Expression::FilteredConcentration { record, filter } => {
+            let mut vars = vars.clone();
+            let mut count = 0;
+            for member in prog.family_members(*record) {
+                for fields in env.instances(member) {
+                    let fields = project_instance(prog, *record, member, fields.clone());
+                    vars.instance = Some(Value::Record(fields));
+                    if let Value::Bool(true) = eval_expr(prog, ctx, env, &vars, *filter)? {
+                        count += 1;
+                    }
+                }
+            }
+            Some(Value::Integer(count))
+        }
+        hir::types::Expression::Instance => vars.instance.clone(),
+        hir::types::Expression::Iteration => Some(Value::Integer(ctx.iteration as isize)),
+        hir::types::Expression::Time => Some(Value::Float(ctx.time)),
+        hir::types::Expression::Aggregate {
+            func,
+            record,
+            field,
+        } => {
+            let vals = prog.family_members(*record).flat_map(|member| {
+                let idx = prog.family_field(*record, member, *field);
+                env.instances(member).iter().map(move |fields| &fields[idx])
+            });
+            let is_float = prog[prog[*record].fields[*field]] == hir::types::Type::Float;
+            Some(eval_aggregate(*func, is_float, vals))
+        }
+        hir::types::Expression::List(elems) => {
+            let elems = elems
+                .iter()
+                .map(|id| eval_expr(prog, ctx, env, vars, *id))
+                .collect::<Option<Vec<_>>>()?;
+            Some(Value::List(elems))
+        }
+        hir::types::Expression::RecordUpdate { base, overrides } => {
+            let mut fields = match eval_expr(prog, ctx, env, vars, *base)? {
+                Value::Record(fields) => fields,
+                _ => return None,
+            };
+            for (idx, id) in overrides {
+                fields[*idx] = eval_expr(prog, ctx, env, vars, *id)?;
+            }
+            Some(Value::Record(fields))
+        }
+        hir::types::Expression::Record { record: _, fields } => {
+            let fields = fields
+                .iter()
+                .map(|id| eval_expr(prog, ctx, env, vars, *id))
+                .collect::<Option<Vec<_>>>()?;
+            Some(Value::Record(fields))
+        }
+        hir::types::Expression::OptionTest { expr, is_some } => {
+            let val = eval_expr(prog, ctx, env, vars, *expr)?;
+            Some(Value::Bool((val != Value::None) == *is_some))
+        }
+        hir::types::Expression::Variant { variant, fields } => {
+            let fields = fields
+                .iter()
+                .map(|id| eval_expr(prog, ctx, env, vars, *id))
+                .collect::<Option<Vec<_>>>()?;
+            Some(Value::Variant(*variant, fields))
+        }
+        hir::types::Expression::Match { expr, arms } => {
+            let (variant, fields) = match eval_expr(prog, ctx, env, vars, *expr)? {
+                Value::Variant(variant, fields) => (variant, fields),
+                _ => return None,
+            };
+
+            for arm in arms {
+                match &arm.pattern {
+                    Pattern::Wildcard => return eval_expr(prog, ctx, env, vars, arm.body),
+                    Pattern::Variant { variant: v, binds } if *v == variant => {
+                        let mut arm_vars = vars.clone();
+                        for (field, name) in binds {
+                            arm_vars.insert(name.1.clone(), fields[*field].clone());
+                        }
+                        return eval_expr(prog, ctx, env, &arm_vars, arm.body);
+                    }
+                    Pattern::Variant { .. } => {}
+                }
+            }
+
+            unreachable!("match expressions must be exhaustive")
+        }
+        hir::types::Expression::BuiltinCall { func, arguments } => {
+            let args = arguments
+                .iter()
+                .map(|id| eval_expr(prog, ctx, env, vars, *id))
+                .collect::<Option<Vec<_>>>()?;
+            eval_builtin(*func, args, &mut ctx.rng)
+        }
+    }
+}
+
+/// Integer divisions fail for a zero divisor and for `int::MIN / -1`.
+fn integer_division_error(op: &str, lhs: isize, rhs: isize) -> ! {
+    if rhs == 0 {
+        panic!("Division by zero in {} {} {}", lhs, op, rhs)
+    } else {
+        panic!("Result of {} {} {} is out of range", lhs, op, rhs)
     }
 }
 
-#[derive(Default)]
+fn eval_builtin(func: Builtin, args: Vec<Value>, rng: &mut impl Rng) -> Option<Value> {
+    use Value::*;
+
+    let val = match (func, &args[..]) {
+        (Builtin::ToString, [Bool(b)]) => String(b.to_string()),
+        (Builtin::ToString, [Integer(i)]) => String(i.to_string()),
+        // `Debug` keeps the `.0` on whole numbers
+        (Builtin::ToString, [Float(f)]) => String(format!("{:?}", f)),
+        (Builtin::ToString, [String(s)]) => String(s.clone()),
+
+        // rounds towards zero, saturating at the bounds of `int`
+        (Builtin::ToInt, [Float(f)]) => Integer(*f as isize),
+        (Builtin::ToFloat, [Integer(i)]) => Float(*i as f64),
+
+        (Builtin::Len, [String(s)]) => Integer(s.chars().count() as isize),
+        (Builtin::Len, [List(l)]) => Integer(l.len() as isize),
+
+        (Builtin::Abs, [Integer(i)]) => Integer(
+            i.checked_abs()
+                .unwrap_or_else(|| panic!("Result of abs({}) is out of range", i)),
+        ),
+        (Builtin::Min, [Integer(a), Integer(b)]) => Integer(*a.min(b)),
+        (Builtin::Max, [Integer(a), Integer(b)]) => Integer(*a.max(b)),
+        // unlike `Ord::clamp` this does not panic when `min > max`
+        (Builtin::Clamp, [Integer(v), Integer(min), Integer(max)]) => Integer(*v.max(min).min(max)),
+
+        (Builtin::Abs, [Float(f)]) => Float(f.abs()),
+        (Builtin::Min, [Float(a), Float(b)]) => Float(a.min(*b)),
+        (Builtin::Max, [Float(a), Float(b)]) => Float(a.max(*b)),
+        (Builtin::Clamp, [Float(v), Float(min), Float(max)]) => Float(v.max(*min).min(*max)),
+
+        // indices are counted in characters and clamped to the string
+        (Builtin::Substring, [String(s), Integer(start), Integer(end)]) => {
+            let len = s.chars().count();
+            let start = (*start).clamp(0, len as isize) as usize;
+            let end = (*end).clamp(0, len as isize) as usize;
+            String(
+                s.chars()
+                    .skip(start)
+                    .take(end.saturating_sub(start))
+                    .collect(),
+            )
+        }
+        (Builtin::Contains, [String(s), String(part)]) => Bool(s.contains(part.as_str())),
+        (Builtin::StartsWith, [String(s), String(prefix)]) => Bool(s.starts_with(prefix.as_str())),
+
+        (Builtin::Contains, [List(l), elem]) => Bool(l.contains(elem)),
+        // out of bounds accesses are an error
+        (Builtin::Get, [List(l), Integer(idx)]) => usize::try_from(*idx)
+            .ok()
+            .and_then(|i| l.get(i))
+            .unwrap_or_else(|| {
+                panic!(
+                    "Index {} of a list with {} elements is out of range",
+                    idx,
+                    l.len()
+                )
+            })
+            .clone(),
+        (Builtin::Push, [List(l), elem]) => {
+            let mut l = l.clone();
+            l.push(elem.clone());
+            List(l)
+        }
+        (Builtin::Concat, [List(a), List(b)]) => List(a.iter().chain(b).cloned().collect()),
+
+        // both bounds are included, an empty range is an error
+        // the bounds can be given in either order
+        (Builtin::Random, [Integer(lo), Integer(hi)]) => {
+            Integer(rng.gen_range(*lo.min(hi)..=*lo.max(hi)))
+        }
+        // `NaN` bounds and bounds too far apart to pick from, like infinities,
+        // give `NaN`
+        (Builtin::Random, [Float(lo), Float(hi)]) if !(hi - lo).is_finite() => Float(f64::NAN),
+        (Builtin::Random, [Float(lo), Float(hi)]) => {
+            Float(rng.gen_range(lo.min(*hi)..=lo.max(*hi)))
+        }
+        // probabilities outside of `0.0..=1.0` never or always succeed
+        (Builtin::Chance, [Float(p)]) => Bool(rng.gen::<f64>() < *p),
+
+        _ => return Option::None,
+    };
+
+    Some(val)
+}
+
+/// Aggregates the values of a field, the result for no instances is `0`.
+fn eval_aggregate<'a>(
+    func: Aggregate,
+    is_float: bool,
+    vals: impl Iterator<Item = &'a Value>,
+) -> Value {
+    if is_float {
+        let nums = vals
+            .map(|val| match val {
+                Value::Float(f) => *f,
+                _ => unreachable!("aggregated fields must have the same type"),
+            })
+            .collect::<Vec<_>>();
+        let sum = nums.iter().sum::<f64>();
+        match func {
+            Aggregate::Sum => Value::Float(sum),
+            Aggregate::Min => Value::Float(nums.into_iter().reduce(f64::min).unwrap_or(0.0)),
+            Aggregate::Max => Value::Float(nums.into_iter().reduce(f64::max).unwrap_or(0.0)),
+            Aggregate::Avg if nums.is_empty() => Value::Float(0.0),
+            Aggregate::Avg => Value::Float(sum / nums.len() as f64),
+        }
+    } else {
+        let nums = vals
+            .map(|val| match val {
+                Value::Integer(i) => *i,
+                _ => unreachable!("aggregated fields must have the same type"),
+            })
+            .collect::<Vec<_>>();
+        let sum = nums.iter().sum::<isize>();
+        match func {
+            Aggregate::Sum => Value::Integer(sum),
+            Aggregate::Min => Value::Integer(nums.into_iter().min().unwrap_or(0)),
+            Aggregate::Max => Value::Integer(nums.into_iter().max().unwrap_or(0)),
+            Aggregate::Avg if nums.is_empty() => Value::Float(0.0),
+            Aggregate::Avg => Value::Float(sum as f64 / nums.len() as f64),
+        }
+    }
+}
+
+#[derive(Default, Clone)]
 pub struct RuntimeVars {
     vals: HashMap<String, Value>,
+    /// The instance that is checked by a concentration filter
+    instance: Option<Value>,
 }
 
 impl RuntimeVars {
     pub fn clear(&mut self) {
         self.vals.clear();
+        self.instance = None;
     }
 
     pub fn lookup(&self, name: &str) -> Option<Value> {
@@ -233,70 +817,461 @@ impl RuntimeVars {
 
 pub trait RecordContainer {
     fn count_records(&self, record_id: RecordId) -> usize;
+
+    /// The field values of the instances, only needed by concentration
+    /// filters and aggregates.
+    fn instances(&self, record_id: RecordId) -> &[RecordFields];
 }
 
-#[derive(Default, Debug)]
+/// The records of an environment and the state that has to be kept with them,
+/// so that a copy of the environment continues where the original was.
+#[derive(Default, Debug, Clone)]
 pub struct CellEnv {
     pub records: HashMap<RecordId, Vec<RecordFields>>,
+    /// The `once` genes that already ran
+    pub fired_genes: HashSet<GeneId>,
+    /// Records expressed with `express after` that didn't appear yet
+    pub pending: Vec<PendingRecord>,
+    /// How many instances with the same field values each `gene on` has
+    /// already run for
+    pub seen_instances: HashMap<GeneId, HashMap<RecordFields, usize>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct PendingRecord {
+    /// The number of iterations until the record appears
+    pub delay: usize,
+    pub quantity: usize,
+    pub record: RecordId,
+    pub fields: RecordFields,
 }
 
 impl CellEnv {
-    pub fn summary(&self, sum: &mut CellEnvSummary) {
+    pub fn summary(&self, prog: &Program, sum: &mut CellEnvSummary) {
         sum.clear();
+        sum.fired_genes.clone_from(&self.fired_genes);
         for (id, v) in &self.records {
             sum.records.insert(*id, v.len());
         }
+
+        // only records bound with field patterns or inspected by filters and
+        // aggregates need their instances checked
+        let inspected = prog
+            .records
+            .iter()
+            .filter(|(_, record)| record.inspected)
+            .map(|(id, _)| id);
+        for id in inspected {
+            for member in prog.family_members(id) {
+                sum.instances
+                    .entry(member)
+                    .or_insert_with(|| self.records.get(&member).cloned().unwrap_or_default());
+            }
+        }
     }
 
     pub fn add_record(&mut self, quantity: usize, record_id: RecordId, fields: RecordFields) {
         let recs = self.records.entry(record_id).or_default();
 
-        recs.extend(std::iter::repeat(fields).take(quantity));
+        recs.extend(std::iter::repeat_n(fields, quantity));
+    }
+
+    /// Adds records like the `express` statement, which respects singletons.
+    pub fn express_record(
+        &mut self,
+        prog: &Program,
+        quantity: usize,
+        record_id: RecordId,
+        fields: RecordFields,
+    ) {
+        // expressing a singleton that is already present does nothing
+        let quantity = if prog[record_id].singleton {
+            quantity.min(usize::from(self.count_records(record_id) == 0))
+        } else {
+            quantity
+        };
+
+        self.add_record(quantity, record_id, fields);
     }
 
-    pub fn apply_moving_bind(&mut self, bind: &Bind, record: RecordId, vars: &mut RuntimeVars) {
-        let mut rng = rand::thread_rng();
+    /// Counts down the delays of the records expressed with `express after`
+    /// and adds the ones that are due. Returns `false` if nothing was pending.
+    pub fn advance_pending(&mut self, prog: &Program) -> bool {
+        if self.pending.is_empty() {
+            return false;
+        }
+
+        for pending in &mut self.pending {
+            pending.delay = pending.delay.saturating_sub(1);
+        }
+
+        let (due, pending) = std::mem::take(&mut self.pending)
+            .into_iter()
+            .partition(|pending| pending.delay == 0);
+        self.pending = pending;
+
+        for PendingRecord {
+            quantity,
+            record,
+            fields,
+            ..
+        } in due
+        {
+            self.express_record(prog, quantity, record, fields);
+        }
+
+        true
+    }
 
-        let recs = self.records.get_mut(&record).unwrap();
+    /// The instances matching `bind` that the gene did not run for yet.
+    /// Instances with the same field values can't be told apart, so only their
+    /// number matters.
+    pub fn new_instances(
+        &self,
+        prog: &Program,
+        gene_id: GeneId,
+        bind: &Bind,
+        record_id: RecordId,
+    ) -> Vec<RecordFields> {
+        let mut current = vec![];
+        for member in prog.family_members(record_id) {
+            let values = match bind {
+                Bind::Pattern { fields, .. } => pattern_values(prog, record_id, member, fields),
+                _ => vec![],
+            };
+            current.extend(
+                self.instances(member)
+                    .iter()
+                    .filter(|rec| fields_match(rec, &values))
+                    .map(|rec| project_instance(prog, record_id, member, rec.clone())),
+            );
+        }
+
+        let mut seen = self
+            .seen_instances
+            .get(&gene_id)
+            .cloned()
+            .unwrap_or_default();
+        current
+            .into_iter()
+            .filter(|rec| match seen.get_mut(rec) {
+                Some(n) if *n > 0 => {
+                    *n -= 1;
+                    false
+                }
+                _ => true,
+            })
+            .collect()
+    }
+
+    /// Remembers that a `gene on` ran for an instance.
+    pub fn mark_instance_seen(&mut self, gene_id: GeneId, fields: RecordFields) {
+        *self
+            .seen_instances
+            .entry(gene_id)
+            .or_default()
+            .entry(fields)
+            .or_default() += 1;
+    }
+
+    /// Removes the instances that are gone from the ones seen by `gene on`s,
+    /// so that an equal instance that appears later is new again.
+    pub fn forget_removed_instances(&mut self, prog: &Program) {
+        for (gene_id, seen) in &mut self.seen_instances {
+            let (_, record_id) = prog[*gene_id].binds[0];
+            let mut present = HashMap::<RecordFields, usize>::default();
+            for member in prog.family_members(record_id) {
+                for rec in self.records.get(&member).into_iter().flatten() {
+                    let rec = project_instance(prog, record_id, member, rec.clone());
+                    *present.entry(rec).or_default() += 1;
+                }
+            }
+            seen.retain(|rec, n| {
+                *n = (*n).min(present.get(rec).copied().unwrap_or(0));
+                *n > 0
+            });
+        }
+    }
+
+    /// Checks if `bind` can take its instances from the environment.
+    pub fn can_bind(&self, prog: &Program, bind: &Bind, record_id: RecordId) -> bool {
+        let mut members = prog.family_members(record_id);
+        match bind {
+            Bind::Quantity(n) => members.map(|m| self.count_records(m)).sum::<usize>() >= *n,
+            Bind::Named(_) => members.any(|m| self.count_records(m) > 0),
+            Bind::Pattern { fields, .. } => members.any(|m| {
+                let values = pattern_values(prog, record_id, m, fields);
+                self.instances(m)
+                    .iter()
+                    .any(|rec| fields_match(rec, &values))
+            }),
+        }
+    }
+
+    /// Picks one of the instances of a record or its family members, as the
+    /// record the instance belongs to and its index.
+    fn choose_instance(
+        &self,
+        prog: &Program,
+        record_id: RecordId,
+        rng: &mut impl Rng,
+    ) -> (RecordId, usize) {
+        let total = prog
+            .family_members(record_id)
+            .map(|m| self.count_records(m))
+            .sum::<usize>();
+        let mut idx = rng.gen_range(0..total);
+        for member in prog.family_members(record_id) {
+            let count = self.count_records(member);
+            if idx < count {
+                return (member, idx);
+            }
+            idx -= count;
+        }
+        unreachable!("the index is less than the number of instances")
+    }
+
+    /// Takes the instances of `bind` out of the environment. They are returned
+    /// with the record they belong to, so that they can be put back.
+    pub fn apply_moving_bind(
+        &mut self,
+        prog: &Program,
+        bind: &Bind,
+        record: RecordId,
+        vars: &mut RuntimeVars,
+        rng: &mut impl Rng,
+    ) -> Vec<(RecordId, RecordFields)> {
         match bind {
             Bind::Quantity(n) => {
-                debug_assert!(recs.len() >= *n);
-                for _ in 0..*n {
-                    let idx = rng.gen_range(0..recs.len());
-                    let _ = recs.swap_remove(idx);
-                }
+                debug_assert!(self.can_bind(prog, bind, record));
+                (0..*n)
+                    .map(|_| {
+                        let (member, idx) = self.choose_instance(prog, record, rng);
+                        let fields = self.records.get_mut(&member).unwrap().swap_remove(idx);
+                        (member, fields)
+                    })
+                    .collect()
             }
             Bind::Named(name) => {
-                let idx = rng.gen_range(0..recs.len());
+                let (member, idx) = self.choose_instance(prog, record, rng);
 
-                let fields = recs.swap_remove(idx);
-                vars.insert(name.1.clone(), Value::Record(fields));
+                let fields = self.records.get_mut(&member).unwrap().swap_remove(idx);
+                let projected = project_instance(prog, record, member, fields.clone());
+                vars.insert(name.1.clone(), Value::Record(projected));
+                vec![(member, fields)]
             }
+            Bind::Pattern { name, fields } => {
+                let mut matching = vec![];
+                for member in prog.family_members(record) {
+                    let values = pattern_values(prog, record, member, fields);
+                    let recs = self.instances(member);
+                    matching.extend(
+                        (0..recs.len())
+                            .filter(|idx| fields_match(&recs[*idx], &values))
+                            .map(|idx| (member, idx)),
+                    );
+                }
+                let (member, idx) = *matching.choose(rng).unwrap();
+
+                let rec = self.records.get_mut(&member).unwrap().swap_remove(idx);
+                let projected = project_instance(prog, record, member, rec.clone());
+                bind_pattern(name.as_ref(), fields, projected, vars);
+                vec![(member, rec)]
+            }
+        }
+    }
+
+    /// Puts back the instances taken by moving binds of a rule that can't run
+    /// after all.
+    fn put_back(&mut self, taken: Vec<(RecordId, RecordFields)>) {
+        for (member, fields) in taken {
+            self.records.entry(member).or_default().push(fields);
         }
     }
 
     pub fn apply_non_moving_bind(
         &mut self,
+        prog: &Program,
         bind: &Bind,
         record_id: RecordId,
         vars: &mut RuntimeVars,
+        rng: &mut impl Rng,
     ) {
-        let mut rng = rand::thread_rng();
-
-        let empty_vec = vec![];
-        let recs = self.records.get(&record_id).unwrap_or(&empty_vec);
         match bind {
-            Bind::Quantity(n) => {
-                debug_assert!(recs.len() >= *n);
+            Bind::Quantity(_) => {
+                debug_assert!(self.can_bind(prog, bind, record_id));
             }
             Bind::Named(name) => {
-                let idx = rng.gen_range(0..recs.len());
+                let (member, idx) = self.choose_instance(prog, record_id, rng);
 
-                let fields = recs[idx].clone();
+                let fields = self.instances(member)[idx].clone();
+                let fields = project_instance(prog, record_id, member, fields);
                 vars.insert(name.1.clone(), Value::Record(fields));
             }
+            Bind::Pattern { name, fields } => {
+                let (member, rec) = prog
+                    .family_members(record_id)
+                    .flat_map(|member| {
+                        let values = pattern_values(prog, record_id, member, fields);
+                        self.instances(member)
+                            .iter()
+                            .filter(move |rec| fields_match(rec, &values))
+                            .map(move |rec| (member, rec))
+                    })
+                    .choose(rng)
+                    .unwrap();
+
+                let rec = project_instance(prog, record_id, member, rec.clone());
+                bind_pattern(name.as_ref(), fields, rec, vars);
+            }
+        }
+    }
+}
+
+/// The fields of an instance of `member` in the order of the fields of its
+/// `family`, instances of the family itself stay as they are.
+fn project_instance(
+    prog: &Program,
+    family: RecordId,
+    member: RecordId,
+    fields: RecordFields,
+) -> RecordFields {
+    if family == member {
+        return fields;
+    }
+
+    (0..prog[family].fields.len())
+        .map(|idx| fields[prog.family_field(family, member, idx)].clone())
+        .collect()
+}
+
+/// The values that the fields of an instance of `member` need to have to match
+/// a pattern on its `family`.
+fn pattern_values(
+    prog: &Program,
+    family: RecordId,
+    member: RecordId,
+    patterns: &[(FieldIndex, FieldPattern)],
+) -> Vec<(FieldIndex, Value)> {
+    patterns
+        .iter()
+        .filter_map(|(idx, pattern)| match pattern {
+            FieldPattern::Bind(_) => None,
+            FieldPattern::Value(lit) => {
+                Some((prog.family_field(family, member, *idx), Value::from(lit)))
+            }
+        })
+        .collect()
+}
+
+fn fields_match(fields: &RecordFields, values: &[(FieldIndex, Value)]) -> bool {
+    values.iter().all(|(idx, val)| fields[*idx] == *val)
+}
+
+/// Binds a single instance like a bind that doesn't move it would.
+fn bind_instance(bind: &Bind, fields: RecordFields, vars: &mut RuntimeVars) {
+    match bind {
+        Bind::Quantity(_) => {}
+        Bind::Named(name) => vars.insert(name.1.clone(), Value::Record(fields)),
+        Bind::Pattern {
+            name,
+            fields: patterns,
+        } => bind_pattern(name.as_ref(), patterns, fields, vars),
+    }
+}
+
+/// Binds the fields that were named in the pattern, and the whole instance if the
+/// bind has a name.
+fn bind_pattern(
+    name: Option<&Identifier>,
+    patterns: &[(FieldIndex, FieldPattern)],
+    fields: RecordFields,
+    vars: &mut RuntimeVars,
+) {
+    for (idx, pattern) in patterns {
+        if let FieldPattern::Bind(var) = pattern {
+            vars.insert(var.1.clone(), fields[*idx].clone());
         }
     }
+
+    if let Some(name) = name {
+        vars.insert(name.1.clone(), Value::Record(fields));
+    }
+}
+
+/// Creates the fields of a record from the host application. Fields that are
+/// not set take their default value, just like in a product.
+pub struct RecordBuilder<'a> {
+    prog: &'a Program,
+    record: RecordId,
+    fields: Vec<Option<Value>>,
+}
+
+impl<'a> RecordBuilder<'a> {
+    pub fn new(prog: &'a Program, record: RecordId) -> Self {
+        Self {
+            prog,
+            record,
+            fields: vec![None; prog[record].fields.len()],
+        }
+    }
+
+    /// Sets the field called `name`. Returns `false` if the record has no
+    /// such field or the value does not fit its type.
+    pub fn set(&mut self, name: &str, val: Value) -> bool {
+        let record = &self.prog[self.record];
+        match record.field_names.iter().position(|f| f.1 == name) {
+            Some(idx) if value_has_type(self.prog, &val, record.fields[idx]) => {
+                self.fields[idx] = Some(val);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns `None` if a field without a default value was not set.
+    pub fn build(self) -> Option<RecordFields> {
+        let prog = self.prog;
+        let record = self.record;
+        self.fields
+            .into_iter()
+            .enumerate()
+            .map(|(idx, val)| val.or_else(|| default_field_value(prog, record, idx)))
+            .collect()
+    }
+}
+
+/// Evaluates an expression that was folded into a literal at compile time.
+fn eval_constant(prog: &Program, id: ExpressionId) -> Option<Value> {
+    // constants never draw random numbers, so a fixed seed avoids asking the
+    // operating system for entropy
+    let mut ctx = ProgramContext {
+        exts: HashMap::default(),
+        params: HashMap::default(),
+        rng: StdRng::seed_from_u64(0),
+        iteration: 0,
+        time: 0.0,
+    };
+    eval_expr(
+        prog,
+        &mut ctx,
+        &CellEnvSummary::default(),
+        &RuntimeVars::default(),
+        id,
+    )
+}
+
+/// The value of a field that was left out when creating a record, if the
+/// field has a default value or is optional.
+pub fn default_field_value(prog: &Program, record: RecordId, field: FieldIndex) -> Option<Value> {
+    let record = &prog[record];
+    if let Some(default) = record.defaults[field] {
+        eval_constant(prog, default)
+    } else if let hir::types::Type::Optional(_) = prog[record.fields[field]] {
+        Some(Value::None)
+    } else {
+        None
+    }
 }
 
 impl RecordContainer for CellEnv {
@@ -306,45 +1281,155 @@ impl RecordContainer for CellEnv {
             .map(|vals| vals.len())
             .unwrap_or(0)
     }
+
+    fn instances(&self, record_id: RecordId) -> &[RecordFields] {
+        self.records.get(&record_id).map_or(&[], |vals| vals)
+    }
 }
 
+/// The number of instances taken out of a summary for each record, with the
+/// ones of them whose fields are tracked.
+pub type TakenInstances = Vec<(RecordId, usize, Vec<RecordFields>)>;
+
 #[derive(Default, Debug)]
 pub struct CellEnvSummary {
     pub records: HashMap<RecordId, usize>,
+    /// The remaining instances of records that are bound with field patterns.
+    pub instances: HashMap<RecordId, Vec<RecordFields>>,
+    pub fired_genes: HashSet<GeneId>,
 }
 
 impl CellEnvSummary {
     pub fn clear(&mut self) {
         self.records.clear();
+        self.instances.clear();
+        self.fired_genes.clear();
     }
 
-    pub fn check_bind(&self, bind: &Bind, record_id: RecordId) -> bool {
-        let have = self.records.get(&record_id).copied().unwrap_or(0);
+    pub fn check_bind(&self, prog: &Program, bind: &Bind, record_id: RecordId) -> bool {
+        let mut members = prog.family_members(record_id);
 
         match bind {
-            Bind::Quantity(need) => have >= *need,
-            Bind::Named(_) => have >= 1,
+            Bind::Quantity(need) => members.map(|m| self.count_records(m)).sum::<usize>() >= *need,
+            Bind::Named(_) => members.any(|m| self.count_records(m) >= 1),
+            Bind::Pattern { fields, .. } => {
+                members.any(|m| self.has_match(prog, record_id, m, fields))
+            }
         }
     }
 
-    pub fn commit_bind(&mut self, bind: &Bind, record_id: RecordId) {
-        let have_opt = self.records.get_mut(&record_id);
-
-        let have = if let Some(n) = have_opt {
-            n
-        } else {
-            return;
-        };
+    /// Checks if an instance of `member` that was not bound yet matches the
+    /// pattern on its `family`.
+    fn has_match(
+        &self,
+        prog: &Program,
+        family: RecordId,
+        member: RecordId,
+        patterns: &[(FieldIndex, FieldPattern)],
+    ) -> bool {
+        let values = pattern_values(prog, family, member, patterns);
+        self.count_records(member) >= 1
+            && self
+                .instances
+                .get(&member)
+                .is_some_and(|recs| recs.iter().any(|rec| fields_match(rec, &values)))
+    }
 
+    /// Takes the instances of `bind` out of the summary, from the family
+    /// members in order. Returns what was taken, so that it can be given back.
+    pub fn commit_bind(
+        &mut self,
+        prog: &Program,
+        bind: &Bind,
+        record_id: RecordId,
+    ) -> TakenInstances {
         match bind {
             Bind::Quantity(need) => {
-                debug_assert!(*have >= *need);
-                *have -= *need;
+                let mut need = *need;
+                let mut taken = vec![];
+                for member in prog.family_members(record_id) {
+                    if let Some(have) = self.records.get_mut(&member) {
+                        let n = need.min(*have);
+                        *have -= n;
+                        need -= n;
+                        taken.push((member, n, self.forget_instances(member, n)));
+                    }
+                }
+                debug_assert_eq!(need, 0);
+                taken
             }
             Bind::Named(_) => {
-                debug_assert!(*have >= 1);
-                *have -= 1;
+                let member = prog
+                    .family_members(record_id)
+                    .find(|m| self.count_records(*m) >= 1);
+                debug_assert!(member.is_some());
+                if let Some(member) = member {
+                    *self.records.get_mut(&member).unwrap() -= 1;
+                    vec![(member, 1, self.forget_instances(member, 1))]
+                } else {
+                    vec![]
+                }
+            }
+            Bind::Pattern { fields, .. } => {
+                let member = prog
+                    .family_members(record_id)
+                    .find(|m| self.has_match(prog, record_id, *m, fields));
+                debug_assert!(member.is_some());
+                let member = if let Some(member) = member {
+                    member
+                } else {
+                    return vec![];
+                };
+
+                *self.records.get_mut(&member).unwrap() -= 1;
+
+                let values = pattern_values(prog, record_id, member, fields);
+                let recs = self.instances.get_mut(&member).unwrap();
+                let rec = recs
+                    .iter()
+                    .position(|rec| fields_match(rec, &values))
+                    .map(|idx| recs.swap_remove(idx));
+                vec![(member, 1, rec.into_iter().collect())]
+            }
+        }
+    }
+
+    /// Takes the instances of all `binds` out of the summary, one bind after
+    /// the other, so that no instance is claimed twice. Nothing is taken if
+    /// they can't all be bound.
+    pub fn commit_binds(
+        &mut self,
+        prog: &Program,
+        binds: &[(Bind, RecordId)],
+    ) -> Option<TakenInstances> {
+        let mut taken = vec![];
+        for (bind, rec) in patterns_first(binds) {
+            if !self.check_bind(prog, bind, *rec) {
+                self.give_back(taken);
+                return None;
             }
+            taken.extend(self.commit_bind(prog, bind, *rec));
+        }
+        Some(taken)
+    }
+
+    /// Gives back instances taken by [`CellEnvSummary::commit_bind`].
+    pub fn give_back(&mut self, taken: TakenInstances) {
+        for (member, n, fields) in taken {
+            *self.records.entry(member).or_default() += n;
+            if let Some(recs) = self.instances.get_mut(&member) {
+                recs.extend(fields);
+            }
+        }
+    }
+
+    /// Drops instances claimed without a pattern and returns them. Which ones
+    /// the bind takes is only decided when it runs, so the last ones stand in
+    /// for those.
+    fn forget_instances(&mut self, record_id: RecordId, n: usize) -> Vec<RecordFields> {
+        match self.instances.get_mut(&record_id) {
+            Some(recs) => recs.split_off(recs.len().saturating_sub(n)),
+            None => vec![],
         }
     }
 }
@@ -353,6 +1438,51 @@ impl RecordContainer for CellEnvSummary {
     fn count_records(&self, record_id: RecordId) -> usize {
         self.records.get(&record_id).copied().unwrap_or(0)
     }
+
+    fn instances(&self, record_id: RecordId) -> &[RecordFields] {
+        self.instances.get(&record_id).map_or(&[], |vals| vals)
+    }
+}
+
+/// The genes that ran recently and have to wait for their `cooldown` to pass.
+#[derive(Default, Debug, Clone)]
+pub struct GeneCooldowns {
+    remaining: HashMap<GeneId, usize>,
+}
+
+impl GeneCooldowns {
+    pub fn is_cooling_down(&self, gene_id: GeneId) -> bool {
+        self.remaining.contains_key(&gene_id)
+    }
+
+    /// Checks if a gene that is cooling down has its factors, so that it only
+    /// waits for the cooldown to pass before it can run again.
+    pub fn any_waiting(&self, prog: &Program, summ: &CellEnvSummary) -> bool {
+        self.remaining.keys().any(|id| {
+            let gene = &prog[*id];
+            !(gene.once && summ.fired_genes.contains(id))
+                && gene
+                    .binds
+                    .iter()
+                    .all(|(bind, rec)| summ.check_bind(prog, bind, *rec))
+        })
+    }
+
+    /// Starts the cooldown of a gene that just ran.
+    pub fn start(&mut self, prog: &Program, gene_id: GeneId) {
+        let cooldown = prog[gene_id].cooldown;
+        if cooldown > 0 {
+            self.remaining.insert(gene_id, cooldown);
+        }
+    }
+
+    /// Counts down one iteration.
+    pub fn tick(&mut self) {
+        self.remaining.retain(|_, n| {
+            *n -= 1;
+            *n > 0
+        });
+    }
 }
 
 #[derive(Default, Debug)]
@@ -376,59 +1506,136 @@ impl ExecutionPlan {
         self.eligable_rules.clear();
     }
 
-    pub fn prepare_gene_execution(&mut self, prog: &Program, summ: &mut CellEnvSummary) {
+    pub fn prepare_gene_execution(
+        &mut self,
+        ctx: &mut ProgramContext,
+        prog: &Program,
+        summ: &mut CellEnvSummary,
+        cooldowns: &GeneCooldowns,
+    ) {
         self.clear();
 
-        self.genes.extend(prog.genes.iter().map(|(id, _)| id));
-        self.genes.shuffle(&mut rand::thread_rng());
+        self.genes.extend(
+            prog.genes
+                .iter()
+                .map(|(id, _)| id)
+                .filter(|id| !cooldowns.is_cooling_down(*id)),
+        );
+        schedule_genes(prog, &mut self.genes, &mut ctx.rng);
 
         self.eligable_genes.extend(
             self.genes
                 .iter()
-                .filter(|id| is_gene_eligable(&prog[**id], summ)),
+                .filter(|id| is_gene_eligable(prog, **id, summ)),
         );
     }
 
-    pub fn prepare_rule_execution(&mut self, prog: &Program, summ: &mut CellEnvSummary) {
+    pub fn prepare_rule_execution(
+        &mut self,
+        ctx: &mut ProgramContext,
+        prog: &Program,
+        summ: &mut CellEnvSummary,
+    ) {
         self.clear();
 
         self.rules.extend(prog.rules.iter().map(|(id, _)| id));
-        self.rules.shuffle(&mut rand::thread_rng());
+        self.rules.shuffle(&mut ctx.rng);
+        self.rules
+            .sort_by_key(|id| std::cmp::Reverse(prog[*id].priority));
+
+        // rules with a higher priority claim their reactants before the rules
+        // with a lower priority are checked at all
+        let rules = std::mem::take(&mut self.rules);
+        for group in rules.chunk_by(|a, b| prog[*a].priority == prog[*b].priority) {
+            if group.iter().all(|id| prog[*id].weight == 1) {
+                self.add_eligable_rules_in_rounds(ctx, prog, summ, group);
+            } else {
+                self.add_eligable_rules_by_weight(ctx, prog, summ, group);
+            }
+        }
+        self.rules = rules;
+    }
 
-        self.eligable_rule_ids.extend(
-            self.rules
-                .iter()
-                .filter(|id| is_rule_eligable(prog, &prog[**id], summ)),
-        );
+    fn add_eligable_rule_run(&mut self, id: RuleId) {
+        if let Some(n) = self.eligable_rules.get_mut(&id) {
+            *n += 1;
+        } else {
+            self.eligable_rules.insert(id, 1);
+            self.eligable_rule_ids.push(id);
+        }
+    }
 
+    /// Lets every eligable rule of the group run once per round, in a random
+    /// order, until none of them is eligable anymore.
+    fn add_eligable_rules_in_rounds(
+        &mut self,
+        ctx: &mut ProgramContext,
+        prog: &Program,
+        summ: &mut CellEnvSummary,
+        group: &[RuleId],
+    ) {
         // all rules can "run" at least once.
-        self.eligable_rules
-            .extend(self.eligable_rule_ids.iter().map(|x| (*x, 1)));
-
-        {
-            let mut rng = rand::thread_rng();
-            let mut still_eligable = self.eligable_rule_ids.clone();
-            let mut to_remove = vec![];
+        for id in group {
+            if is_rule_eligable(ctx, prog, &prog[*id], summ) {
+                self.add_eligable_rule_run(*id);
+            }
+        }
 
-            loop {
-                if still_eligable.is_empty() {
-                    break;
+        // rules that consume nothing would stay eligable forever, so
+        // they only run once
+        let mut still_eligable = group
+            .iter()
+            .copied()
+            .filter(|id| self.eligable_rules.contains_key(id) && !prog[*id].binds.is_empty())
+            .collect::<Vec<_>>();
+        let mut to_remove = vec![];
+
+        while !still_eligable.is_empty() {
+            still_eligable.shuffle(&mut ctx.rng);
+
+            for (idx, id) in still_eligable.iter().enumerate() {
+                if is_rule_eligable(ctx, prog, &prog[*id], summ) {
+                    *self.eligable_rules.get_mut(id).unwrap() += 1;
+                } else {
+                    to_remove.push(idx);
                 }
+            }
 
-                still_eligable.shuffle(&mut rng);
+            to_remove.sort_unstable_by(|a, b| b.cmp(a));
 
-                for (idx, id) in still_eligable.iter().enumerate() {
-                    if is_rule_eligable(prog, &prog[*id], summ) {
-                        *self.eligable_rules.get_mut(id).unwrap() += 1;
-                    } else {
-                        to_remove.push(idx);
-                    }
-                }
+            for idx in to_remove.drain(..) {
+                still_eligable.swap_remove(idx);
+            }
+        }
+    }
 
-                to_remove.sort_unstable_by(|a, b| b.cmp(a));
+    /// Gives every run to a random rule of the group, picked by weight, until
+    /// none of them is eligable anymore.
+    fn add_eligable_rules_by_weight(
+        &mut self,
+        ctx: &mut ProgramContext,
+        prog: &Program,
+        summ: &mut CellEnvSummary,
+        group: &[RuleId],
+    ) {
+        let mut weights = WeightedIndex::new(group.iter().map(|id| prog[*id].weight))
+            .expect("weights are positive");
 
-                for idx in to_remove.drain(..) {
-                    still_eligable.swap_remove(idx);
+        loop {
+            let idx = weights.sample(&mut ctx.rng);
+            let id = group[idx];
+
+            let eligable = is_rule_eligable(ctx, prog, &prog[id], summ);
+            if eligable {
+                self.add_eligable_rule_run(id);
+            }
+
+            // rules that consume nothing would stay eligable forever, so
+            // they only run once
+            if !eligable || prog[id].binds.is_empty() {
+                // the group is done once all weights are zero
+                if weights.update_weights(&[(idx, &0)]).is_err() {
+                    break;
                 }
             }
         }
@@ -439,36 +1646,76 @@ impl ExecutionPlan {
     }
 
     pub fn eligable_rules(&self) -> impl Iterator<Item = (RuleId, usize)> + '_ {
-        self.eligable_rules
+        // the ids are in the order the rules became eligable, which puts
+        // higher priorities first and keeps the order reproducible
+        self.eligable_rule_ids
             .iter()
-            .map(|(i, n)| (*i, *n))
+            .map(|id| (*id, self.eligable_rules[id]))
             .filter(|(_, n)| *n > 0)
     }
 }
 
-fn is_gene_eligable(gene: &Gene, summ: &mut CellEnvSummary) -> bool {
-    for (bind, rec) in &gene.binds {
-        if !summ.check_bind(bind, *rec) {
-            return false;
-        }
+/// Orders genes by descending priority. The order among the same priority is
+/// random, genes with a higher weight are more likely to come first.
+fn schedule_genes(prog: &Program, genes: &mut Vec<GeneId>, rng: &mut impl Rng) {
+    genes.shuffle(rng);
+
+    if genes.iter().all(|id| prog[*id].weight == 1) {
+        // the sort is stable, so the shuffled order stays within a priority
+        genes.sort_by_key(|id| std::cmp::Reverse(prog[*id].priority));
+        return;
     }
 
-    for (bind, rec) in &gene.binds {
-        summ.commit_bind(bind, *rec);
+    // weighted random sampling by Efraimidis and Spirakis, sorting by
+    // `u^(1/weight)` with a uniform `u` puts heavier genes first more often
+    let mut keyed = genes
+        .drain(..)
+        .map(|id| {
+            let gene = &prog[id];
+            let key = rng.gen::<f64>().powf(1.0 / gene.weight as f64);
+            (gene.priority, key, id)
+        })
+        .collect::<Vec<_>>();
+    keyed.sort_by(|a, b| b.0.cmp(&a.0).then(b.1.total_cmp(&a.1)));
+    genes.extend(keyed.into_iter().map(|(_, _, id)| id));
+}
+
+fn is_gene_eligable(prog: &Program, gene_id: GeneId, summ: &mut CellEnvSummary) -> bool {
+    let gene = &prog[gene_id];
+
+    if gene.once && summ.fired_genes.contains(&gene_id) {
+        return false;
     }
 
-    true
+    summ.commit_binds(prog, &gene.binds).is_some()
 }
 
-fn is_rule_eligable(prog: &Program, rule: &Rule, summ: &mut CellEnvSummary) -> bool {
-    for (bind, rec) in &rule.binds {
-        if !summ.check_bind(bind, *rec) {
-            return false;
-        }
+fn is_rule_eligable(
+    ctx: &mut ProgramContext,
+    prog: &Program,
+    rule: &Rule,
+    summ: &mut CellEnvSummary,
+) -> bool {
+    // the binds are only tried here, the when condition still sees the
+    // instances that the rule would take
+    let taken = match summ.commit_binds(prog, &rule.binds) {
+        Some(taken) => taken,
+        None => return false,
+    };
+
+    // catalysts are not consumed, so they stay available for the next run, but
+    // they can't be one of the instances the rule consumes
+    let catalysts_bound = rule
+        .catalysts
+        .iter()
+        .all(|(bind, rec)| summ.check_bind(prog, bind, *rec));
+    summ.give_back(taken);
+    if !catalysts_bound {
+        return false;
     }
 
     if let Some(expr) = rule.when {
-        match eval_expr(prog, summ, &RuntimeVars::default(), expr).unwrap() {
+        match eval_expr(prog, ctx, summ, &RuntimeVars::default(), expr).unwrap() {
             Value::Bool(true) => {}
             Value::Bool(false) => {
                 // This rule is not eligable to run because the when condition
@@ -479,9 +1726,8 @@ fn is_rule_eligable(prog: &Program, rule: &Rule, summ: &mut CellEnvSummary) -> b
         }
     }
 
-    for (bind, rec) in &rule.binds {
-        summ.commit_bind(bind, *rec);
-    }
+    let taken = summ.commit_binds(prog, &rule.binds);
+    debug_assert!(taken.is_some());
 
     true
 }
@@ -1,11 +1,59 @@
+use std::hash::{Hash, Hasher};
+
+use cytosol_hir::types::Literal;
+
 pub type RecordFields = Vec<Value>;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Value {
     Bool(bool),
     Integer(isize),
+    Float(f64),
     String(String),
     Record(RecordFields),
+    /// The index of the variant inside of the enum and its payload
+    Variant(usize, RecordFields),
+    List(Vec<Value>),
+    /// An optional value that is not set. Set optional values are stored
+    /// like non-optional values.
+    None,
+}
+
+/// Values are hashed to find instances with the same field values. Floats
+/// compare like `f64` does, so a `NaN` is not even equal to itself.
+impl Eq for Value {}
+
+impl Hash for Value {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+        match self {
+            Value::Bool(b) => b.hash(state),
+            Value::Integer(i) => i.hash(state),
+            // `-0.0` is equal to `0.0`, so it needs the same hash
+            Value::Float(f) => (f + 0.0).to_bits().hash(state),
+            Value::String(s) => s.hash(state),
+            Value::Record(fields) => fields.hash(state),
+            Value::Variant(idx, fields) => {
+                idx.hash(state);
+                fields.hash(state);
+            }
+            Value::List(vals) => vals.hash(state),
+            Value::None => {}
+        }
+    }
+}
+
+impl From<&Literal> for Value {
+    fn from(lit: &Literal) -> Self {
+        match lit {
+            Literal::Bool(b) => Value::Bool(*b),
+            Literal::Integer(i) => Value::Integer(*i),
+            Literal::Float(f) => Value::Float(*f),
+            Literal::String(s) => Value::String(s.clone()),
+            Literal::None => Value::None,
+            Literal::Variant(variant) => Value::Variant(*variant, vec![]),
+        }
+    }
 }
 
 pub trait FromValue<'val>
@@ -41,6 +89,16 @@ impl<'a> FromValue<'a> for isize {
     }
 }
 
+impl<'a> FromValue<'a> for f64 {
+    fn from_value(val: &'a Value) -> Self {
+        if let Value::Float(f) = val {
+            *f
+        } else {
+            panic!("`f64::from_value()` on unexpected value {:?}", val)
+        }
+    }
+}
+
 impl<'a> FromValue<'a> for String {
     fn from_value(val: &'a Value) -> String {
         if let Value::String(s) = val {
@@ -51,12 +109,27 @@ impl<'a> FromValue<'a> for String {
     }
 }
 
-impl<'a> FromValue<'a> for Vec<Value> {
-    fn from_value(val: &'a Value) -> Vec<Value> {
-        if let Value::Record(fields) = val {
-            fields.clone()
-        } else {
-            panic!("`&[Value]::from_value()` on unexpected value {:?}", val)
+/// Converts lists element-wise, records are converted field by field.
+impl<'a, T: FromValue<'a>> FromValue<'a> for Vec<T> {
+    fn from_value(val: &'a Value) -> Vec<T> {
+        match val {
+            Value::List(vals) | Value::Record(vals) => vals.iter().map(T::from_value).collect(),
+            _ => {
+                let type_name = std::any::type_name::<Vec<T>>();
+                panic!(
+                    "`{}::from_value()` on unexpected value {:?}",
+                    type_name, val
+                )
+            }
+        }
+    }
+}
+
+impl<'a, T: FromValue<'a>> FromValue<'a> for Option<T> {
+    fn from_value(val: &'a Value) -> Option<T> {
+        match val {
+            Value::None => None,
+            val => Some(T::from_value(val)),
         }
     }
 }
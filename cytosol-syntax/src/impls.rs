@@ -1,7 +1,8 @@
 use std::{borrow::Borrow, ops::Range};
 
 use crate::types::{
-    Binding, Expression, Gene, GeneStatement, Identifier, Literal, Product, Record, Rule, Type, FC,
+    Binding, Const, Enum, Expression, Gene, GeneStatement, Identifier, Instantiation, Literal,
+    Param, Pattern, Product, Record, Rule, Template, Type, Variant, FC,
 };
 
 impl FC {
@@ -38,6 +39,28 @@ impl HasFC for Record {
     }
 }
 
+impl HasFC for Enum {
+    fn fc(&self) -> FC {
+        self.fc
+    }
+}
+impl HasFC for Variant {
+    fn fc(&self) -> FC {
+        self.fc
+    }
+}
+impl HasFC for Const {
+    fn fc(&self) -> FC {
+        self.fc
+    }
+}
+
+impl HasFC for Param {
+    fn fc(&self) -> FC {
+        self.fc
+    }
+}
+
 impl HasFC for Binding {
     fn fc(&self) -> FC {
         self.fc
@@ -48,6 +71,8 @@ impl HasFC for Type {
     fn fc(&self) -> FC {
         match self {
             Type::Named(n) => n.fc(),
+            Type::List { fc, .. } => *fc,
+            Type::Optional { fc, .. } => *fc,
         }
     }
 }
@@ -63,6 +88,18 @@ impl HasFC for Rule {
         self.fc
     }
 }
+
+impl HasFC for Template {
+    fn fc(&self) -> FC {
+        self.fc
+    }
+}
+
+impl HasFC for Instantiation {
+    fn fc(&self) -> FC {
+        self.fc
+    }
+}
 impl HasFC for Product {
     fn fc(&self) -> FC {
         self.fc
@@ -73,6 +110,11 @@ impl HasFC for GeneStatement {
         match self {
             GeneStatement::Call { fc, .. } => *fc,
             GeneStatement::Express(fc, _) => *fc,
+            GeneStatement::ExpressAfter { fc, .. } => *fc,
+            GeneStatement::WithProbability { fc, .. } => *fc,
+            GeneStatement::Let { fc, .. } => *fc,
+            GeneStatement::If { fc, .. } => *fc,
+            GeneStatement::Repeat { fc, .. } => *fc,
         }
     }
 }
@@ -85,6 +127,24 @@ impl HasFC for Expression {
             Expression::PrefixOp { op, expr } => op.0.merge(expr.fc()),
             Expression::InfixOp { op: _, args } => args[0].fc().merge(args[1].fc()),
             Expression::Concentration(ty) => ty.0,
+            Expression::List { fc, .. } => *fc,
+            Expression::OptionTest { fc, .. } => *fc,
+            Expression::Call { fc, .. } => *fc,
+            Expression::FilteredConcentration { fc, .. } => *fc,
+            Expression::InstanceField { fc, .. } => *fc,
+            Expression::Iteration(fc) => *fc,
+            Expression::Time(fc) => *fc,
+            Expression::Aggregate { fc, .. } => *fc,
+            Expression::Variant { fc, .. } => *fc,
+            Expression::Match { fc, .. } => *fc,
+        }
+    }
+}
+impl HasFC for Pattern {
+    fn fc(&self) -> FC {
+        match self {
+            Pattern::Wildcard(fc) => *fc,
+            Pattern::Variant { fc, .. } => *fc,
         }
     }
 }
@@ -93,6 +153,8 @@ impl HasFC for Literal {
         match self {
             Literal::Bool(fc, _) => *fc,
             Literal::Integer(fc, _) => *fc,
+            Literal::Float(fc, _) => *fc,
+            Literal::None(fc) => *fc,
             Literal::String(fc, _) => *fc,
         }
     }
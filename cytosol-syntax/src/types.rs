@@ -11,9 +11,14 @@ pub struct FC {
 #[derive(Debug, Default, Clone)]
 pub struct File {
     pub records: Vec<Record>,
+    pub enums: Vec<Enum>,
     pub genes: Vec<Gene>,
     pub rules: Vec<Rule>,
     pub externs: Vec<Extern>,
+    pub consts: Vec<Const>,
+    pub params: Vec<Param>,
+    pub templates: Vec<Template>,
+    pub instantiations: Vec<Instantiation>,
 }
 
 #[derive(Debug, Clone, Hash, PartialEq, Eq)]
@@ -22,8 +27,14 @@ pub struct Identifier(pub FC, pub String);
 #[derive(Debug, Clone)]
 pub struct Record {
     pub fc: FC,
+    /// The `singleton` modifier, only one instance can be present at a time
+    pub singleton: Option<FC>,
     pub name: Identifier,
     pub fields: Vec<(Identifier, Type)>,
+    /// Values of the fields that can be left out, in the order of `fields`
+    pub defaults: Vec<Option<Expression>>,
+    /// The record family this record belongs to, `is Family`
+    pub family: Option<Identifier>,
 }
 
 #[derive(Debug, Clone)]
@@ -33,6 +44,39 @@ pub struct Extern {
     pub parameters: Vec<(Identifier, Type)>,
 }
 
+/// A named value that is folded at compile time.
+/// A type whose values are one of a fixed set of variants.
+#[derive(Debug, Clone)]
+pub struct Enum {
+    pub fc: FC,
+    pub name: Identifier,
+    pub variants: Vec<Variant>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Variant {
+    pub fc: FC,
+    pub name: Identifier,
+    pub fields: Vec<(Identifier, Type)>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Const {
+    pub fc: FC,
+    pub name: Identifier,
+    pub ty: Type,
+    pub value: Expression,
+}
+
+/// A named value that can be overridden by the host application.
+#[derive(Debug, Clone)]
+pub struct Param {
+    pub fc: FC,
+    pub name: Identifier,
+    pub ty: Type,
+    pub default: Expression,
+}
+
 #[derive(Debug, Clone)]
 pub enum BindingAttribute {
     Quantity(FC, usize),
@@ -42,19 +86,42 @@ pub enum BindingAttribute {
 #[derive(Debug, Clone)]
 pub struct Binding {
     pub fc: FC,
+    /// `catalyst` marker of a rule reactant, which is bound without being
+    /// consumed
+    pub catalyst: Option<FC>,
     pub attr: Option<BindingAttribute>,
     pub name: Identifier,
+    /// Field patterns like in `Person(name: n, age: 30)`
+    pub fields: Vec<(Identifier, Expression)>,
 }
 
 #[derive(Debug, Clone)]
 pub enum Type {
     Named(Identifier),
+    /// `list<T>`
+    List {
+        fc: FC,
+        elem: Box<Type>,
+    },
+    /// `T?`
+    Optional {
+        fc: FC,
+        inner: Box<Type>,
+    },
 }
 
 #[derive(Debug, Clone)]
 pub struct Gene {
     pub fc: FC,
+    /// The `once` modifier, the gene runs at most once per environment
+    pub once: Option<FC>,
+    /// The `on` of `gene on (m: Message)`, which runs for every new instance
+    /// of its factor instead of while it is present
+    pub on: Option<FC>,
     pub factors: Vec<Binding>,
+    /// Number of iterations the gene can't run after it ran, like `cooldown 10`
+    pub cooldown: Option<(FC, usize)>,
+    pub scheduling: Scheduling,
     pub when: Option<Expression>,
     pub body: Vec<GeneStatement>,
 }
@@ -63,15 +130,61 @@ pub struct Gene {
 pub struct Rule {
     pub fc: FC,
     pub reactants: Vec<Binding>,
+    /// The `<->` arrow of a reversible rule
+    pub reversible: Option<FC>,
+    pub scheduling: Scheduling,
     pub when: Option<Expression>,
+    /// The `reverse when` clause of a reversible rule
+    pub reverse_when: Option<Expression>,
     pub products: Vec<Product>,
+    /// Local variables from the `where` clause
+    pub lets: Vec<(Identifier, Expression)>,
+}
+
+/// Rules and genes that are written once for any record types, like
+/// `template degrade<T> { rule (T) -> nothing }`
+#[derive(Debug, Clone)]
+pub struct Template {
+    pub fc: FC,
+    pub name: Identifier,
+    /// The names that stand for the record types of an instantiation
+    pub params: Vec<Identifier>,
+    pub rules: Vec<Rule>,
+    pub genes: Vec<Gene>,
+}
+
+/// Use of a template with concrete record types, like `instantiate degrade<mRNA>`
+#[derive(Debug, Clone)]
+pub struct Instantiation {
+    pub fc: FC,
+    pub template: Identifier,
+    pub args: Vec<Identifier>,
+}
+
+/// The `priority n` and `weight n` annotations of a gene or rule
+#[derive(Debug, Clone, Default)]
+pub struct Scheduling {
+    /// Genes and rules with a higher priority claim their factors or
+    /// reactants first
+    pub priority: Option<(FC, isize)>,
+    /// Bias of the random order among the same priority
+    pub weight: Option<(FC, usize)>,
+}
+
+#[derive(Debug, Clone)]
+pub enum Quantity {
+    Literal(FC, usize),
+    /// Quantity that is evaluated at runtime, like `express f.n Widget`
+    Expression(Box<Expression>),
 }
 
 #[derive(Debug, Clone)]
 pub struct Product {
     pub fc: FC,
-    pub quantity: Option<(FC, usize)>,
+    pub quantity: Option<Quantity>,
     pub name: Identifier,
+    /// Value the omitted fields are copied from, written as `..base`
+    pub base: Option<Expression>,
     pub fields: Vec<(Identifier, Expression)>,
 }
 
@@ -84,6 +197,38 @@ pub enum GeneStatement {
         arguments: Vec<(Identifier, Expression)>,
     },
     Express(FC, Product),
+    /// Expression that takes effect `delay` iterations later, like
+    /// `express after 5 Signal`
+    ExpressAfter {
+        fc: FC,
+        delay: Expression,
+        product: Product,
+    },
+    /// Expression that only happens some of the time, like
+    /// `express Signal with probability 0.3`
+    WithProbability {
+        fc: FC,
+        probability: Expression,
+        statement: Box<GeneStatement>,
+    },
+    /// Local variable definition
+    Let {
+        fc: FC,
+        name: Identifier,
+        value: Expression,
+    },
+    /// Conditional execution, `else if` chains are nested in `else_body`
+    If {
+        fc: FC,
+        cond: Expression,
+        then_body: Vec<GeneStatement>,
+        else_body: Vec<GeneStatement>,
+    },
+    Repeat {
+        fc: FC,
+        count: Expression,
+        body: Vec<GeneStatement>,
+    },
 }
 
 #[derive(Debug, Clone)]
@@ -103,18 +248,97 @@ pub enum Expression {
         args: Box<[Expression; 2]>,
     },
     Concentration(Identifier),
+    /// Number of instances that match a filter, like `[Person where .age >= 18]`
+    FilteredConcentration {
+        fc: FC,
+        name: Identifier,
+        filter: Box<Expression>,
+    },
+    /// Field of the instance that is checked by a concentration filter, like `.age`
+    InstanceField {
+        fc: FC,
+        field_name: Identifier,
+    },
+    /// `sum`, `min`, `max` or `avg` of a field over all instances of a record,
+    /// like `sum(Person.age)`
+    Aggregate {
+        fc: FC,
+        func: Identifier,
+        record: Identifier,
+        field: Identifier,
+    },
+    /// `@iteration`, the number of iterations that ran before the current one
+    Iteration(FC),
+    /// `@time`, the simulated time of the current iteration
+    Time(FC),
+    /// List literal like `[1, 2, 3]` or `list<int>[]`.
+    ///
+    /// A list with a single variable like `[x]` is parsed as a concentration.
+    List {
+        fc: FC,
+        elem_ty: Option<Type>,
+        elems: Vec<Expression>,
+    },
+    /// `x is some` or `x is none`
+    OptionTest {
+        fc: FC,
+        expr: Box<Expression>,
+        is_some: bool,
+    },
+    /// Call of a builtin function or construction of a record like `Fib(a: 1, b: 2)`
+    Call {
+        fc: FC,
+        name: Identifier,
+        /// Value the omitted fields are copied from, written as `..base`
+        base: Option<Box<Expression>>,
+        arguments: Vec<(Identifier, Expression)>,
+    },
+    /// Enum value like `Shape::Circle(r: 3)`
+    Variant {
+        fc: FC,
+        enum_name: Identifier,
+        variant: Identifier,
+        fields: Vec<(Identifier, Expression)>,
+    },
+    Match {
+        fc: FC,
+        expr: Box<Expression>,
+        arms: Vec<MatchArm>,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct MatchArm {
+    pub pattern: Pattern,
+    pub body: Expression,
+}
+
+#[derive(Debug, Clone)]
+pub enum Pattern {
+    /// `_`
+    Wildcard(FC),
+    /// `Shape::Circle(r: radius)`, binding payload fields to variables
+    Variant {
+        fc: FC,
+        enum_name: Identifier,
+        variant: Identifier,
+        binds: Vec<(Identifier, Identifier)>,
+    },
 }
 
 #[derive(Debug, Clone)]
 pub enum Literal {
     Bool(FC, bool),
     Integer(FC, usize),
+    Float(FC, f64),
     String(FC, String),
+    None(FC),
 }
 
 #[derive(Debug, Clone, Copy)]
 pub enum PrefixOperator {
     Neg,
+    Not,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -124,6 +348,7 @@ pub enum InfixOperator {
 
     Mul,
     Div,
+    Mod,
 
     Eq,
     Neq,
@@ -134,4 +359,5 @@ pub enum InfixOperator {
 
     And,
     Or,
+    Xor,
 }
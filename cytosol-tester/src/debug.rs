@@ -3,8 +3,9 @@ use pretty::RcDoc as Doc;
 use cytosol::{
     parser::Token,
     syntax::{
-        Binding, BindingAttribute, Expression, Extern, File, Gene, GeneStatement, Identifier,
-        InfixOperator, Literal, PrefixOperator, Product, Record, Rule, Type,
+        Binding, BindingAttribute, Const, Enum, Expression, Extern, File, Gene, GeneStatement,
+        Identifier, InfixOperator, Instantiation, Literal, MatchArm, Param, Pattern,
+        PrefixOperator, Product, Quantity, Record, Rule, Scheduling, Template, Type, Variant,
     },
 };
 
@@ -26,11 +27,11 @@ pub(crate) fn pretty_print<T: ToDoc>(val: &T, width: usize) -> String {
 }
 
 pub(crate) trait ToDoc {
-    fn to_doc(&self) -> Doc;
+    fn to_doc(&self) -> Doc<'_>;
 }
 
 impl<T: ToDoc> ToDoc for Vec<T> {
-    fn to_doc(&self) -> Doc {
+    fn to_doc(&self) -> Doc<'_> {
         if self.is_empty() {
             Doc::text("()")
         } else {
@@ -46,11 +47,48 @@ impl<T: ToDoc> ToDoc for Vec<T> {
     }
 }
 
+impl<T: ToDoc> ToDoc for Option<T> {
+    fn to_doc(&self) -> Doc<'_> {
+        match self {
+            Some(val) => val.to_doc(),
+            None => Doc::text("_"),
+        }
+    }
+}
+
 impl ToDoc for File {
-    fn to_doc(&self) -> Doc {
+    fn to_doc(&self) -> Doc<'_> {
+        // values are only shown when present to keep dumps of other files short
+        let values = if self.consts.is_empty() && self.params.is_empty() {
+            Doc::nil()
+        } else {
+            self.consts
+                .to_doc()
+                .append(Doc::hardline())
+                .append(self.params.to_doc())
+                .append(Doc::hardline())
+        };
+
+        let enums = if self.enums.is_empty() {
+            Doc::nil()
+        } else {
+            self.enums.to_doc().append(Doc::hardline())
+        };
+
+        let templates = if self.templates.is_empty() && self.instantiations.is_empty() {
+            Doc::nil()
+        } else {
+            Doc::hardline()
+                .append(self.templates.to_doc())
+                .append(Doc::hardline())
+                .append(self.instantiations.to_doc().group())
+        };
+
         Doc::text("(file")
             .append(
                 Doc::line()
+                    .append(values)
+                    .append(enums)
                     .append(self.records.to_doc())
                     .append(Doc::hardline())
                     .append(self.genes.to_doc())
@@ -58,19 +96,49 @@ impl ToDoc for File {
                     .append(self.rules.to_doc().group())
                     .append(Doc::hardline())
                     .append(self.externs.to_doc().group())
+                    .append(templates)
                     .nest(4)
                     .group(),
             )
             .append(Doc::text(")"))
     }
 }
+impl ToDoc for Template {
+    fn to_doc(&self) -> Doc<'_> {
+        Doc::text("(template")
+            .append(
+                Doc::line()
+                    .append(self.name.to_doc())
+                    .append(Doc::space())
+                    .append(self.params.to_doc())
+                    .append(Doc::line())
+                    .append(self.rules.to_doc().group())
+                    .append(Doc::line())
+                    .append(self.genes.to_doc())
+                    .nest(4)
+                    .group(),
+            )
+            .append(Doc::text(")"))
+    }
+}
+
+impl ToDoc for Instantiation {
+    fn to_doc(&self) -> Doc<'_> {
+        Doc::text("(instantiate ")
+            .append(self.template.to_doc())
+            .append(Doc::space())
+            .append(self.args.to_doc())
+            .append(Doc::text(")"))
+    }
+}
+
 impl ToDoc for Identifier {
-    fn to_doc(&self) -> Doc {
+    fn to_doc(&self) -> Doc<'_> {
         Doc::text(&self.1)
     }
 }
 impl ToDoc for Extern {
-    fn to_doc(&self) -> Doc {
+    fn to_doc(&self) -> Doc<'_> {
         Doc::text("(extern")
             .append(
                 Doc::line()
@@ -84,14 +152,100 @@ impl ToDoc for Extern {
     }
 }
 
+impl ToDoc for Enum {
+    fn to_doc(&self) -> Doc<'_> {
+        Doc::text("(enum")
+            .append(
+                Doc::line()
+                    .append(self.name.to_doc())
+                    .append(Doc::space())
+                    .append(self.variants.to_doc())
+                    .nest(4)
+                    .group(),
+            )
+            .append(Doc::text(")"))
+    }
+}
+
+impl ToDoc for Variant {
+    fn to_doc(&self) -> Doc<'_> {
+        Doc::text("(variant")
+            .append(
+                Doc::line()
+                    .append(self.name.to_doc())
+                    .append(Doc::space())
+                    .append(self.fields.to_doc())
+                    .nest(4)
+                    .group(),
+            )
+            .append(Doc::text(")"))
+    }
+}
+
+impl ToDoc for Const {
+    fn to_doc(&self) -> Doc<'_> {
+        Doc::text("(const")
+            .append(
+                Doc::line()
+                    .append(self.name.to_doc())
+                    .append(Doc::line())
+                    .append(self.ty.to_doc())
+                    .append(Doc::line())
+                    .append(self.value.to_doc())
+                    .nest(4)
+                    .group(),
+            )
+            .append(Doc::text(")"))
+    }
+}
+
+impl ToDoc for Param {
+    fn to_doc(&self) -> Doc<'_> {
+        Doc::text("(param")
+            .append(
+                Doc::line()
+                    .append(self.name.to_doc())
+                    .append(Doc::line())
+                    .append(self.ty.to_doc())
+                    .append(Doc::line())
+                    .append(self.default.to_doc())
+                    .nest(4)
+                    .group(),
+            )
+            .append(Doc::text(")"))
+    }
+}
+
 impl ToDoc for Record {
-    fn to_doc(&self) -> Doc {
-        Doc::text("(record")
+    fn to_doc(&self) -> Doc<'_> {
+        let defaults = if self.defaults.iter().any(Option::is_some) {
+            Doc::line().append(self.defaults.to_doc())
+        } else {
+            Doc::nil()
+        };
+
+        let family = match &self.family {
+            Some(family) => Doc::line()
+                .append(Doc::text("(is "))
+                .append(family.to_doc())
+                .append(Doc::text(")")),
+            None => Doc::nil(),
+        };
+
+        let head = if self.singleton.is_some() {
+            "(singleton-record"
+        } else {
+            "(record"
+        };
+
+        Doc::text(head)
             .append(
                 Doc::line()
                     .append(self.name.to_doc())
                     .append(Doc::space())
                     .append(self.fields.to_doc())
+                    .append(defaults)
+                    .append(family)
                     .nest(4)
                     .group(),
             )
@@ -100,29 +254,45 @@ impl ToDoc for Record {
 }
 
 impl ToDoc for Binding {
-    fn to_doc(&self) -> Doc {
+    fn to_doc(&self) -> Doc<'_> {
         let attr = match &self.attr {
             Some(BindingAttribute::Name(n)) => n.to_doc().append(Doc::space()),
             Some(BindingAttribute::Quantity(_, n)) => Doc::as_string(n).append(Doc::space()),
             None => Doc::nil(),
         };
 
+        let fields = if self.fields.is_empty() {
+            Doc::nil()
+        } else {
+            Doc::space().append(self.fields.to_doc())
+        };
+
+        let catalyst = if self.catalyst.is_some() {
+            Doc::text("catalyst ")
+        } else {
+            Doc::nil()
+        };
+
         Doc::text("(record ")
+            .append(catalyst)
             .append(attr)
             .append(self.name.to_doc())
+            .append(fields)
             .append(Doc::text(")"))
             .group()
     }
 }
 impl ToDoc for Type {
-    fn to_doc(&self) -> Doc {
+    fn to_doc(&self) -> Doc<'_> {
         match self {
             Type::Named(n) => n.to_doc(),
+            Type::List { fc: _, elem } => Doc::text("list<").append(elem.to_doc()).append(">"),
+            Type::Optional { fc: _, inner } => inner.to_doc().append("?"),
         }
     }
 }
 impl ToDoc for Gene {
-    fn to_doc(&self) -> Doc {
+    fn to_doc(&self) -> Doc<'_> {
         let when = if let Some(expr) = &self.when {
             Doc::text("(when ")
                 .append(expr.to_doc())
@@ -132,11 +302,30 @@ impl ToDoc for Gene {
         } else {
             Doc::nil()
         };
-        Doc::text("(gene")
+        let head = if self.once.is_some() {
+            "(once-gene"
+        } else {
+            "(gene"
+        };
+        let cooldown = if let Some((_, n)) = &self.cooldown {
+            Doc::text("(cooldown ")
+                .append(Doc::as_string(n))
+                .append(")")
+                .append(Doc::hardline())
+        } else {
+            Doc::nil()
+        };
+        let scheduling = scheduling_to_doc(&self.scheduling)
+            .map_or(Doc::nil(), |doc| doc.append(Doc::hardline()));
+        let on = if self.on.is_some() { " on" } else { "" };
+        Doc::text(head)
+            .append(on)
             .append(
                 Doc::hardline()
                     .append(self.factors.to_doc())
                     .append(Doc::hardline())
+                    .append(cooldown)
+                    .append(scheduling)
                     .append(when)
                     .append(self.body.to_doc())
                     .nest(4)
@@ -148,7 +337,7 @@ impl ToDoc for Gene {
 }
 
 impl ToDoc for Rule {
-    fn to_doc(&self) -> Doc {
+    fn to_doc(&self) -> Doc<'_> {
         let when = if let Some(expr) = &self.when {
             Doc::hardline()
                 .append("(when ")
@@ -158,13 +347,41 @@ impl ToDoc for Rule {
         } else {
             Doc::nil()
         };
-        Doc::text("(rule")
+        let reverse_when = if let Some(expr) = &self.reverse_when {
+            Doc::hardline()
+                .append("(reverse-when ")
+                .append(expr.to_doc())
+                .append(")")
+                .group()
+        } else {
+            Doc::nil()
+        };
+        let lets = if self.lets.is_empty() {
+            Doc::nil()
+        } else {
+            Doc::hardline()
+                .append("(where ")
+                .append(self.lets.to_doc())
+                .append(")")
+                .group()
+        };
+        let scheduling = scheduling_to_doc(&self.scheduling)
+            .map_or(Doc::nil(), |doc| Doc::hardline().append(doc));
+        let head = if self.reversible.is_some() {
+            "(reversible-rule"
+        } else {
+            "(rule"
+        };
+        Doc::text(head)
             .append(
                 Doc::line()
                     .append(self.reactants.to_doc())
                     .append(Doc::line())
                     .append(self.products.to_doc())
+                    .append(scheduling)
                     .append(when)
+                    .append(reverse_when)
+                    .append(lets)
                     .nest(4)
                     .group(),
             )
@@ -172,15 +389,31 @@ impl ToDoc for Rule {
             .group()
     }
 }
+/// The `priority` and `weight` of a gene or rule, `None` if it has neither.
+fn scheduling_to_doc(scheduling: &Scheduling) -> Option<Doc<'_>> {
+    let priority = scheduling.priority.map(|(_, n)| {
+        Doc::text("(priority ")
+            .append(Doc::as_string(n))
+            .append(")")
+    });
+    let weight = scheduling
+        .weight
+        .map(|(_, n)| Doc::text("(weight ").append(Doc::as_string(n)).append(")"));
+    match (priority, weight) {
+        (Some(priority), Some(weight)) => Some(priority.append(Doc::space()).append(weight)),
+        (priority, weight) => priority.or(weight),
+    }
+}
+
 impl ToDoc for Product {
-    fn to_doc(&self) -> Doc {
-        let quantity = if let Some((_, n)) = &self.quantity {
-            Doc::as_string(*n).append(Doc::line())
-        } else {
-            Doc::nil()
+    fn to_doc(&self) -> Doc<'_> {
+        let quantity = match &self.quantity {
+            Some(Quantity::Literal(_, n)) => Doc::as_string(*n).append(Doc::line()),
+            Some(Quantity::Expression(expr)) => expr.to_doc().append(Doc::line()),
+            None => Doc::nil(),
         };
 
-        if self.fields.is_empty() {
+        if self.fields.is_empty() && self.base.is_none() {
             Doc::text("(product")
                 .append(Doc::line())
                 .append(quantity)
@@ -193,14 +426,27 @@ impl ToDoc for Product {
                 .append(quantity)
                 .append(self.name.to_doc())
                 .append(Doc::line())
+                .append(record_base_to_doc(self.base.as_ref()))
                 .append(self.fields.to_doc())
                 .append(")")
                 .group()
         }
     }
 }
+fn record_base_to_doc(base: Option<&Expression>) -> Doc<'_> {
+    if let Some(base) = base {
+        Doc::text("(..")
+            .append(Doc::line())
+            .append(base.to_doc())
+            .append(")")
+            .group()
+            .append(Doc::line())
+    } else {
+        Doc::nil()
+    }
+}
 impl ToDoc for GeneStatement {
-    fn to_doc(&self) -> Doc {
+    fn to_doc(&self) -> Doc<'_> {
         match self {
             GeneStatement::Call {
                 fc: _,
@@ -218,11 +464,63 @@ impl ToDoc for GeneStatement {
                 .append(prod.to_doc())
                 .append(")")
                 .group(),
+            GeneStatement::ExpressAfter {
+                fc: _,
+                delay,
+                product,
+            } => Doc::text("(express-after")
+                .append(Doc::line())
+                .append(delay.to_doc())
+                .append(Doc::line())
+                .append(product.to_doc())
+                .append(")")
+                .group(),
+            GeneStatement::WithProbability {
+                fc: _,
+                probability,
+                statement,
+            } => Doc::text("(with-probability")
+                .append(Doc::line())
+                .append(probability.to_doc())
+                .append(Doc::line())
+                .append(statement.to_doc())
+                .append(")")
+                .group(),
+            GeneStatement::Let { fc: _, name, value } => Doc::text("(let")
+                .append(Doc::line())
+                .append(name.to_doc())
+                .append(Doc::line())
+                .append(value.to_doc())
+                .append(")")
+                .group(),
+            GeneStatement::If {
+                fc: _,
+                cond,
+                then_body,
+                else_body,
+            } => Doc::text("(if")
+                .append(Doc::line())
+                .append(cond.to_doc())
+                .append(Doc::line())
+                .append(then_body.to_doc())
+                .append(Doc::line())
+                .append(else_body.to_doc())
+                .append(")")
+                .nest(4)
+                .group(),
+            GeneStatement::Repeat { fc: _, count, body } => Doc::text("(repeat")
+                .append(Doc::line())
+                .append(count.to_doc())
+                .append(Doc::line())
+                .append(body.to_doc())
+                .append(")")
+                .nest(4)
+                .group(),
         }
     }
 }
 impl ToDoc for Expression {
-    fn to_doc(&self) -> Doc {
+    fn to_doc(&self) -> Doc<'_> {
         match self {
             Expression::Literal(l) => l.to_doc(),
             Expression::Variable(v) => v.to_doc(),
@@ -250,32 +548,157 @@ impl ToDoc for Expression {
                 .append(Doc::line())
                 .append(c.to_doc())
                 .append(")"),
+            Expression::FilteredConcentration {
+                fc: _,
+                name,
+                filter,
+            } => Doc::text("(concentration")
+                .append(Doc::line())
+                .append(name.to_doc())
+                .append(Doc::line())
+                .append("where")
+                .append(Doc::line())
+                .append(filter.to_doc())
+                .append(")")
+                .group(),
+            Expression::InstanceField { fc: _, field_name } => {
+                Doc::text("(. ").append(field_name.to_doc()).append(")")
+            }
+            Expression::Iteration(_) => Doc::text("@iteration"),
+            Expression::Time(_) => Doc::text("@time"),
+            Expression::Aggregate {
+                fc: _,
+                func,
+                record,
+                field,
+            } => Doc::text("(")
+                .append(func.to_doc())
+                .append(Doc::line())
+                .append(record.to_doc())
+                .append(".")
+                .append(field.to_doc())
+                .append(")")
+                .group(),
+            Expression::List {
+                fc: _,
+                elem_ty,
+                elems,
+            } => {
+                let list = Doc::text("(list");
+                let list = if let Some(ty) = elem_ty {
+                    list.append(Doc::line()).append(ty.to_doc())
+                } else {
+                    list
+                };
+                list.append(Doc::line())
+                    .append(elems.to_doc())
+                    .append(")")
+                    .group()
+            }
+            Expression::OptionTest {
+                fc: _,
+                expr,
+                is_some,
+            } => Doc::text(if *is_some { "(is-some" } else { "(is-none" })
+                .append(Doc::line())
+                .append(expr.to_doc())
+                .append(")")
+                .group(),
+            Expression::Variant {
+                fc: _,
+                enum_name,
+                variant,
+                fields,
+            } => Doc::text("(variant")
+                .append(Doc::line())
+                .append(enum_name.to_doc())
+                .append("::")
+                .append(variant.to_doc())
+                .append(Doc::line())
+                .append(fields.to_doc())
+                .append(")")
+                .group(),
+            Expression::Match { fc: _, expr, arms } => Doc::text("(match")
+                .append(Doc::line())
+                .append(expr.to_doc())
+                .append(Doc::line())
+                .append(arms.to_doc())
+                .append(")")
+                .nest(4)
+                .group(),
+            Expression::Call {
+                fc: _,
+                name,
+                base,
+                arguments,
+            } => Doc::text("(call")
+                .append(Doc::line())
+                .append(name.to_doc())
+                .append(Doc::line())
+                .append(record_base_to_doc(base.as_deref()))
+                .append(arguments.to_doc())
+                .append(")")
+                .group(),
+        }
+    }
+}
+impl ToDoc for MatchArm {
+    fn to_doc(&self) -> Doc<'_> {
+        Doc::text("(=>")
+            .append(Doc::line())
+            .append(self.pattern.to_doc())
+            .append(Doc::line())
+            .append(self.body.to_doc())
+            .append(")")
+            .group()
+    }
+}
+impl ToDoc for Pattern {
+    fn to_doc(&self) -> Doc<'_> {
+        match self {
+            Pattern::Wildcard(_) => Doc::text("_"),
+            Pattern::Variant {
+                fc: _,
+                enum_name,
+                variant,
+                binds,
+            } => enum_name
+                .to_doc()
+                .append("::")
+                .append(variant.to_doc())
+                .append(Doc::space())
+                .append(binds.to_doc())
+                .group(),
         }
     }
 }
 impl ToDoc for Literal {
-    fn to_doc(&self) -> Doc {
+    fn to_doc(&self) -> Doc<'_> {
         match self {
             Literal::Bool(_, b) => Doc::as_string(b),
             Literal::Integer(_, i) => Doc::as_string(i),
+            Literal::Float(_, f) => Doc::text(format!("{:?}", f)),
+            Literal::None(_) => Doc::text("none"),
             Literal::String(_, s) => Doc::text(format!("{:?}", s)),
         }
     }
 }
 impl ToDoc for PrefixOperator {
-    fn to_doc(&self) -> Doc {
+    fn to_doc(&self) -> Doc<'_> {
         match self {
             PrefixOperator::Neg => Doc::text("-"),
+            PrefixOperator::Not => Doc::text("not"),
         }
     }
 }
 impl ToDoc for InfixOperator {
-    fn to_doc(&self) -> Doc {
+    fn to_doc(&self) -> Doc<'_> {
         match self {
             InfixOperator::Add => Doc::text("+"),
             InfixOperator::Sub => Doc::text("-"),
             InfixOperator::Mul => Doc::text("*"),
             InfixOperator::Div => Doc::text("/"),
+            InfixOperator::Mod => Doc::text("%"),
             InfixOperator::Eq => Doc::text("="),
             InfixOperator::Neq => Doc::text("≠"),
             InfixOperator::Lt => Doc::text("<"),
@@ -284,12 +707,13 @@ impl ToDoc for InfixOperator {
             InfixOperator::Gte => Doc::text("≥"),
             InfixOperator::And => Doc::text("and"),
             InfixOperator::Or => Doc::text("or"),
+            InfixOperator::Xor => Doc::text("xor"),
         }
     }
 }
 
 impl ToDoc for (Identifier, Type) {
-    fn to_doc(&self) -> Doc {
+    fn to_doc(&self) -> Doc<'_> {
         Doc::text("(")
             .append(
                 self.0
@@ -304,7 +728,22 @@ impl ToDoc for (Identifier, Type) {
 }
 
 impl ToDoc for (Identifier, Expression) {
-    fn to_doc(&self) -> Doc {
+    fn to_doc(&self) -> Doc<'_> {
+        Doc::text("(")
+            .append(
+                self.0
+                    .to_doc()
+                    .append(Doc::space())
+                    .append(self.1.to_doc())
+                    .group(),
+            )
+            .append(")")
+            .group()
+    }
+}
+
+impl ToDoc for (Identifier, Identifier) {
+    fn to_doc(&self) -> Doc<'_> {
         Doc::text("(")
             .append(
                 self.0
@@ -3,7 +3,7 @@ use std::path::PathBuf;
 use cytosol::{
     driver::{Driver, DriverExecutionState, DriverRunner},
     hir::Program,
-    runtime::CellEnv,
+    runtime::{CellEnv, RecordBuilder},
 };
 
 use clap::Parser;
@@ -43,6 +43,14 @@ struct Arguments {
     #[clap(long)]
     no_run: bool,
 
+    /// Seed for the random choices of the run, so that it can be reproduced
+    #[clap(long)]
+    seed: Option<u64>,
+
+    /// How much the simulated time advances with every iteration
+    #[clap(long)]
+    time_step: Option<f64>,
+
     file_paths: Vec<PathBuf>,
 }
 
@@ -68,7 +76,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         return Ok(());
     }
 
-    execute(&prog, &mut runner);
+    execute(&prog, &mut runner, &args);
 
     if args.perf_report {
         runner
@@ -87,20 +95,30 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
-fn execute<D: Driver>(prog: &Program, runner: &mut DriverRunner<D>) {
+fn execute<D: Driver>(prog: &Program, runner: &mut DriverRunner<D>, args: &Arguments) {
     let mut exec_state = DriverExecutionState::default();
 
+    if let Some(time_step) = args.time_step {
+        exec_state.set_time_step(time_step);
+    }
+
     {
         let ctx = exec_state.program_context();
         ctx.set_extern_function("print_line", |s: String| println!("{}", s));
         ctx.set_extern_function("print_string", |s: String| print!("{}", s));
         ctx.set_extern_function("print_int", |i: isize| print!("{}", i));
+
+        if let Some(seed) = args.seed {
+            ctx.set_seed(seed);
+        }
     }
 
     let mut env = CellEnv::default();
 
     if let Some(id) = prog.record_by_name("Start") {
-        env.add_record(1, id, vec![]);
+        if let Some(fields) = RecordBuilder::new(prog, id).build() {
+            env.add_record(1, id, fields);
+        }
     }
 
     runner.run(prog, &mut exec_state, &mut env, 300);
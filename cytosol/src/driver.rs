@@ -4,7 +4,8 @@ use codespan_reporting::files::SimpleFiles;
 use cytosol_hir::{ast_to_hir::Error as AstToHirError, Program};
 use cytosol_parser::ParseError;
 use cytosol_runtime::{
-    run_gene, run_rules, CellEnv, CellEnvSummary, ExecutionPlan, ProgramContext, RuntimeVars,
+    run_gene, run_rules, CellEnv, CellEnvSummary, ExecutionPlan, GeneCooldowns, ProgramContext,
+    RuntimeVars,
 };
 use cytosol_syntax::{File, FileId};
 
@@ -58,7 +59,7 @@ pub struct DriverRunner<D: Driver = DefaultDriver> {
 
 impl Default for DriverRunner {
     fn default() -> Self {
-        Self::new(DefaultDriver::default())
+        Self::new(DefaultDriver)
     }
 }
 
@@ -203,12 +204,27 @@ impl Driver for DefaultDriver {
     }
 }
 
-#[derive(Default)]
 pub struct DriverExecutionState {
     prog_ctx: ProgramContext,
     cell_env_summ: CellEnvSummary,
     exec_plan: ExecutionPlan,
     runtime_vars: RuntimeVars,
+    gene_cooldowns: GeneCooldowns,
+    /// How much `@time` advances with every iteration
+    time_step: f64,
+}
+
+impl Default for DriverExecutionState {
+    fn default() -> Self {
+        Self {
+            prog_ctx: ProgramContext::default(),
+            cell_env_summ: CellEnvSummary::default(),
+            exec_plan: ExecutionPlan::default(),
+            runtime_vars: RuntimeVars::default(),
+            gene_cooldowns: GeneCooldowns::default(),
+            time_step: 1.0,
+        }
+    }
 }
 
 impl DriverExecutionState {
@@ -216,45 +232,95 @@ impl DriverExecutionState {
         &mut self.prog_ctx
     }
 
+    /// The number of iterations that ran so far, which is the value of
+    /// `@iteration` during the next one.
+    pub fn iteration(&self) -> usize {
+        self.prog_ctx.iteration()
+    }
+
+    pub fn time(&self) -> f64 {
+        self.prog_ctx.time()
+    }
+
+    /// Sets the value of `@time`. Drivers that simulate time in their own
+    /// way can call this before every iteration.
+    pub fn set_time(&mut self, time: f64) {
+        self.prog_ctx.set_time(time);
+    }
+
+    /// Sets how much `@time` advances after every iteration, `1.0` by default.
+    pub fn set_time_step(&mut self, time_step: f64) {
+        self.time_step = time_step;
+    }
+
+    /// Advances `@iteration`, `@time` and the delays of `express after` after
+    /// an iteration ran. Returns `false` if no records are pending.
+    fn finish_iteration(&mut self, prog: &Program, env: &mut CellEnv) -> bool {
+        self.prog_ctx.advance_iteration();
+        let time = self.prog_ctx.time() + self.time_step;
+        self.prog_ctx.set_time(time);
+
+        env.advance_pending(prog)
+    }
+
     pub fn run_gene_stage(&mut self, prog: &Program, env: &mut CellEnv) -> RunResult {
-        env.summary(&mut self.cell_env_summ);
-        self.exec_plan
-            .prepare_gene_execution(prog, &mut self.cell_env_summ);
+        env.forget_removed_instances(prog);
+        env.summary(prog, &mut self.cell_env_summ);
+        // genes waiting for their cooldown might run again later
+        let any_waiting = self.gene_cooldowns.any_waiting(prog, &self.cell_env_summ);
+        self.exec_plan.prepare_gene_execution(
+            &mut self.prog_ctx,
+            prog,
+            &mut self.cell_env_summ,
+            &self.gene_cooldowns,
+        );
+        self.gene_cooldowns.tick();
 
         let mut ran_any_genes = false;
         for gene_id in self.exec_plan.eligable_genes() {
             self.runtime_vars.clear();
 
-            run_gene(
+            let ran = run_gene(
                 &mut self.prog_ctx,
                 prog,
                 env,
                 &mut self.runtime_vars,
                 gene_id,
             );
-            ran_any_genes = true;
+            if ran {
+                self.gene_cooldowns.start(prog, gene_id);
+            }
+            // a `gene on` without new instances is just waiting for them
+            ran_any_genes |= ran || !prog[gene_id].on_arrival;
         }
 
-        if ran_any_genes {
+        if ran_any_genes || any_waiting {
             RunResult::MadeProgress
         } else {
             RunResult::NoProgress
         }
     }
 
+    /// Runs the rules, which is the last stage of an iteration, so the
+    /// iteration is finished afterwards.
     pub fn run_rule_stage(&mut self, prog: &Program, env: &mut CellEnv) -> RunResult {
-        env.summary(&mut self.cell_env_summ);
+        env.summary(prog, &mut self.cell_env_summ);
         self.exec_plan
-            .prepare_rule_execution(prog, &mut self.cell_env_summ);
+            .prepare_rule_execution(&mut self.prog_ctx, prog, &mut self.cell_env_summ);
 
         let ran_any_rules = run_rules(
+            &mut self.prog_ctx,
             prog,
             env,
             &mut self.runtime_vars,
             self.exec_plan.eligable_rules(),
         );
 
-        if ran_any_rules {
+        // records that will appear later count as progress, otherwise the
+        // run would stop while waiting for them
+        let any_pending = self.finish_iteration(prog, env);
+
+        if ran_any_rules || any_pending {
             RunResult::MadeProgress
         } else {
             RunResult::NoProgress
@@ -276,3 +342,41 @@ impl RunResult {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use cytosol_runtime::RecordBuilder;
+
+    #[test]
+    fn cooldown_without_factors_stops_run() {
+        let source = r#"
+            record Start
+            record Spark
+            record Done
+
+            gene (Start) cooldown 50 {
+                express Spark
+            }
+
+            rule (Start, Spark) -> Done
+        "#;
+
+        let mut runner = DriverRunner::default();
+        runner.add_file_from_string("<test>", source.to_string());
+        let mut prog = Program::new();
+        assert!(runner.compile(&mut prog).is_ok());
+
+        let mut env = CellEnv::default();
+        let start = prog.record_by_name("Start").unwrap();
+        env.add_record(1, start, RecordBuilder::new(&prog, start).build().unwrap());
+
+        let mut exec_state = DriverExecutionState::default();
+        runner.run(&prog, &mut exec_state, &mut env, None);
+
+        // the gene can't run again once `Start` is gone, so there is nothing
+        // to wait for
+        assert_eq!(exec_state.iteration(), 2);
+    }
+}
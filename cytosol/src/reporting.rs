@@ -101,7 +101,28 @@ pub(crate) fn report_hir_translate_errors<'a>(
 ) {
     let mut diags = vec![];
     for err in errs {
-        let diag = match err {
+        // errors in the rules and genes of a template are reported like the
+        // error itself, pointing at the instantiation as well
+        let mut err = err;
+        let mut instantiation_labels = vec![];
+        while let Error::InTemplateInstantiation {
+            template_name,
+            instantiation,
+            error,
+        } = err
+        {
+            instantiation_labels.push(
+                Label::secondary(instantiation.file, instantiation.range())
+                    .with_message(format!("in this instantiation of `{}`", template_name.1)),
+            );
+            instantiation_labels.push(
+                Label::secondary(template_name.0.file, template_name.0.range())
+                    .with_message(format!("template `{}` defined here", template_name.1)),
+            );
+            err = error;
+        }
+
+        let mut diag = match err {
             Error::RedefinedItem {
                 redef_name,
                 orig_name,
@@ -137,6 +158,44 @@ pub(crate) fn report_hir_translate_errors<'a>(
                     .with_message(message)
                     .with_labels(labels)
             }
+            Error::DuplicateEnumVariant {
+                enum_name,
+                variant,
+                first_occurance,
+            } => {
+                let message = format!(
+                    "duplicated variant `{}` in enum `{}`",
+                    variant.1, enum_name.1
+                );
+                let labels = vec![
+                    Label::primary(variant.0.file, variant.0.range())
+                        .with_message("duplicate variant"),
+                    Label::secondary(first_occurance.file, first_occurance.range())
+                        .with_message("first occurance of variant name"),
+                ];
+                Diagnostic::error()
+                    .with_message(message)
+                    .with_labels(labels)
+            }
+            Error::DuplicateVariantField {
+                variant,
+                field_name,
+                first_occurance,
+            } => {
+                let message = format!(
+                    "duplicated field `{}` in variant `{}`",
+                    field_name.1, variant.1
+                );
+                let labels = vec![
+                    Label::primary(field_name.0.file, field_name.0.range())
+                        .with_message("duplicate field"),
+                    Label::secondary(first_occurance.file, first_occurance.range())
+                        .with_message("first occurance of field name"),
+                ];
+                Diagnostic::error()
+                    .with_message(message)
+                    .with_labels(labels)
+            }
             Error::RecursiveRecordDefinitions { defs } => {
                 let message = if defs.len() > 1 {
                     "recursive record types"
@@ -171,6 +230,164 @@ pub(crate) fn report_hir_translate_errors<'a>(
                     .with_message(message)
                     .with_labels(labels)
             }
+            Error::UnknownTemplate { name } => {
+                let message = format!("unknown template `{}`", name.1);
+                let label = Label::primary(name.0.file, name.0.range())
+                    .with_message("no template with this name");
+                Diagnostic::error()
+                    .with_message(message)
+                    .with_labels(vec![label])
+            }
+            Error::DuplicateTemplateParameter {
+                template_name,
+                param,
+                first_occurance,
+            } => {
+                let message = format!(
+                    "duplicated type parameter `{}` in template `{}`",
+                    param.1, template_name.1
+                );
+                let labels = vec![
+                    Label::primary(param.0.file, param.0.range())
+                        .with_message("duplicate parameter"),
+                    Label::secondary(first_occurance.file, first_occurance.range())
+                        .with_message("first occurance of parameter name"),
+                ];
+                Diagnostic::error()
+                    .with_message(message)
+                    .with_labels(labels)
+            }
+            Error::TemplateArgumentCount {
+                template_name,
+                instantiation,
+                expected,
+                found,
+            } => {
+                let message = format!(
+                    "template `{}` takes {} type arguments but {} were given",
+                    template_name.1, expected, found
+                );
+                let labels = vec![
+                    Label::primary(instantiation.file, instantiation.range())
+                        .with_message(format!("expected {} type arguments", expected)),
+                    Label::secondary(template_name.0.file, template_name.0.range())
+                        .with_message("template defined here"),
+                ];
+                Diagnostic::error()
+                    .with_message(message)
+                    .with_labels(labels)
+            }
+            Error::TemplateArgumentNotRecord { arg } => {
+                let message = format!("type `{}` cannot be used as a template argument", arg.1);
+                let label = Label::primary(arg.0.file, arg.0.range())
+                    .with_message("templates can only be instantiated with records");
+                Diagnostic::error()
+                    .with_message(message)
+                    .with_labels(vec![label])
+            }
+            Error::UnknownRecordFamily { name } => {
+                let message = format!("unknown record family `{}`", name.1);
+                let label = Label::primary(name.0.file, name.0.range())
+                    .with_message("no record with this name");
+                Diagnostic::error()
+                    .with_message(message)
+                    .with_labels(vec![label])
+            }
+            Error::IncompatibleFamilyField {
+                record_name,
+                family_name,
+                field_name,
+                expected,
+                found,
+            } => {
+                let (expected, _) = prog.type_name(*expected).unwrap();
+
+                let message = format!(
+                    "record `{}` does not have the field `{}` of family `{}`",
+                    record_name.1, field_name.1, family_name.1
+                );
+
+                let primary = match found {
+                    Some((found_name, found_ty)) => {
+                        let (found, _) = prog.type_name(*found_ty).unwrap();
+                        Label::primary(found_name.0.file, found_name.0.range()).with_message(
+                            format!("this field has type `{}` instead of `{}`", found, expected),
+                        )
+                    }
+                    None => Label::primary(record_name.0.file, record_name.0.range())
+                        .with_message(format!("missing field `{}`", field_name.1)),
+                };
+
+                let labels = vec![
+                    primary,
+                    Label::secondary(field_name.0.file, field_name.0.range())
+                        .with_message(format!("field declared with type `{}` here", expected)),
+                ];
+
+                Diagnostic::error()
+                    .with_message(message)
+                    .with_labels(labels)
+            }
+            Error::NestedRecordFamily {
+                record_name,
+                family_name,
+            } => {
+                let message = format!(
+                    "record `{}` can't be in family `{}`",
+                    record_name.1, family_name.1
+                );
+                let labels = vec![
+                    Label::primary(family_name.0.file, family_name.0.range())
+                        .with_message("this record is part of a family itself"),
+                    Label::secondary(record_name.0.file, record_name.0.range())
+                        .with_message("families can't be nested"),
+                ];
+                Diagnostic::error()
+                    .with_message(message)
+                    .with_labels(labels)
+            }
+            Error::RecursiveConstDefinitions { defs } => {
+                let message = if defs.len() > 1 {
+                    "recursive constant definitions"
+                } else {
+                    "recursive constant definition"
+                };
+
+                let mut defs = defs.clone();
+                defs.sort_by_key(|k| Reverse(*k));
+
+                let labels = defs
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, fc)| {
+                        let style = if i == 0 {
+                            LabelStyle::Primary
+                        } else {
+                            LabelStyle::Secondary
+                        };
+
+                        let message = if i == 0 {
+                            "value depends on itself"
+                        } else {
+                            "value is part of a recursive cycle"
+                        };
+
+                        Label::new(style, fc.file, fc.range()).with_message(message)
+                    })
+                    .collect();
+
+                Diagnostic::error()
+                    .with_message(message)
+                    .with_labels(labels)
+            }
+            Error::NonConstantExpression { fc } => {
+                let message = "expression can't be evaluated at compile time";
+                let label = Label::primary(fc.file, fc.range())
+                    .with_message("only literals, operators and other constants are allowed");
+                Diagnostic::error()
+                    .with_message(message)
+                    .with_labels(vec![label])
+            }
             Error::UnknownType { name } => {
                 let message = format!("unknown type `{}`", name.1);
                 let label =
@@ -206,6 +423,14 @@ pub(crate) fn report_hir_translate_errors<'a>(
                     .with_message(message)
                     .with_labels(vec![label])
             }
+            Error::InvalidProductType { name } => {
+                let message = format!("type `{}` cannot be used as a product", name.1);
+                let label = Label::primary(name.0.file, name.0.range())
+                    .with_message("only records can be used as products");
+                Diagnostic::error()
+                    .with_message(message)
+                    .with_labels(vec![label])
+            }
             Error::ZeroBind {
                 item,
                 bind_number,
@@ -430,6 +655,20 @@ pub(crate) fn report_hir_translate_errors<'a>(
                     .with_message(message)
                     .with_labels(labels)
             }
+            Error::ParamInPattern { name, param_name } => {
+                let message = format!("parameter `{}` can't be used in a field pattern", name.1);
+
+                let labels = vec![
+                    Label::primary(name.0.file, name.0.range())
+                        .with_message("parameters can change at runtime"),
+                    Label::secondary(param_name.0.file, param_name.0.range())
+                        .with_message("parameter declared here"),
+                ];
+
+                Diagnostic::error()
+                    .with_message(message)
+                    .with_labels(labels)
+            }
             Error::UndefinedVariable { name, in_scope: _ } => {
                 // TODO suggest similar names in scope
                 let message = format!("undefined variable `{}`", name.1);
@@ -569,6 +808,335 @@ pub(crate) fn report_hir_translate_errors<'a>(
                     .with_message(message)
                     .with_labels(labels)
             }
+            Error::UnknownFunction { name } => {
+                let message = format!("unknown function `{}`", name.1);
+                let label =
+                    Label::primary(name.0.file, name.0.range()).with_message("unknown function");
+                Diagnostic::error()
+                    .with_message(message)
+                    .with_labels(vec![label])
+            }
+            Error::BuiltinCallWithBase { name, base_fc } => {
+                let message = format!(
+                    "builtin function `{}` can't copy fields from a base value",
+                    name.1
+                );
+                let labels = vec![
+                    Label::primary(base_fc.file, base_fc.range())
+                        .with_message("`..` is only allowed when constructing records"),
+                    Label::secondary(name.0.file, name.0.range())
+                        .with_message("this is a builtin function"),
+                ];
+                Diagnostic::error()
+                    .with_message(message)
+                    .with_labels(labels)
+            }
+            Error::InstanceFieldOutsideFilter { fc } => {
+                let label = Label::primary(fc.file, fc.range())
+                    .with_message("no instance to take the field from");
+                let note = "instance fields can only be used in a concentration filter, like `[Person where .age >= 18]`".to_string();
+                Diagnostic::error()
+                    .with_message("instance field used outside of a concentration filter")
+                    .with_labels(vec![label])
+                    .with_notes(vec![note])
+            }
+            Error::AggregateOfNonNumber {
+                field_name,
+                type_id,
+            } => {
+                let (type_name, _) = prog.type_name(*type_id).unwrap();
+                let message = format!(
+                    "can't aggregate field `{}` of type `{}`",
+                    field_name.1, type_name
+                );
+                let label = Label::primary(field_name.0.file, field_name.0.range())
+                    .with_message("expected a field of type `int` or `float`");
+                Diagnostic::error()
+                    .with_message(message)
+                    .with_labels(vec![label])
+            }
+            Error::IrreversibleNamedBinding { rule_fc, name } => {
+                let message = format!("named binding `{}` can't be reversed", name.1);
+                let labels = vec![
+                    Label::primary(name.0.file, name.0.range())
+                        .with_message("the whole record can't be expressed again"),
+                    Label::secondary(rule_fc.file, rule_fc.range())
+                        .with_message("in this reversible rule"),
+                ];
+                let note = "bind the fields with a field pattern instead, like `Person(name: n)`"
+                    .to_string();
+                Diagnostic::error()
+                    .with_message(message)
+                    .with_labels(labels)
+                    .with_notes(vec![note])
+            }
+            Error::IrreversibleProductBase { rule_fc, base_fc } => {
+                let labels = vec![
+                    Label::primary(base_fc.file, base_fc.range())
+                        .with_message("a base value can't be used as a field pattern"),
+                    Label::secondary(rule_fc.file, rule_fc.range())
+                        .with_message("in this reversible rule"),
+                ];
+                Diagnostic::error()
+                    .with_message("product with a base value can't be reversed")
+                    .with_labels(labels)
+            }
+            Error::IrreversibleQuantity {
+                rule_fc,
+                quantity_fc,
+            } => {
+                let labels = vec![
+                    Label::primary(quantity_fc.file, quantity_fc.range())
+                        .with_message("only literal quantities can be used as bind quantities"),
+                    Label::secondary(rule_fc.file, rule_fc.range())
+                        .with_message("in this reversible rule"),
+                ];
+                Diagnostic::error()
+                    .with_message("computed quantity can't be reversed")
+                    .with_labels(labels)
+            }
+            Error::IrreversibleFieldBinding { rule_fc, name } => {
+                let message = format!("field binding `{}` can't be reversed", name.1);
+                let labels = vec![
+                    Label::primary(name.0.file, name.0.range())
+                        .with_message("no product binds this in the reverse direction"),
+                    Label::secondary(rule_fc.file, rule_fc.range())
+                        .with_message("in this reversible rule"),
+                ];
+                let note = "use the variable as a field of a product, the `where` clause \
+                            only belongs to the forward direction"
+                    .to_string();
+                Diagnostic::error()
+                    .with_message(message)
+                    .with_labels(labels)
+                    .with_notes(vec![note])
+            }
+            Error::InvalidGeneTrigger { gene_fc, on_fc } => {
+                let labels = vec![
+                    Label::primary(gene_fc.file, gene_fc.range()),
+                    Label::secondary(on_fc.file, on_fc.range())
+                        .with_message("runs for every new instance of its factor"),
+                ];
+                let note = "use a single factor like `gene on (m: Message)`".to_string();
+                Diagnostic::error()
+                    .with_message("`gene on` needs a single factor without a quantity")
+                    .with_labels(labels)
+                    .with_notes(vec![note])
+            }
+            Error::NotAnEnum { name } => {
+                let message = format!("type `{}` is not an enum", name.1);
+                let label = Label::primary(name.0.file, name.0.range())
+                    .with_message("expected the name of an enum");
+                Diagnostic::error()
+                    .with_message(message)
+                    .with_labels(vec![label])
+            }
+            Error::UnknownEnumVariant { enum_name, variant } => {
+                let message = format!(
+                    "enum `{}` has no variant called `{}`",
+                    enum_name.1, variant.1
+                );
+                let label = Label::primary(variant.0.file, variant.0.range())
+                    .with_message("unknown variant");
+                Diagnostic::error()
+                    .with_message(message)
+                    .with_labels(vec![label])
+            }
+            Error::VariantMissingField {
+                variant,
+                use_fc,
+                missing_field,
+            } => {
+                let message = format!(
+                    "variant `{}` is missing a `{}` field",
+                    variant.1, missing_field.1
+                );
+
+                let labels = vec![
+                    Label::primary(use_fc.file, use_fc.range())
+                        .with_message("this variant is incomplete"),
+                    Label::secondary(missing_field.0.file, missing_field.0.range())
+                        .with_message("this field is missing"),
+                ];
+
+                Diagnostic::error()
+                    .with_message(message)
+                    .with_labels(labels)
+            }
+            Error::VariantDuplicateField {
+                variant,
+                duplicate_field,
+                original_field,
+            } => {
+                let message = format!(
+                    "duplicated field `{}` on variant `{}`",
+                    duplicate_field.1, variant.1
+                );
+
+                let labels = vec![
+                    Label::primary(duplicate_field.0.file, duplicate_field.0.range())
+                        .with_message("this is a duplicate field"),
+                    Label::secondary(original_field.0.file, original_field.0.range())
+                        .with_message("field already supplied here"),
+                ];
+
+                Diagnostic::error()
+                    .with_message(message)
+                    .with_labels(labels)
+            }
+            Error::VariantUnknownField { variant, field } => {
+                let message = format!("unknown field `{}` on variant `{}`", field.1, variant.1);
+
+                let labels = vec![
+                    Label::primary(field.0.file, field.0.range()).with_message("unknown field")
+                ];
+
+                Diagnostic::error()
+                    .with_message(message)
+                    .with_labels(labels)
+            }
+            Error::MatchOnNonEnum { expr, type_id } => {
+                let expr_fc = prog.exprs_fc[expr];
+                let (type_name, _) = prog.type_name(*type_id).unwrap();
+                let message = format!("cannot match on a value of type `{}`", type_name);
+                let labels = vec![Label::primary(expr_fc.file, expr_fc.range())
+                    .with_message("only enum values can be matched on")];
+                Diagnostic::error()
+                    .with_message(message)
+                    .with_labels(labels)
+            }
+            Error::NonExhaustiveMatch { fc, missing } => {
+                let message = "match does not handle all variants";
+                let labels = vec![Label::primary(fc.file, fc.range())
+                    .with_message("some variants are not handled")];
+
+                let notes = std::iter::once("the following variants are missing:".to_string())
+                    .chain(missing.iter().map(|v| format!("- `{}`", v.1)))
+                    .collect::<Vec<_>>();
+
+                Diagnostic::error()
+                    .with_message(message)
+                    .with_labels(labels)
+                    .with_notes(vec![notes.join("\n")])
+            }
+            Error::UnreachableMatchArm { fc } => {
+                let message = "unreachable match arm";
+                let label = Label::primary(fc.file, fc.range())
+                    .with_message("this pattern is already covered by previous arms");
+                Diagnostic::error()
+                    .with_message(message)
+                    .with_labels(vec![label])
+            }
+            Error::BuiltinMissingParameter {
+                func_name,
+                call_fc,
+                missing_param,
+            } => {
+                let message = format!(
+                    "call to `{}` is missing the `{}` parameter",
+                    func_name.1, missing_param
+                );
+
+                let labels = vec![Label::primary(call_fc.file, call_fc.range())
+                    .with_message("this function call is incomplete")];
+
+                Diagnostic::error()
+                    .with_message(message)
+                    .with_labels(labels)
+            }
+            Error::BuiltinUnknownParameter {
+                func_name,
+                parameter,
+            } => {
+                let message = format!(
+                    "unknown parameter `{}` in call to `{}`",
+                    parameter.1, func_name.1
+                );
+
+                let labels = vec![Label::primary(parameter.0.file, parameter.0.range())
+                    .with_message("unknown parameter")];
+
+                Diagnostic::error()
+                    .with_message(message)
+                    .with_labels(labels)
+            }
+            Error::TypeMismatchBuiltinCall {
+                func_name,
+                param_name,
+                expr,
+                expected_types,
+            } => {
+                let expr_fc = prog.expr_fc(*expr).unwrap();
+                let expr_ty = prog.expr_type(*expr).unwrap();
+                let (expr_ty_name, _) = prog.type_name(expr_ty).unwrap();
+
+                let message = format!(
+                    "parameter `{}` of `{}` cannot be of type `{}`",
+                    param_name, func_name.1, expr_ty_name
+                );
+
+                let expected_types = expected_types
+                    .iter()
+                    .filter_map(|id| prog.type_name(*id))
+                    .map(|(name, _)| format!("- type `{}`", name));
+
+                let labels = vec![Label::primary(expr_fc.file, expr_fc.range())
+                    .with_message(format!("this expression has type `{}`", expr_ty_name))];
+
+                let notes = std::iter::once(format!(
+                    "the parameter `{}` is compatible with:",
+                    param_name
+                ))
+                .chain(expected_types)
+                .collect::<Vec<_>>();
+
+                Diagnostic::error()
+                    .with_message(message)
+                    .with_labels(labels)
+                    .with_notes(vec![notes.join("\n")])
+            }
+            Error::BuiltinExpectedList {
+                func_name,
+                param_name,
+                expr,
+            } => {
+                let expr_fc = prog.expr_fc(*expr).unwrap();
+                let expr_ty = prog.expr_type(*expr).unwrap();
+                let (expr_ty_name, _) = prog.type_name(expr_ty).unwrap();
+
+                let message = format!(
+                    "parameter `{}` of `{}` has to be a list",
+                    param_name, func_name.1
+                );
+                let label = Label::primary(expr_fc.file, expr_fc.range())
+                    .with_message(format!("this expression has type `{}`", expr_ty_name));
+                Diagnostic::error()
+                    .with_message(message)
+                    .with_labels(vec![label])
+            }
+            Error::OptionTestOnNonOptional { expr, type_id } => {
+                let expr_fc = prog.exprs_fc[expr];
+                let (type_name, _) = prog.type_name(*type_id).unwrap();
+                let message = format!(
+                    "values of type `{}` are never `none`, so they can't be tested with `is`",
+                    type_name
+                );
+                let labels = vec![Label::primary(expr_fc.file, expr_fc.range())
+                    .with_message(format!("this expression has type `{}`", type_name))];
+                Diagnostic::error()
+                    .with_message(message)
+                    .with_labels(labels)
+            }
+            Error::UntypedEmptyList { fc } => {
+                let message = "cannot infer the element type of an empty list";
+                let label = Label::primary(fc.file, fc.range()).with_message("empty list");
+                Diagnostic::error()
+                    .with_message(message)
+                    .with_labels(vec![label])
+                    .with_notes(vec![
+                        "give the element type explicitly, like `list<int>[]`".to_string()
+                    ])
+            }
             Error::ConcentrationOfNonRecordType { fc: _, type_name } => {
                 let message = format!("concentration of type {} cannot be measured", type_name.1);
                 let labels = vec![Label::primary(type_name.0.file, type_name.0.range())
@@ -587,8 +1155,12 @@ pub(crate) fn report_hir_translate_errors<'a>(
                     .with_message(message)
                     .with_labels(labels)
             }
+            Error::InTemplateInstantiation { .. } => {
+                unreachable!("instantiation errors are unwrapped above")
+            }
         };
 
+        diag.labels.extend(instantiation_labels);
         diags.push(diag);
     }
 
@@ -604,6 +1176,6 @@ fn emit<'a>(
     let term_config = Config::default();
 
     for diag in diags {
-        codespan_reporting::term::emit(&mut writer, &term_config, files, &diag).unwrap();
+        codespan_reporting::term::emit(&mut writer, &term_config, files, diag).unwrap();
     }
 }